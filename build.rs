@@ -1,42 +1,123 @@
-use texture_packer::{*, importer::*, exporter::*, texture::*};
+// The packing code is shared with the game, which uses it to load textures at runtime
+#[path = "src/context/packing.rs"]
+mod packing;
+
 use codegen::*;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
 use std::path::*;
-use image::DynamicImage;
 
-fn load_image(path: &Path) -> DynamicImage {
-    println!("{}", path.display());
-    ImageImporter::import_from_file(path).unwrap()
+// Used for animations that aren't in the animation manifest
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+#[derive(Deserialize)]
+struct AnimationSettings {
+    frame_duration: f32,
+    #[serde(default)]
+    looping: bool
 }
 
-fn load_dir(path: &str, packer: &mut TexturePacker<DynamicImage>) {
-    for entry in std::fs::read_dir(path).unwrap() {
-        let path = entry.unwrap().path();
+// Textures with the same name and a number at the end (e.g. 'explosion_1.png', 'explosion_2.png')
+// are the frames of an animation, in the order of their numbers
+fn find_animations(names: &[&str]) -> BTreeMap<String, Vec<String>> {
+    let mut sequences: BTreeMap<String, Vec<(u32, String)>> = BTreeMap::new();
+
+    for name in names {
+        let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
 
-        if path.extension() == Some(std::ffi::OsStr::new("png")) {
-            let stem = path.file_stem().unwrap().to_str().unwrap();
-            let capitalised = case_style::CaseStyle::guess(stem).unwrap().to_pascalcase();
-            packer.pack_own(capitalised, load_image(&path)).unwrap();
+        if prefix.is_empty() || prefix.len() == name.len() {
+            continue;
         }
+
+        let number = name[prefix.len() ..].parse().unwrap();
+        sequences.entry(prefix.to_string()).or_default().push((number, name.to_string()));
     }
+
+    sequences.into_iter()
+        .filter(|(_, frames)| frames.len() > 1)
+        .map(|(name, mut frames)| {
+            frames.sort();
+            (name, frames.into_iter().map(|(_, frame)| frame).collect())
+        })
+        .collect()
 }
 
-fn main() {
-    let mut packer = TexturePacker::new_skyline(TexturePackerConfig {
-        trim: false,
-        texture_padding: 1,
-        .. Default::default()
-    });
+fn generate_animations(scope: &mut Scope, names: &[&str], settings: &BTreeMap<String, AnimationSettings>) {
+    let animations = find_animations(names);
+
+    for name in settings.keys() {
+        assert!(animations.contains_key(name), "'{}' is in the animation manifest, but there aren't any frames for it", name);
+    }
+
+    {
+        let animation_enum = scope.new_enum("Animation")
+            .derive("Copy, Clone, Serialize, Deserialize, PartialEq, Debug")
+            .vis("pub");
+
+        for name in animations.keys() {
+            animation_enum.push_variant(Variant::new(name));
+        }
+    }
+
+    let impl_block = scope.new_impl("Animation");
+
+    let mut frames_match_block = Block::new("match self");
+    let mut duration_match_block = Block::new("match self");
+    let mut looping_match_block = Block::new("match self");
+
+    for (name, frames) in &animations {
+        let frames: Vec<_> = frames.iter().map(|frame| format!("Image::{}", frame)).collect();
+        let (frame_duration, looping) = settings.get(name)
+            .map(|settings| (settings.frame_duration, settings.looping))
+            .unwrap_or((DEFAULT_FRAME_DURATION, false));
+
+        frames_match_block.line(&format!("Animation::{} => &[{}],", name, frames.join(", ")));
+        duration_match_block.line(&format!("Animation::{} => {:?},", name, frame_duration));
+        looping_match_block.line(&format!("Animation::{} => {:?},", name, looping));
+    }
+
+    impl_block.new_fn("frames")
+        .arg_self()
+        .vis("pub")
+        .ret("&'static [Image]")
+        .push_block(frames_match_block);
+
+    // How long each frame is shown for, in seconds
+    impl_block.new_fn("frame_duration")
+        .arg_self()
+        .vis("pub")
+        .ret("f32")
+        .push_block(duration_match_block);
+
+    impl_block.new_fn("looping")
+        .arg_self()
+        .vis("pub")
+        .ret("bool")
+        .push_block(looping_match_block);
+}
 
-    load_dir("resources", &mut packer);
-    load_dir("resources/models", &mut packer);
+// Embed every obj file in the models directory, so that the model manifest can refer to them by file name
+fn embed_models(path: &str) -> String {
+    let mut files = Vec::new();
 
-    let width = packer.width();
+    for entry in std::fs::read_dir(path).unwrap() {
+        let path = entry.unwrap().path();
 
-    if width % 64 != 0 {
-        let needed = 64 - (width % 64);
-        packer.set_row_padding(needed);
+        if path.extension() == Some(std::ffi::OsStr::new("obj")) {
+            let name = path.file_name().unwrap().to_str().unwrap().to_string();
+            let full_path = std::fs::canonicalize(&path).unwrap();
+            files.push(format!("    ({:?}, include_bytes!({:?})),", name, full_path.display().to_string()));
+        }
     }
 
+    files.sort();
+
+    format!("pub const EMBEDDED_MODELS: &[(&str, &[u8])] = &[\n{}\n];\n", files.join("\n"))
+}
+
+fn main() {
+    let packed = packing::pack_textures(&[Path::new("resources"), Path::new("resources/models")]).unwrap();
+
     let mut scope = Scope::new();
 
     {
@@ -45,32 +126,49 @@ fn main() {
                 .derive("Copy, Clone, Component, Serialize, Deserialize, PartialEq, Debug")
                 .vis("pub");
 
-            for (name, _) in packer.get_frames() {
+            for (name, _, _) in &packed.frames {
                 image_enum.push_variant(Variant::new(name));
             }
         }
 
         let impl_block = scope.new_impl("Image");    
 
+        let mut name_match_block = Block::new("match self");
+
         let mut offset_match_block = Block::new("match self");
 
         let mut dimensions_match_block = Block::new("match self");
 
-        for (name, frame) in packer.get_frames() {
-            let width = packer.width() as f32;
-            let height = packer.height() as f32;
+        for (name, offset, dimensions) in &packed.frames {
+            name_match_block.line(&format!("Image::{} => {:?},", name, name));
 
             dimensions_match_block.line(&format!(
                 "Image::{} => [{:?}, {:?}],",
-                name, frame.frame.w as f32 / width, frame.frame.h as f32 / height
+                name, dimensions[0], dimensions[1]
             ));
 
             offset_match_block.line(&format!(
                 "Image::{} => [{:?}, {:?}],",
-                name, frame.frame.x as f32 / width, frame.frame.y as f32 / height
+                name, offset[0], offset[1]
             ));
         }
 
+        let all: Vec<_> = packed.frames.iter()
+            .map(|(name, _, _)| format!("Image::{}", name))
+            .collect();
+
+        impl_block.new_fn("all")
+            .vis("pub")
+            .ret("&'static [Image]")
+            .line(&format!("&[{}]", all.join(", ")));
+
+        impl_block.new_fn("name")
+            .arg_self()
+            .vis("pub")
+            .ret("&'static str")
+            .push_block(name_match_block);
+
+        // Where the image is in the embedded texture
         impl_block.new_fn("dimensions")
             .arg_self()
             .vis("pub")
@@ -82,30 +180,23 @@ fn main() {
             .vis("pub")
             .ret("[f32; 2]")
             .push_block(offset_match_block);
-
-        impl_block.new_fn("translate")
-            .arg_self()
-            .vis("pub")
-            .arg("uv", "[f32; 2]")
-            .ret("[f32; 2]")
-            .line("let offset = self.offset();")
-            .line("let dim = self.dimensions();")
-            .line("[
-                offset[0] + uv[0] * dim[0],
-                offset[1] + uv[1] * dim[1]
-            ]");
     }
 
+    let manifest = std::fs::read_to_string("resources/animations.ron").unwrap();
+    let settings: BTreeMap<String, AnimationSettings> = ron::de::from_str(&manifest).unwrap();
+    let names: Vec<_> = packed.frames.iter().map(|(name, _, _)| name.as_str()).collect();
+    generate_animations(&mut scope, &names, &settings);
+
     let out_dir = std::env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("packed_textures.rs");
     std::fs::write(dest_path, &scope.to_string()).unwrap();
 
     print!("{}", scope.to_string());
 
+    std::fs::write(Path::new(&out_dir).join("embedded_models.rs"), embed_models("resources/models")).unwrap();
+
     //
     // Save the result
     //
-    let exporter = ImageExporter::export(&packer).unwrap();
-
-    exporter.save("resources/output/packed.png").unwrap();
+    packed.image.save("resources/output/packed.png").unwrap();
 }
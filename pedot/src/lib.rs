@@ -6,7 +6,10 @@ use std::ops::*;
 use std::slice::*;
 
 mod alignment;
+mod widgets;
+mod tests;
 pub use alignment::*;
+pub use widgets::*;
 
 
 
@@ -18,7 +21,7 @@ pub enum ButtonState {
     None
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Gui {
     screen_width: f32,
     screen_height: f32,
@@ -26,7 +29,9 @@ pub struct Gui {
     last_keypress: Option<VirtualKeyCode>,
     mouse_x: f32,
     mouse_y: f32,
-    mouse_clicked: bool
+    mouse_clicked: bool,
+    // Whether the mouse was pressed since the last clear
+    mouse_pressed: bool
 }
 
 impl Gui {
@@ -37,13 +42,15 @@ impl Gui {
             last_keypress: None,
             mouse_x: 0.0,
             mouse_y: 0.0,
-            mouse_clicked: false
+            mouse_clicked: false,
+            mouse_pressed: false
         }
     }
 
     pub fn clear(&mut self) {
         self.last_char = None;
         self.last_keypress = None;
+        self.mouse_pressed = false;
     }
 
     pub fn update(&mut self, event: &WindowEvent) {
@@ -52,17 +59,27 @@ impl Gui {
                 self.screen_width = width as f32;
                 self.screen_height = height as f32;
             },
-            WindowEvent::CursorMoved {position: PhysicalPosition {x, y}, ..} => {
-                self.mouse_x = x as f32;
-                self.mouse_y = y as f32;
-            },
-            WindowEvent::MouseInput {button: MouseButton::Left, state, ..} => self.mouse_clicked = state == ElementState::Pressed,
+            WindowEvent::CursorMoved {position: PhysicalPosition {x, y}, ..} => self.set_mouse(x as f32, y as f32),
+            WindowEvent::MouseInput {button, state, ..} => self.handle_mouse(button, state == ElementState::Pressed),
             WindowEvent::ReceivedCharacter(character) => self.last_char = Some(character),
             WindowEvent::KeyboardInput {input: KeyboardInput {virtual_keycode: Some(key), state: ElementState::Pressed, ..}, ..} => self.last_keypress = Some(key),
             _ => {}
         }
     }
 
+    // These can also be used for cursors that aren't the window's, such as one moved with a gamepad
+    pub fn set_mouse(&mut self, x: f32, y: f32) {
+        self.mouse_x = x;
+        self.mouse_y = y;
+    }
+
+    pub fn handle_mouse(&mut self, button: MouseButton, pressed: bool) {
+        if button == MouseButton::Left {
+            self.mouse_clicked = pressed;
+            self.mouse_pressed |= pressed;
+        }
+    }
+
     pub fn button<X: Into<HorizontalAlign>, Y: Into<VerticalAlign>>(&self, x: X, y: Y, width: f32, height: f32) -> ButtonState {
         let x = x.into();
         let y = y.into();
@@ -80,6 +97,14 @@ impl Gui {
         }
     }
 
+    pub fn mouse(&self) -> (f32, f32) {
+        (self.mouse_x, self.mouse_y)
+    }
+
+    pub fn mouse_pressed(&self) -> bool {
+        self.mouse_pressed
+    }
+
     pub fn key_pressed(&self, key: VirtualKeyCode) -> bool {
         self.last_keypress == Some(key)
     }
@@ -102,6 +127,7 @@ impl Gui {
     }
 }
 
+#[derive(Debug)]
 pub struct List<T> {
     entries: Vec<T>,
     index: usize
@@ -126,7 +152,7 @@ impl<T> List<T> {
 
     pub fn set_entries(&mut self, entries: Vec<T>) {
         self.entries = entries;
-        self.index = self.index.min(self.entries.len() - 1);
+        self.index = self.index.min(self.entries.len().saturating_sub(1));
     }
 
     pub fn clear_entries(&mut self) {
@@ -0,0 +1,50 @@
+#![cfg(test)]
+
+use crate::*;
+
+#[test]
+fn test_panel_layout() {
+    let gui = Gui::new(800.0, 600.0);
+
+    let mut panel = Panel::new(HorizontalAlign::Right(10.0), VerticalAlign::Bottom(10.0), 200.0);
+    panel.widgets = vec![Widget::button("Launch", ()), Widget::progress_bar("Health", 0.5, [1.0; 3])];
+    panel.layout(&gui);
+
+    // Two rows and the padding above and below them
+    assert_eq!(panel.rect(), Rect::new(590.0, 540.0, 200.0, 50.0));
+    assert!(panel.contains(600.0, 560.0));
+    assert!(!panel.contains(10.0, 10.0));
+
+    let mut primitives = Vec::new();
+    panel.draw(&gui, &mut primitives);
+
+    // The panel, the button text, and the bar's background, filling and text
+    assert_eq!(primitives.len(), 5);
+    assert_eq!(primitives[0], Primitive::Rect(panel.rect(), PANEL_COLOUR));
+    assert!(primitives.iter().any(|primitive| match primitive {
+        Primitive::Rect(rect, _) => rect.width == 95.0,
+        _ => false
+    }));
+}
+
+#[test]
+fn test_panel_interact() {
+    let mut gui = Gui::new(800.0, 600.0);
+
+    let mut panel = Panel::new(HorizontalAlign::Left(0.0), VerticalAlign::Top(0.0), 200.0);
+    panel.widgets = vec![Widget::label("Ships"), Widget::list(List::new(vec!["Carrier".to_string(), "Fighter".to_string()]), |index| index)];
+    panel.layout(&gui);
+
+    // Over the second entry of the list, below the label
+    gui.mouse_x = 10.0;
+    gui.mouse_y = 50.0;
+    assert_eq!(panel.interact(&gui), None);
+
+    gui.mouse_pressed = true;
+    assert_eq!(panel.interact(&gui), Some(1));
+
+    match &panel.widgets[1].contents {
+        Contents::List(list, _) => assert_eq!(list.index(), 1),
+        _ => unreachable!()
+    }
+}
@@ -0,0 +1,239 @@
+use crate::*;
+
+pub const ROW_HEIGHT: f32 = 20.0;
+const PADDING: f32 = 5.0;
+// How far tooltips are drawn from the mouse
+const TOOLTIP_OFFSET: f32 = 15.0;
+
+pub const PANEL_COLOUR: [f32; 3] = [0.1, 0.1, 0.15];
+pub const HOVER_COLOUR: [f32; 3] = [0.25, 0.25, 0.35];
+pub const BAR_BACKGROUND_COLOUR: [f32; 3] = [0.2, 0.2, 0.2];
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x, y, width, height
+        }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x <= self.x + self.width && y <= self.y + self.height
+    }
+}
+
+// What a renderer needs to draw for the widgets
+#[derive(PartialEq, Debug, Clone)]
+pub enum Primitive {
+    Rect(Rect, [f32; 3]),
+    // Positioned at the top left of the text
+    Text(String, f32, f32)
+}
+
+#[derive(Debug)]
+pub enum Contents<T> {
+    Label(String),
+    Button(String, T),
+    // The fraction of the bar that's filled and its colour
+    ProgressBar(String, f32, [f32; 3]),
+    // Clicking an entry selects it and makes an action from its index
+    List(List<String>, fn(usize) -> T)
+}
+
+#[derive(Debug)]
+pub struct Widget<T> {
+    pub contents: Contents<T>,
+    pub tooltip: Option<String>
+}
+
+impl<T> Widget<T> {
+    fn new(contents: Contents<T>) -> Self {
+        Self {
+            contents,
+            tooltip: None
+        }
+    }
+
+    pub fn label<S: Into<String>>(text: S) -> Self {
+        Self::new(Contents::Label(text.into()))
+    }
+
+    pub fn button<S: Into<String>>(text: S, action: T) -> Self {
+        Self::new(Contents::Button(text.into(), action))
+    }
+
+    pub fn progress_bar<S: Into<String>>(text: S, fraction: f32, colour: [f32; 3]) -> Self {
+        Self::new(Contents::ProgressBar(text.into(), fraction.max(0.0).min(1.0), colour))
+    }
+
+    pub fn list(entries: List<String>, action: fn(usize) -> T) -> Self {
+        Self::new(Contents::List(entries, action))
+    }
+
+    pub fn with_tooltip<S: Into<String>>(mut self, tooltip: S) -> Self {
+        self.tooltip = Some(tooltip.into());
+        self
+    }
+
+    fn rows(&self) -> usize {
+        match &self.contents {
+            Contents::List(list, _) => list.iter().count(),
+            _ => 1
+        }
+    }
+}
+
+// A column of widgets in a box, placed on the screen by its alignment. The widgets can be replaced at any
+// time (e.g. every frame), but `layout` has to be called afterwards to resize the panel.
+#[derive(Debug)]
+pub struct Panel<T> {
+    x: HorizontalAlign,
+    y: VerticalAlign,
+    width: f32,
+    pub widgets: Vec<Widget<T>>,
+    rect: Rect
+}
+
+impl<T: Clone> Panel<T> {
+    pub fn new<X: Into<HorizontalAlign>, Y: Into<VerticalAlign>>(x: X, y: Y, width: f32) -> Self {
+        Self {
+            x: x.into(),
+            y: y.into(),
+            width,
+            widgets: Vec::new(),
+            rect: Rect::new(0.0, 0.0, 0.0, 0.0)
+        }
+    }
+
+    // Work out where the panel is from its alignment. Aligning to the left or top places that edge,
+    // to the right or bottom places the opposite edge and to the middle places the centre.
+    pub fn layout(&mut self, gui: &Gui) {
+        let rows: usize = self.widgets.iter().map(Widget::rows).sum();
+        let height = rows as f32 * ROW_HEIGHT + PADDING * 2.0;
+
+        let x = gui.x_absolute(self.x) - match self.x {
+            HorizontalAlign::Left(_) => 0.0,
+            HorizontalAlign::Middle(_) => self.width / 2.0,
+            HorizontalAlign::Right(_) => self.width
+        };
+
+        let y = gui.y_absolute(self.y) - match self.y {
+            VerticalAlign::Top(_) => 0.0,
+            VerticalAlign::Middle(_) => height / 2.0,
+            VerticalAlign::Bottom(_) => height
+        };
+
+        self.rect = Rect::new(x, y, self.width, height);
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.widgets.is_empty()
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        !self.is_empty() && self.rect.contains(x, y)
+    }
+
+    // The area of each row, along with the index of its widget and the index of the row in a list
+    fn rows(&self) -> impl Iterator<Item=(Rect, usize, usize)> + '_ {
+        let rect = self.rect;
+        let mut y = rect.y + PADDING;
+
+        self.widgets.iter().enumerate()
+            .flat_map(|(index, widget)| (0 .. widget.rows()).map(move |row| (index, row)))
+            .map(move |(index, row)| {
+                let row_rect = Rect::new(rect.x + PADDING, y, rect.width - PADDING * 2.0, ROW_HEIGHT);
+                y += ROW_HEIGHT;
+                (row_rect, index, row)
+            })
+    }
+
+    fn hovered(&self, gui: &Gui) -> Option<(Rect, usize, usize)> {
+        let (x, y) = gui.mouse();
+        self.rows().find(|(rect, _, _)| rect.contains(x, y))
+    }
+
+    // Returns the action of the button or list entry that was clicked this frame
+    pub fn interact(&mut self, gui: &Gui) -> Option<T> {
+        if !gui.mouse_pressed() {
+            return None;
+        }
+
+        let (_, index, row) = self.hovered(gui)?;
+
+        match &mut self.widgets[index].contents {
+            Contents::Button(_, action) => Some(action.clone()),
+            Contents::List(list, action) => {
+                list.set_index(row);
+                Some(action(row))
+            },
+            _ => None
+        }
+    }
+
+    pub fn draw(&self, gui: &Gui, primitives: &mut Vec<Primitive>) {
+        if self.is_empty() {
+            return;
+        }
+
+        primitives.push(Primitive::Rect(self.rect, PANEL_COLOUR));
+
+        let hovered = self.hovered(gui).map(|(rect, _, _)| rect);
+
+        for (rect, index, row) in self.rows() {
+            let widget = &self.widgets[index];
+            let text_x = rect.x + PADDING;
+
+            match &widget.contents {
+                Contents::Label(text) => primitives.push(Primitive::Text(text.clone(), text_x, rect.y)),
+                Contents::Button(text, _) => {
+                    if hovered == Some(rect) {
+                        primitives.push(Primitive::Rect(rect, HOVER_COLOUR));
+                    }
+
+                    primitives.push(Primitive::Text(text.clone(), text_x, rect.y));
+                },
+                Contents::ProgressBar(text, fraction, colour) => {
+                    let bar = Rect::new(rect.x, rect.y + 1.0, rect.width, rect.height - 2.0);
+                    primitives.push(Primitive::Rect(bar, BAR_BACKGROUND_COLOUR));
+                    primitives.push(Primitive::Rect(Rect::new(bar.x, bar.y, bar.width * fraction, bar.height), *colour));
+                    primitives.push(Primitive::Text(text.clone(), text_x, rect.y));
+                },
+                Contents::List(list, _) => {
+                    if hovered == Some(rect) || list.index() == row {
+                        primitives.push(Primitive::Rect(rect, HOVER_COLOUR));
+                    }
+
+                    primitives.push(Primitive::Text(list[row].clone(), text_x, rect.y));
+                }
+            }
+        }
+    }
+
+    // Drawn after every panel, so that it's on top
+    pub fn draw_tooltip(&self, gui: &Gui, primitives: &mut Vec<Primitive>) {
+        let tooltip = self.hovered(gui).and_then(|(_, index, _)| self.widgets[index].tooltip.as_ref());
+
+        if let Some(tooltip) = tooltip {
+            let (x, y) = gui.mouse();
+            let x = x + TOOLTIP_OFFSET;
+            let y = y + TOOLTIP_OFFSET;
+            // There's no text measuring here, so this is a guess
+            let width = tooltip.chars().count() as f32 * 8.0 + PADDING * 2.0;
+
+            primitives.push(Primitive::Rect(Rect::new(x, y, width, ROW_HEIGHT), PANEL_COLOUR));
+            primitives.push(Primitive::Text(tooltip.clone(), x + PADDING, y));
+        }
+    }
+}
@@ -69,6 +69,10 @@ impl Camera {
         )
     }
 
+    pub fn frustum(&self, aspect_ratio: f32) -> Frustum {
+        Frustum::new(perspective_matrix(aspect_ratio) * self.view_matrix())
+    }
+
     pub fn step(&mut self) {
         self.distance = move_towards(self.distance, self.target_distance, Self::MAX_ZOOM_SPEED);
     }
@@ -116,6 +120,31 @@ impl Camera {
     }
 }
 
+// The volume the camera can see, used to skip rendering things that are off screen
+pub struct Frustum {
+    planes: [Vector4<f32>; 6]
+}
+
+impl Frustum {
+    // Extract the left, right, bottom, top, near and far planes from a projection * view matrix
+    pub fn new(matrix: Matrix4<f32>) -> Self {
+        let row = |i| matrix.row(i);
+        let normalise = |plane: Vector4<f32>| plane / plane.truncate().magnitude();
+
+        Self {
+            planes: [
+                normalise(row(3) + row(0)), normalise(row(3) - row(0)),
+                normalise(row(3) + row(1)), normalise(row(3) - row(1)),
+                normalise(row(3) + row(2)), normalise(row(3) - row(2))
+            ]
+        }
+    }
+
+    pub fn contains_sphere(&self, center: Vector3<f32>, radius: f32) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
+
 impl Default for Camera {
     fn default() -> Self {
         Self {
@@ -1,7 +1,7 @@
 use specs::{prelude::*, saveload::*, error::*};
 use cgmath::*;
 use crate::util::*;
-use rand::rngs::ThreadRng;
+use rand::Rng;
 use crate::ships::*;
 use serde::*;
 
@@ -15,6 +15,13 @@ pub struct Materials(pub StoredResource);
 #[derive(ConvertSaveload, Component, NewtypeProxy)]
 pub struct MineableMaterials(pub StoredResource);
 
+#[derive(ConvertSaveload, Component, NewtypeProxy)]
+pub struct Food(pub StoredResource);
+
+// Parts of a derelict ship that can be salvaged into materials
+#[derive(ConvertSaveload, Component, NewtypeProxy)]
+pub struct Salvage(pub StoredResource);
+
 #[derive(ConvertSaveload, Component, NewtypeProxy, Clone, Copy)]
 #[storage(VecStorage)]
 pub struct Size(pub f32);
@@ -60,6 +67,16 @@ pub enum Occupation {
     Government
 }
 
+impl Occupation {
+    // Marines and government officials don't help fly the ship
+    pub fn is_crew(self) -> bool {
+        match self {
+            Occupation::Worker | Occupation::Pilot | Occupation::Engineer => true,
+            Occupation::Marine | Occupation::Government => false
+        }
+    }
+}
+
 
 #[derive(ConvertSaveload, Component)]
 pub struct ObjectSpin {
@@ -70,7 +87,7 @@ pub struct ObjectSpin {
 }
 
 impl ObjectSpin {
-    pub fn random(rng: &mut ThreadRng) -> Self {
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
         use cgmath::Rotation;
         let initial = uniform_sphere_distribution(rng);
 
@@ -91,7 +108,7 @@ impl ObjectSpin {
     }
 }
 
-#[derive(Component, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Component, PartialEq, Debug, Serialize, Deserialize, Clone)]
 pub enum Side {
     Friendly,
     Neutral,
@@ -111,6 +128,113 @@ impl Side {
 #[derive(Component, ConvertSaveload)]
 pub struct DrillSpeed(pub f32);
 
+#[derive(Component, ConvertSaveload)]
+pub struct MinimumCrew(pub usize);
+
+#[derive(Component, ConvertSaveload)]
+pub struct Hangar {
+    pub capacity: usize
+}
+
+// A group of ships that moves together while keeping its shape
+#[derive(Component, ConvertSaveload)]
+pub struct FormationGroup {
+    pub target: Vector3<f32>,
+    pub facing: Option<Vector3<f32>>,
+    // The group starts once one of its members has got to it in its command queue
+    pub started: bool
+}
+
+impl FormationGroup {
+    pub fn new(target: Vector3<f32>, facing: Option<Vector3<f32>>) -> Self {
+        Self {
+            target, facing,
+            started: false
+        }
+    }
+
+    pub fn slot(&self, centre: Vector3<f32>, offset: Vector3<f32>) -> Vector3<f32> {
+        formation_point(centre, self.facing.unwrap_or_else(|| -Vector3::unit_z()), offset)
+    }
+}
+
+// Something that goes round in a flat circle, like a moon
+#[derive(Component, ConvertSaveload, Debug, Clone, PartialEq)]
+pub struct Orbit {
+    pub centre: Vector3<f32>,
+    pub radius: f32,
+    pub angle: f32,
+    // Radians per second
+    pub speed: f32
+}
+
+impl Orbit {
+    pub fn position(&self) -> Vector3<f32> {
+        self.centre + Vector3::new(self.angle.cos(), 0.0, self.angle.sin()) * self.radius
+    }
+}
+
+// Pulls ships towards its centre, harder the closer they get
+#[derive(Component, ConvertSaveload, Debug, Clone, PartialEq)]
+pub struct GravityWell {
+    pub radius: f32,
+    pub strength: f32
+}
+
+// A space station that repairs ships, and takes materials in exchange for people who want to join the fleet
+#[derive(Component, ConvertSaveload)]
+pub struct Station {
+    pub recruits: usize
+}
+
+// A cloud of gas that ships inside can't easily be seen in
+#[derive(Component, ConvertSaveload)]
+pub struct Nebula {
+    pub radius: f32
+}
+
+pub const MAX_CONTROL_GROUPS: usize = 16;
+
+// A bitmask of the numbered control groups that the ship is in
+#[derive(Component, ConvertSaveload, Default)]
+pub struct ControlGroups(pub u16);
+
+impl ControlGroups {
+    pub fn contains(&self, group: usize) -> bool {
+        self.0 & (1 << group) != 0
+    }
+
+    pub fn add(&mut self, group: usize) {
+        self.0 |= 1 << group;
+    }
+
+    pub fn remove(&mut self, group: usize) {
+        self.0 &= !(1 << group);
+    }
+}
+
+// Docked ships are children of the ship they're docked in
+#[derive(Component, Default, Serialize, Deserialize, Clone)]
+#[storage(NullStorage)]
+pub struct Docked;
+
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct CrewEfficiency(pub f32);
+
+impl CrewEfficiency {
+    pub fn new(crew: usize, minimum: usize) -> Self {
+        if minimum == 0 {
+            CrewEfficiency(1.0)
+        } else {
+            CrewEfficiency((crew as f32 / minimum as f32).min(1.0))
+        }
+    }
+
+    pub fn is_crewed(&self) -> bool {
+        self.0 > 0.0
+    }
+}
+
 #[derive(Component, Debug, Clone, PartialEq, ConvertSaveload)]
 pub struct TimeLeft(pub f32);
 
@@ -120,6 +244,35 @@ pub struct Velocity(pub Vector3<f32>);
 #[derive(Component, ConvertSaveload)]
 pub struct MaxSpeed(pub f32);
 
+// How much faster ships turn for each unit of speed their thrust gives them
+const TURN_FACTOR: f32 = 0.2;
+const MIN_TURN_RATE: f32 = 0.005;
+const MAX_TURN_RATE: f32 = 0.1;
+
+// The fastest a ship can turn, in radians per frame
+#[derive(Component, ConvertSaveload)]
+pub struct TurnRate(pub f32);
+
+impl TurnRate {
+    // Light ships with lots of thrust turn quickly and heavy ones turn slowly
+    pub fn new(thrust: f32, mass: f32) -> Self {
+        TurnRate((thrust / mass * TURN_FACTOR).max(MIN_TURN_RATE).min(MAX_TURN_RATE))
+    }
+
+    // It takes a few frames to get up to full turning speed
+    pub fn acceleration(&self) -> f32 {
+        self.0 / 10.0
+    }
+}
+
+// How fast a ship is currently turning, in radians per frame
+#[derive(Component, ConvertSaveload, Default)]
+pub struct AngularVelocity(pub f32);
+
+// How far a ship is rolled over into a turn, in radians
+#[derive(Component, ConvertSaveload, Default)]
+pub struct Bank(pub f32);
+
 #[derive(Component)]
 pub struct SeekPosition {
     point: Vector3<f32>,
@@ -172,6 +325,16 @@ pub struct AvoidanceForce(pub Vector3<f32>);
 #[derive(Component)]
 pub struct FrictionForce(pub Vector3<f32>);
 
+#[derive(Component)]
+pub struct FlockingForce(pub Vector3<f32>);
+
+// The waypoints around obstacles on the way to a move order's destination
+#[derive(Component)]
+pub struct Path {
+    pub destination: Vector3<f32>,
+    pub waypoints: Vec<Vector3<f32>>
+}
+
 
 #[derive(Component, NewtypeProxy)]
 pub struct Commands(pub Vec<Command>);
@@ -184,6 +347,48 @@ impl Commands {
 
         self.push(command);
     }
+
+    // Insert a command into the queue, or at the end if the index is past it
+    pub fn insert_command(&mut self, index: usize, command: Command) {
+        let index = index.min(self.len());
+        self.insert(index, command);
+    }
+
+    pub fn remove_command(&mut self, index: usize) -> Option<Command> {
+        if index < self.len() {
+            Some(self.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn move_command(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.len() || to >= self.len() {
+            return false;
+        }
+
+        let command = self.remove(from);
+        self.insert(to, command);
+        true
+    }
+
+    // Remove the current command, adding it back in before the `Repeat` if there is one
+    pub fn finish_current(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+
+        let mut command = self.remove(0);
+
+        if command.is_repeat() {
+            return;
+        }
+
+        if let Some(index) = self.iter().position(Command::is_repeat) {
+            command.reset();
+            self.insert(index, command);
+        }
+    }
 }
 
 impl<M: Serialize + Marker> ConvertSaveload<M> for Commands {
@@ -210,9 +415,6 @@ pub struct CanAttack {
     pub range: f32
 }
 
-#[derive(Component, Serialize, Deserialize, Default)]
-pub struct SpawnSmoke(pub u8);
-
 #[derive(Component, ConvertSaveload)]
 pub struct AttackTarget {
     pub entity: Entity,
@@ -222,6 +424,19 @@ pub struct AttackTarget {
 #[derive(Component, ConvertSaveload)]
 pub struct Health(pub f32);
 
+// A marine fighting aboard an enemy ship
+#[derive(Component, ConvertSaveload)]
+pub struct Boarder {
+    pub side: Side,
+    pub origin: Entity
+}
+
+// Put on a ship while boarders are fighting aboard it
+#[derive(Component, ConvertSaveload, Default)]
+pub struct BoardingAction {
+    pub time: f32
+}
+
 #[derive(Component, Default, Serialize, Deserialize, Clone)]
 #[storage(NullStorage)]
 pub struct NoCollide;
@@ -229,6 +444,9 @@ pub struct NoCollide;
 #[derive(Component, ConvertSaveload)]
 pub struct ExplosionSize(pub f32);
 
-#[derive(Component, Default, Serialize, Deserialize, Clone)]
-#[storage(NullStorage)]
-pub struct Explosion;
+// Plays an animation by changing the entity's image
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Animated {
+    pub animation: crate::context::Animation,
+    pub time: f32
+}
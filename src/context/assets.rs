@@ -0,0 +1,234 @@
+// The textures and models that the game uses, loaded from the resources directory (natively)
+// or from the copies that are embedded into the game
+
+use obj::ObjData;
+use crate::context::Vertex;
+use super::resources::{Image, Model};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use failure::Fail;
+
+include!(concat!(env!("OUT_DIR"), "/embedded_models.rs"));
+
+pub const ASSET_DIR: &str = "resources";
+
+const MODEL_MANIFEST: &str = include_str!("../../resources/models/models.ron");
+const PACKED_TEXTURES: &[u8] = include_bytes!("../../resources/output/packed.png");
+
+// Where an image is in the packed texture, as fractions of its size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub offset: [f32; 2],
+    pub dimensions: [f32; 2]
+}
+
+impl Frame {
+    pub fn translate(self, uv: [f32; 2]) -> [f32; 2] {
+        [
+            self.offset[0] + uv[0] * self.dimensions[0],
+            self.offset[1] + uv[1] * self.dimensions[1]
+        ]
+    }
+}
+
+#[derive(Clone)]
+pub struct TextureAtlas {
+    frames: HashMap<String, Frame>
+}
+
+impl TextureAtlas {
+    pub fn embedded() -> Self {
+        Self {
+            frames: Image::all().iter()
+                .map(|image| (image.name().to_string(), Frame { offset: image.offset(), dimensions: image.dimensions() }))
+                .collect()
+        }
+    }
+
+    #[cfg(feature = "native")]
+    fn new(frames: Vec<(String, [f32; 2], [f32; 2])>) -> Result<Self, AssetError> {
+        let frames: HashMap<_, _> = frames.into_iter()
+            .map(|(name, offset, dimensions)| (name, Frame { offset, dimensions }))
+            .collect();
+
+        if let Some(missing) = Image::all().iter().find(|image| !frames.contains_key(image.name())) {
+            return Err(AssetError::MissingTexture(*missing));
+        }
+
+        Ok(Self {
+            frames
+        })
+    }
+
+    // The atlas is checked to have every image when it's created
+    pub fn image(&self, image: Image) -> Frame {
+        self.frames[image.name()]
+    }
+
+    // Look up a texture by the name it was packed with, which might not be one the game was built with
+    pub fn get(&self, name: &str) -> Option<Frame> {
+        self.frames.get(name).cloned()
+    }
+}
+
+impl Default for TextureAtlas {
+    fn default() -> Self {
+        Self::embedded()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ModelDefinition {
+    pub name: String,
+    // The file name of the model in the models directory
+    pub obj: String,
+    // The names of the textures
+    pub diffuse: String,
+    #[serde(default)]
+    pub specular: Option<String>
+}
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum AssetError {
+    #[fail(display = "'{}' is defined more than once", _0)]
+    Duplicate(String),
+    #[fail(display = "Model '{}' uses '{}', which isn't in the models directory", _0, _1)]
+    MissingFile(String, String),
+    #[fail(display = "The game needs a model called '{}'", _0)]
+    Missing(Model),
+    #[fail(display = "The game needs a texture called '{:?}'", _0)]
+    MissingTexture(Image),
+    #[fail(display = "Model '{}' uses the texture '{}', which isn't in the resources directories", _0, _1)]
+    UnknownTexture(String, String),
+    #[fail(display = "Couldn't load '{}': {}", _0, _1)]
+    InvalidFile(String, String)
+}
+
+// Parse the model manifest and check that every model it defines has a file
+pub fn parse_manifest(text: &str, files: &HashMap<String, Vec<u8>>) -> Result<Vec<ModelDefinition>, failure::Error> {
+    let definitions: Vec<ModelDefinition> = ron::de::from_str(text)?;
+    let mut names = HashSet::new();
+
+    for definition in &definitions {
+        if !names.insert(Model::new(&definition.name)) {
+            return Err(AssetError::Duplicate(definition.name.clone()).into());
+        }
+
+        if !files.contains_key(&definition.obj) {
+            return Err(AssetError::MissingFile(definition.name.clone(), definition.obj.clone()).into());
+        }
+    }
+
+    let required = [Model::asteroid(), Model::missile(), Model::planet(), Model::station()];
+
+    if let Some(missing) = required.iter().find(|model| !names.contains(*model)) {
+        return Err(AssetError::Missing(missing.clone()).into());
+    }
+
+    Ok(definitions)
+}
+
+// Load a model from the manifest, with its textures from the atlas
+pub fn load_model(definition: &ModelDefinition, atlas: &TextureAtlas, files: &HashMap<String, Vec<u8>>) -> Result<Vec<Vertex>, AssetError> {
+    let texture = |name: &String| atlas.get(name).ok_or_else(|| AssetError::UnknownTexture(definition.name.clone(), name.clone()));
+
+    let diffuse = texture(&definition.diffuse)?;
+    let specular = definition.specular.as_ref().map(texture).transpose()?;
+
+    load_wavefront(&files[&definition.obj], diffuse, specular)
+        .map_err(|error| AssetError::InvalidFile(definition.obj.clone(), error))
+}
+
+// Returns a vertex buffer that should be rendered as `TrianglesList`.
+pub fn load_wavefront(data: &[u8], diffuse: Frame, specular: Option<Frame>) -> Result<Vec<Vertex>, String> {
+    let data = ObjData::load_buf(&mut io::BufReader::new(data))
+        .map_err(|error| format!("{:?}", error))?;
+
+    let ObjData {texture, normal, position, objects, ..} = data;
+
+    let mut vertices = Vec::new();
+
+    for polygon in objects.into_iter().flat_map(|object| object.groups).flat_map(|group| group.polys) {
+        let triangle = match polygon.into_genmesh() {
+            genmesh::Polygon::PolyTri(genmesh::Triangle { x: v1, y: v2, z: v3 }) => [v1, v2, v3],
+            genmesh::Polygon::PolyQuad(_) => return Err("Quad polygons aren't supported, use triangles instead".into())
+        };
+
+        for v in &triangle {
+            let texture = v.1.map(|index| texture[index]).unwrap_or([0.0, 0.0]);
+            let texture = [texture[0], 1.0 - texture[1]];
+
+            vertices.push(Vertex {
+                position: position[v.0],
+                normal: v.2.map(|index| normal[index]).unwrap_or([0.0, 0.0, 0.0]),
+                diff_texture: diffuse.translate(texture),
+                spec_texture: specular.map(|frame| frame.translate(texture)).unwrap_or([-1.0; 2]),
+            });
+        }
+    }
+
+    Ok(vertices)
+}
+
+pub struct Assets {
+    pub textures: image::RgbaImage,
+    pub atlas: TextureAtlas,
+    // The vertices of each model, with texture coordinates in the packed texture
+    pub models: HashMap<Model, Vec<Vertex>>
+}
+
+impl Assets {
+    fn new(textures: image::RgbaImage, atlas: TextureAtlas, manifest: &str, files: &HashMap<String, Vec<u8>>) -> Result<Self, failure::Error> {
+        let mut models = HashMap::new();
+
+        for definition in parse_manifest(manifest, files)? {
+            models.insert(Model::new(&definition.name), load_model(&definition, &atlas, files)?);
+        }
+
+        Ok(Self {
+            textures, atlas, models
+        })
+    }
+
+    pub fn embedded() -> Self {
+        let textures = image::load_from_memory_with_format(PACKED_TEXTURES, image::ImageFormat::Png)
+            .expect("The embedded textures are packed by the build script")
+            .into_rgba();
+
+        let files = EMBEDDED_MODELS.iter()
+            .map(|(name, bytes)| (name.to_string(), bytes.to_vec()))
+            .collect();
+
+        // This is checked by a test, so it won't fail
+        Self::new(textures, TextureAtlas::embedded(), MODEL_MANIFEST, &files).unwrap()
+    }
+
+    // Load and pack the assets in a directory laid out like the resources directory
+    #[cfg(feature = "native")]
+    pub fn load<P: AsRef<std::path::Path>>(dir: P) -> Result<Self, failure::Error> {
+        let dir = dir.as_ref();
+        let models_dir = dir.join("models");
+
+        let packed = super::packing::pack_textures(&[dir, &models_dir]).map_err(failure::err_msg)?;
+        let atlas = TextureAtlas::new(packed.frames)?;
+
+        let read_error = |path: &std::path::Path, error: io::Error| AssetError::InvalidFile(path.display().to_string(), error.to_string());
+
+        let mut files = HashMap::new();
+
+        for entry in std::fs::read_dir(&models_dir).map_err(|error| read_error(&models_dir, error))? {
+            let path = entry?.path();
+
+            if path.extension() == Some(std::ffi::OsStr::new("obj")) {
+                let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+                let bytes = std::fs::read(&path).map_err(|error| read_error(&path, error))?;
+                files.insert(name, bytes);
+            }
+        }
+
+        let manifest_path = models_dir.join("models.ron");
+        let manifest = std::fs::read_to_string(&manifest_path).map_err(|error| read_error(&manifest_path, error))?;
+
+        Self::new(packed.image.into_rgba(), atlas, &manifest, &files)
+    }
+}
@@ -0,0 +1,86 @@
+use super::{Uniforms, create_bind_group};
+use zerocopy::AsBytes;
+
+// A GPU buffer that's kept between frames and written to through the queue. It's only
+// reallocated when the data doesn't fit, and then to the next power of two.
+pub struct GrowableBuffer {
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+    usage: wgpu::BufferUsage
+}
+
+impl GrowableBuffer {
+    pub fn new(usage: wgpu::BufferUsage) -> Self {
+        Self {
+            buffer: None,
+            capacity: 0,
+            usage: usage | wgpu::BufferUsage::COPY_DST
+        }
+    }
+
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        // Buffer writes have to be a multiple of 4 bytes long
+        let padded_len = (bytes.len() + 3) / 4 * 4;
+
+        if padded_len > self.capacity {
+            self.capacity = padded_len.next_power_of_two();
+
+            let mut padded = bytes.to_vec();
+            padded.resize(self.capacity, 0);
+            self.buffer = Some(device.create_buffer_with_data(&padded, self.usage));
+        } else if let Some(buffer) = &self.buffer {
+            if padded_len == bytes.len() {
+                queue.write_buffer(buffer, 0, bytes);
+            } else {
+                let mut padded = bytes.to_vec();
+                padded.resize(padded_len, 0);
+                queue.write_buffer(buffer, 0, &padded);
+            }
+        }
+    }
+
+    pub fn buffer(&self) -> Option<&wgpu::Buffer> {
+        self.buffer.as_ref()
+    }
+}
+
+// A uniform buffer that's rewritten every frame, along with the bind group that uses it
+pub struct UniformBinding {
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup
+}
+
+impl UniformBinding {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, texture: &wgpu::TextureView, sampler: &wgpu::Sampler) -> Self {
+        let buffer = device.create_buffer_with_data(Uniforms::default().as_bytes(), wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST);
+        let bind_group = create_bind_group(device, layout, &buffer, texture, sampler);
+
+        Self {
+            buffer, bind_group
+        }
+    }
+}
+
+// The bindings for each way that things are rendered
+pub struct UniformBindings {
+    pub normal: UniformBinding,
+    pub shadeless: UniformBinding,
+    pub nebula: UniformBinding,
+    pub stars: UniformBinding
+}
+
+impl UniformBindings {
+    // These have to be recreated when the texture is reloaded
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, texture: &wgpu::TextureView, sampler: &wgpu::Sampler) -> Self {
+        Self {
+            normal: UniformBinding::new(device, layout, texture, sampler),
+            shadeless: UniformBinding::new(device, layout, texture, sampler),
+            nebula: UniformBinding::new(device, layout, texture, sampler),
+            stars: UniformBinding::new(device, layout, texture, sampler)
+        }
+    }
+}
@@ -62,6 +62,9 @@ impl StrokeVertexConstructor<Vertex2d> for Constructor {
 pub struct LineRenderer {
     pub pipeline: wgpu::RenderPipeline,
     pub bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    vertices: GrowableBuffer,
+    indices: GrowableBuffer,
 }
 
 impl LineRenderer {
@@ -150,25 +153,58 @@ impl LineRenderer {
             alpha_to_coverage_enabled: false,
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            bindings: &[
-                wgpu::Binding {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&resources.image)
-                },
-                wgpu::Binding {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(sampler)
-                }
-            ],
-            label: None
-        });
+        let bind_group = create_bind_group(device, &bind_group_layout, &resources.image, sampler);
 
         Self {
-            pipeline, bind_group,
+            pipeline, bind_group, bind_group_layout,
+            vertices: GrowableBuffer::new(wgpu::BufferUsage::VERTEX),
+            indices: GrowableBuffer::new(wgpu::BufferUsage::INDEX),
+        }
+    }
+
+    // Returns whether there's anything to draw
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, buffers: &LineBuffers) -> bool {
+        if buffers.vertex_buffers.vertices.is_empty() {
+            return false;
+        }
+
+        self.vertices.upload(device, queue, buffers.vertex_buffers.vertices.as_bytes());
+        self.indices.upload(device, queue, buffers.vertex_buffers.indices.as_bytes());
+
+        true
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, num_indices: u32) {
+        if let (Some(vertices), Some(indices)) = (self.vertices.buffer(), self.indices.buffer()) {
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.set_index_buffer(indices.slice(0 .. 0));
+            pass.set_vertex_buffer(0, vertices.slice(0 .. 0));
+            pass.draw_indexed(0 .. num_indices, 0, 0 .. 1);
         }
     }
+
+    // The bind group has to be recreated when the texture is reloaded
+    pub fn set_texture(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, texture: &wgpu::TextureView) {
+        self.bind_group = create_bind_group(device, &self.bind_group_layout, texture, sampler);
+    }
+}
+
+fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, texture: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(texture)
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler)
+            }
+        ],
+        label: None
+    })
 }
 
 #[derive(Default)]
@@ -250,14 +286,22 @@ impl LineBuffers {
         ).unwrap();
     }
     
-    pub fn push_image(&mut self, image: Image, x: f32, y: f32, width: f32, height: f32, overlay: [f32; 4], window_size: Vector2<f32>) {
+    pub fn push_filled_rect(&mut self, (x, y): (f32, f32), (width, height): (f32, f32), colour: [f32; 3], window_size: Vector2<f32>) {
+        fill_rectangle(
+            &rect(x, y, width, height),
+            &FillOptions::default(),
+            &mut BuffersBuilder::new(&mut self.vertex_buffers, Constructor::new([colour[0], colour[1], colour[2], 1.0], window_size, None))
+        ).unwrap();
+    }
+
+    pub fn push_image(&mut self, frame: Frame, x: f32, y: f32, width: f32, height: f32, overlay: [f32; 4], window_size: Vector2<f32>) {
         let len = self.vertex_buffers.vertices.len() as u16;
 
         self.vertex_buffers.vertices.extend_from_slice(&[
-            Vertex2d::new(Vector2::new(x - width / 2.0, y - height / 2.0), window_size, image.translate([0.0, 1.0]), overlay, 0.0),
-            Vertex2d::new(Vector2::new(x + width / 2.0, y - height / 2.0), window_size, image.translate([1.0, 1.0]), overlay, 0.0),
-            Vertex2d::new(Vector2::new(x - width / 2.0, y + height / 2.0), window_size, image.translate([0.0, 0.0]), overlay, 0.0),
-            Vertex2d::new(Vector2::new(x + width / 2.0, y + height / 2.0), window_size, image.translate([1.0, 0.0]), overlay, 0.0)
+            Vertex2d::new(Vector2::new(x - width / 2.0, y - height / 2.0), window_size, frame.translate([0.0, 1.0]), overlay, 0.0),
+            Vertex2d::new(Vector2::new(x + width / 2.0, y - height / 2.0), window_size, frame.translate([1.0, 1.0]), overlay, 0.0),
+            Vertex2d::new(Vector2::new(x - width / 2.0, y + height / 2.0), window_size, frame.translate([0.0, 0.0]), overlay, 0.0),
+            Vertex2d::new(Vector2::new(x + width / 2.0, y + height / 2.0), window_size, frame.translate([1.0, 0.0]), overlay, 0.0)
         ]);
 
         self.vertex_buffers.indices.extend_from_slice(&[
@@ -266,17 +310,6 @@ impl LineBuffers {
         ]);
     }
 
-    pub fn upload(&mut self, device: &wgpu::Device) -> Option<(wgpu::Buffer, wgpu::Buffer)> {
-        if self.vertex_buffers.vertices.is_empty() {
-            return None;
-        }
-
-        let vertices = device.create_buffer_with_data(&self.vertex_buffers.vertices.as_bytes(), wgpu::BufferUsage::VERTEX);
-        let indices = device.create_buffer_with_data(&self.vertex_buffers.indices.as_bytes(), wgpu::BufferUsage::INDEX);
-
-        Some((vertices, indices))
-    }
-
     pub fn clear(&mut self) {
         self.vertex_buffers.vertices.clear();
         self.vertex_buffers.indices.clear();
@@ -1,9 +1,15 @@
 mod lines;
 mod resources;
+mod assets;
+mod buffers;
+#[cfg(feature = "native")]
+mod packing;
 
 pub use self::lines::*;
+use self::buffers::*;
 
-pub use self::resources::{Image, Model, MeshArray, Resources, BILLBOARD_VERTICES};
+pub use self::resources::{Image, Animation, Model, MeshMap, Resources, BILLBOARD_VERTICES};
+pub use self::assets::{Assets, AssetError, Frame, TextureAtlas, ModelDefinition, parse_manifest, load_model, ASSET_DIR};
 pub const WHITE: [f32; 3] = [1.0; 3];
 
 use {
@@ -17,7 +23,6 @@ use {
     std::f32::consts::PI,
     zerocopy::AsBytes,
     wgpu::vertex_attr_array,
-    pedot::Gui,
 };
 
 // ** Line Rendering Woes **
@@ -74,15 +79,17 @@ pub struct Context {
 
     pub resources: Resources,
 
-    lines: LineRenderer,
+    uniforms: UniformBindings,
+    model_instances: std::collections::HashMap<Model, GrowableBuffer>,
+    billboard_instances: GrowableBuffer,
 
-    glyph_brush: wgpu_glyph::GlyphBrush<'static, ()>,
+    lines: LineRenderer,
 
-    pub gui: Gui
+    glyph_brush: wgpu_glyph::GlyphBrush<'static, ()>
 }
 
 impl Context {
-    pub async fn new(event_loop: &event_loop::EventLoop<()>) -> (Self, MeshArray) {
+    pub async fn new(event_loop: &event_loop::EventLoop<()>, assets: &Assets) -> (Self, MeshMap) {
         let window = winit::window::Window::new(event_loop).unwrap();
 
         #[cfg(feature = "wasm")]
@@ -131,7 +138,7 @@ impl Context {
         
         let mut init_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
         
-        let (resources, meshes) = Resources::new(&mut init_encoder, &device).unwrap();
+        let (resources, meshes) = Resources::new(&mut init_encoder, &device, assets);
         
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::Repeat,
@@ -178,6 +185,8 @@ impl Context {
             bind_group_layouts: &[&bind_group_layout],
         });
 
+        let uniforms = UniformBindings::new(&device, &bind_group_layout, &resources.image, &sampler);
+
         let triangle_pipeline = create_pipeline(&device, &pipeline_layout, &vs_module, &fs_module, wgpu::PrimitiveTopology::TriangleList, true);
         let billboard_pipeline = create_pipeline(&device, &pipeline_layout, &vs_module, &fs_module, wgpu::PrimitiveTopology::TriangleList, false);
 
@@ -192,8 +201,6 @@ impl Context {
 
         let lines = LineRenderer::new(&device, &sampler, &resources);
 
-        let gui = Gui::new(window_size.width as f32, window_size.height as f32);
-
         let font: &[u8] = include_bytes!("pixel_operator/PixelOperator.ttf");
 
         let glyph_brush = wgpu_glyph::GlyphBrushBuilder::using_font_bytes(font)
@@ -205,14 +212,24 @@ impl Context {
         (
             Self {
                 swap_chain, triangle_pipeline, bind_group_layout, identity_instance, billboard_pipeline,
-                queue, sampler, resources, device, window, surface, depth_texture, billboard_vertices, lines, glyph_brush, gui,
+                queue, sampler, resources, device, window, surface, depth_texture, billboard_vertices, lines, glyph_brush,
+                uniforms,
+                model_instances: std::collections::HashMap::new(),
+                billboard_instances: GrowableBuffer::new(wgpu::BufferUsage::VERTEX),
             },
             meshes
         )
     }
 
-    pub fn copy_event(&mut self, event: &event::WindowEvent) {
-        self.gui.update(event);
+    // Replace the models and textures, returning the new collision meshes
+    pub fn reload_assets(&mut self, assets: &Assets) -> MeshMap {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let (resources, meshes) = Resources::new(&mut encoder, &self.device, assets);
+        self.queue.submit(Some(encoder.finish()));
+        self.lines.set_texture(&self.device, &self.sampler, &resources.image);
+        self.uniforms = UniformBindings::new(&self.device, &self.bind_group_layout, &resources.image, &self.sampler);
+        self.resources = resources;
+        meshes
     }
 
     pub fn dpi(&self) -> f32 {
@@ -233,7 +250,7 @@ impl Context {
         perspective_matrix(self.aspect_ratio())
     }
 
-    fn uniforms(&self, view_matrix: Matrix4<f32>, system: &StarSystem, mode: Mode) -> wgpu::Buffer {
+    fn write_uniforms(&self, binding: &UniformBinding, view_matrix: Matrix4<f32>, system: &StarSystem, mode: Mode) {
         let uniforms = Uniforms {
             view: view_matrix.into(),
             perspective: self.perspective_matrix().into(),
@@ -243,12 +260,7 @@ impl Context {
             dpi: self.dpi()
         };
         
-        self.device.create_buffer_with_data(uniforms.as_bytes(), wgpu::BufferUsage::UNIFORM)
-    }
-
-    fn bind_group_from_uniforms(&self, view_matrix: Matrix4<f32>, system: &StarSystem, mode: Mode) -> wgpu::BindGroup {
-        let uniforms = self.uniforms(view_matrix, system, mode);
-        create_bind_group(&self.device, &self.bind_group_layout, &uniforms, &self.resources.image, &self.sampler)
+        self.queue.write_buffer(&binding.buffer, 0, uniforms.as_bytes());
     }
 
     pub fn render(
@@ -256,19 +268,26 @@ impl Context {
         model_buffers: &mut ModelBuffers, lines: &mut LineBuffers, billboards: &mut BillboardBuffer, text: &mut TextBuffer,
         clear_colour: wgpu::Color, camera: &Camera, system: &mut StarSystem
     ) {        
-        let normal_bind_group = self.bind_group_from_uniforms(camera.view_matrix(), system, Mode::Normal);
-        let shadeless_bind_group = self.bind_group_from_uniforms(camera.view_matrix(), system, Mode::Shadeless);
-        let nebula_bind_group = self.bind_group_from_uniforms(camera.view_matrix_only_direction(), system, Mode::VertexColoured);
-        let star_bind_group = self.bind_group_from_uniforms(camera.view_matrix_only_direction(), system, Mode::White);
+        self.write_uniforms(&self.uniforms.normal, camera.view_matrix(), system, Mode::Normal);
+        self.write_uniforms(&self.uniforms.shadeless, camera.view_matrix(), system, Mode::Shadeless);
+        self.write_uniforms(&self.uniforms.nebula, camera.view_matrix_only_direction(), system, Mode::VertexColoured);
+        self.write_uniforms(&self.uniforms.stars, camera.view_matrix_only_direction(), system, Mode::White);
+
+        for (model, instances) in &model_buffers.inner {
+            // Only clone the model the first time it's seen
+            if !self.model_instances.contains_key(model) {
+                self.model_instances.insert(model.clone(), GrowableBuffer::new(wgpu::BufferUsage::VERTEX));
+            }
+
+            self.model_instances.get_mut(model).unwrap().upload(&self.device, &self.queue, instances.as_bytes());
+        }
 
-        let gpu_model_buffers = model_buffers.upload(&self.device);
-        let nebula_buffer = self.device.create_buffer_with_data(&system.background.as_bytes(), wgpu::BufferUsage::VERTEX);
         let background_len = system.background.len() as u32;
         let stars_len = system.stars.len() as u32;
-        let star_buffer = system.star_buffer(&self.device);
+        let (nebula_buffer, star_buffer) = system.buffers(&self.device);
 
-        let gpu_lines = lines.upload(&self.device);
-        let gpu_billboards = billboards.upload(&self.device);
+        let draw_lines = self.lines.upload(&self.device, &self.queue, lines);
+        self.billboard_instances.upload(&self.device, &self.queue, billboards.inner.as_bytes());
 
         let output = self.swap_chain.get_next_texture().unwrap();
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None});
@@ -293,43 +312,39 @@ impl Context {
             });
 
             pass.set_pipeline(&self.triangle_pipeline);
-            pass.set_bind_group(0, &normal_bind_group, &[]);
+            pass.set_bind_group(0, &self.uniforms.normal.bind_group, &[]);
 
-            for i in 0 .. 6 {
-                if let Some(instances) = &gpu_model_buffers[i] {
-                    let model = &self.resources.models[i];
+            for (model, instances) in model_buffers.inner.iter().filter(|(_, instances)| !instances.is_empty()) {
+                let buffer = self.model_instances.get(model).and_then(GrowableBuffer::buffer);
 
+                if let (Some(model), Some(buffer)) = (self.resources.models.get(model), buffer) {
                     let vertices_slice = model.vertices.slice(0 .. 0);
-                    let instances_slice = instances.slice(0 .. 0);
+                    let instances_slice = buffer.slice(0 .. 0);
                     pass.set_vertex_buffer(0, vertices_slice);
                     pass.set_vertex_buffer(1, instances_slice);
-                    pass.draw(0 .. model.vertices_len as u32, 0 .. model_buffers.inner[i].len() as u32);
+                    pass.draw(0 .. model.vertices_len as u32, 0 .. instances.len() as u32);
                 }
             }
 
             // Background
-            pass.set_bind_group(0, &nebula_bind_group, &[]);
+            pass.set_bind_group(0, &self.uniforms.nebula.bind_group, &[]);
             pass.set_vertex_buffer(0, nebula_buffer.slice(0 .. 0));
             pass.set_vertex_buffer(1, self.identity_instance.slice(0 .. 0));
             pass.draw(0 .. background_len, 0 .. 1); 
 
             // Stars
-            pass.set_bind_group(0, &star_bind_group, &[]);
+            pass.set_bind_group(0, &self.uniforms.stars.bind_group, &[]);
             pass.set_vertex_buffer(0, self.billboard_vertices.slice(0 .. 0));
             pass.set_vertex_buffer(1, star_buffer.slice(0 .. 0));
             pass.draw(0 .. BILLBOARD_VERTICES.len() as u32, 0 .. stars_len);
 
-            if let Some((vertices, indices)) = &gpu_lines {
-                pass.set_pipeline(&self.lines.pipeline);
-                pass.set_bind_group(0, &self.lines.bind_group, &[]);
-                pass.set_index_buffer(indices.slice(0 .. 0));
-                pass.set_vertex_buffer(0, vertices.slice(0 .. 0));
-                pass.draw_indexed(0 .. lines.num_indices(), 0, 0 .. 1);
+            if draw_lines {
+                self.lines.draw(&mut pass, lines.num_indices());
             }
 
-            if let Some(instances) = &gpu_billboards {
+            if let (Some(instances), false) = (self.billboard_instances.buffer(), billboards.inner.is_empty()) {
                 pass.set_pipeline(&self.billboard_pipeline);
-                pass.set_bind_group(0, &shadeless_bind_group, &[]);
+                pass.set_bind_group(0, &self.uniforms.shadeless.bind_group, &[]);
                 pass.set_vertex_buffer(0, self.billboard_vertices.slice(0 .. 0));
                 pass.set_vertex_buffer(1, instances.slice(0 .. 0));
                 pass.draw(0 .. BILLBOARD_VERTICES.len() as u32, 0 .. billboards.inner.len() as u32);
@@ -538,60 +553,36 @@ impl BillboardBuffer {
         self.inner.clear();
     }
 
-    pub fn push_billboard(&mut self, matrix: Matrix4<f32>, image: Image) {
+    pub fn push_billboard(&mut self, matrix: Matrix4<f32>, frame: Frame) {
         let vertex = InstanceVertex {
             instance_pos: matrix.into(),
-            uv_dimensions: image.dimensions(),
-            uv_offset: image.offset(),
+            uv_dimensions: frame.dimensions,
+            uv_offset: frame.offset,
         };
         self.inner.push(vertex);
     }
-
-    fn upload(&self, device: &wgpu::Device) -> Option<wgpu::Buffer> {
-        if self.inner.is_empty() {
-            None
-        } else {
-            Some(device.create_buffer_with_data(self.inner.as_bytes(), wgpu::BufferUsage::VERTEX))
-        }
-    }
 }
 
 #[derive(Default)]
 pub struct ModelBuffers {
-    inner: [Vec<InstanceVertex>; 6]
+    inner: std::collections::HashMap<Model, Vec<InstanceVertex>>
 }
 
 impl ModelBuffers {
     fn clear(&mut self) {
-        for buffer in &mut self.inner {
+        for buffer in self.inner.values_mut() {
             buffer.clear();
         }
     }
 
-    pub fn push_model(&mut self, model: Model, instance: InstanceVertex) {
-        self.inner[model as usize].push(instance);
-    }
-
-    fn upload(&self, device: &wgpu::Device) -> [Option<wgpu::Buffer>; 6] {
-        [
-            self.buffer(0, device),
-            self.buffer(1, device),
-            self.buffer(2, device),
-            self.buffer(3, device),
-            self.buffer(4, device),
-            self.buffer(5, device),
-        ]
-    }
-
-    fn buffer(&self, index: usize, device: &wgpu::Device) -> Option<wgpu::Buffer> {
-        let bytes = self.inner[index].as_bytes();
-
-        if bytes.is_empty() {
-            None
-        } else {
-            let buffer = device.create_buffer_with_data(bytes, wgpu::BufferUsage::VERTEX);
-            Some(buffer)
-        }        
+    pub fn push_model(&mut self, model: &Model, instance: InstanceVertex) {
+        // Only clone the model the first time it's seen
+        match self.inner.get_mut(model) {
+            Some(buffer) => buffer.push(instance),
+            None => {
+                self.inner.insert(model.clone(), vec![instance]);
+            }
+        }
     }
 }
 
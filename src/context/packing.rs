@@ -0,0 +1,85 @@
+// Texture packing, shared between the build script (which packs the textures that are embedded
+// into the game) and the runtime asset loader. Errors are strings because the build script
+// doesn't use failure.
+
+use texture_packer::{*, importer::*, exporter::*, texture::*};
+use image::DynamicImage;
+use std::path::Path;
+
+pub struct PackedTextures {
+    pub image: DynamicImage,
+    // The name of each texture with its offset and dimensions, as fractions of the image size
+    pub frames: Vec<(String, [f32; 2], [f32; 2])>
+}
+
+// Textures are named after their file stem, in PascalCase
+fn texture_name(path: &Path) -> Result<String, String> {
+    let stem = path.file_stem().and_then(|stem| stem.to_str())
+        .ok_or_else(|| format!("'{}' doesn't have a valid file name", path.display()))?;
+
+    case_style::CaseStyle::guess(stem)
+        .map(|style| style.to_pascalcase())
+        .map_err(|error| format!("Couldn't work out a name for '{}': {:?}", path.display(), error))
+}
+
+fn load_dir(path: &Path, packer: &mut TexturePacker<DynamicImage>) -> Result<(), String> {
+    let entries = std::fs::read_dir(path)
+        .map_err(|error| format!("Couldn't read '{}': {}", path.display(), error))?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension() == Some(std::ffi::OsStr::new("png")))
+        .collect();
+
+    // Pack in the same order every time
+    paths.sort();
+
+    for path in paths {
+        let image = ImageImporter::import_from_file(&path)
+            .map_err(|error| format!("Couldn't load '{}': {}", path.display(), error))?;
+
+        packer.pack_own(texture_name(&path)?, image)
+            .map_err(|error| format!("Couldn't pack '{}': {:?}", path.display(), error))?;
+    }
+
+    Ok(())
+}
+
+pub fn pack_textures(dirs: &[&Path]) -> Result<PackedTextures, String> {
+    let mut packer = TexturePacker::new_skyline(TexturePackerConfig {
+        trim: false,
+        texture_padding: 1,
+        .. Default::default()
+    });
+
+    for dir in dirs {
+        load_dir(dir, &mut packer)?;
+    }
+
+    let width = packer.width();
+
+    if width % 64 != 0 {
+        let needed = 64 - (width % 64);
+        packer.set_row_padding(needed);
+    }
+
+    let width = packer.width() as f32;
+    let height = packer.height() as f32;
+
+    let mut frames: Vec<_> = packer.get_frames().iter()
+        .map(|(name, frame)| (
+            name.clone(),
+            [frame.frame.x as f32 / width, frame.frame.y as f32 / height],
+            [frame.frame.w as f32 / width, frame.frame.h as f32 / height]
+        ))
+        .collect();
+
+    frames.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let image = ImageExporter::export(&packer)
+        .map_err(|error| format!("Couldn't export the packed textures: {}", error))?;
+
+    Ok(PackedTextures {
+        image, frames
+    })
+}
@@ -1,11 +1,10 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::unreadable_literal))]
 
-use obj::*;
 use crate::context::Vertex;
-use crate::util::*;
-use std::io;
+use super::assets::Assets;
 use specs::*;
-use arrayvec::*;
+use std::collections::HashMap;
+use std::fmt;
 use ncollide3d::shape::TriMesh;
 use nalgebra::Point3;
 use zerocopy::*;
@@ -42,18 +41,9 @@ const BOTTOM_RIGHT: Vertex = Vertex {
 
 pub const BILLBOARD_VERTICES: [Vertex; 6] = [TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT, TOP_RIGHT, BOTTOM_RIGHT, BOTTOM_LEFT];
 
-macro_rules! load_resource {
-    ($filename:expr) => (
-        include_bytes!(concat!("../../resources/", $filename))
-    )
-}
-
-pub fn load_image(encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device, bytes: &[u8]) -> wgpu::TextureView {
-    let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png).unwrap()
-        .into_rgba();
-
+pub fn load_image(encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device, image: &image::RgbaImage) -> wgpu::TextureView {
     let temp_buf =
-        device.create_buffer_with_data(&*image, wgpu::BufferUsage::COPY_SRC);
+        device.create_buffer_with_data(&**image, wgpu::BufferUsage::COPY_SRC);
 
     let texture_extent = wgpu::Extent3d {
         width: image.width(),
@@ -90,67 +80,52 @@ pub fn load_image(encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device, byt
     texture.create_default_view()
 }
 
-// Returns a vertex buffer that should be rendered as `TrianglesList`.
-pub fn load_wavefront(data: &[u8], model: Model) ->  Vec<Vertex> {
-    let mut data = io::BufReader::new(data);
-    let data = ObjData::load_buf(&mut data).unwrap();
-
-    let ObjData {texture, normal, position, objects, ..} = data;
-
-    objects.into_iter()
-        .flat_map(|object| object.groups)
-        .flat_map(|group| group.polys)
-        .flat_map(|polygon| {
-            match polygon.into_genmesh() {
-                genmesh::Polygon::PolyTri(genmesh::Triangle { x: v1, y: v2, z: v3 }) => iter_owned([v1, v2, v3]),
-                genmesh::Polygon::PolyQuad(_) => unimplemented!("Quad polygons not supported, use triangles instead.")
-            }
-        })
-        .map(|v| {
-            let texture = v.1.map(|index| texture[index]).unwrap_or([0.0, 0.0]);
-            let texture = [texture[0], 1.0 - texture[1]];
-            Vertex {
-                position: position[v.0],
-                normal: v.2.map(|index| normal[index]).unwrap_or([0.0, 0.0, 0.0]),
-                diff_texture: model.diffuse().translate(texture),
-                spec_texture: model.specular().map(|image| image.translate(texture)).unwrap_or([-1.0; 2]),
-            }
-        })
-        .collect()
-}
-
 include!(concat!(env!("OUT_DIR"), "/packed_textures.rs"));
 
-#[derive(Serialize, Deserialize, Component, Copy, Clone, Hash, PartialEq, Eq)]
-pub enum Model {
-    Fighter = 0,
-    Tanker = 1,
-    Carrier = 2,
-    Asteroid = 3,
-    Miner = 4,
-    Missile = 5
+impl Animation {
+    // The frame to show after the animation has been playing for some time
+    pub fn frame(self, time: f32) -> Image {
+        let frames = self.frames();
+        let index = (time / self.frame_duration()) as usize;
+
+        if self.looping() {
+            frames[index % frames.len()]
+        } else {
+            frames[index.min(frames.len() - 1)]
+        }
+    }
 }
 
+// A model, referred to by its name in the model manifest
+#[derive(Serialize, Deserialize, Component, Clone, Hash, PartialEq, Eq, Debug)]
+#[serde(transparent)]
+pub struct Model(String);
+
 impl Model {
-    pub fn diffuse(self) -> Image {
-        match self {
-            Model::Fighter => Image::Fighter,
-            Model::Tanker => Image::Tanker,
-            Model::Carrier => Image::Carrier,
-            Model::Asteroid => Image::Asteroid,
-            Model::Miner => Image::Miner,
-            Model::Missile => Image::Missile,
-        }
+    pub fn new(name: &str) -> Self {
+        Model(name.into())
+    }
+
+    pub fn asteroid() -> Self {
+        Self::new("Asteroid")
     }
 
-    pub fn specular(self) -> Option<Image> {
-        Some(match self {
-            Model::Fighter => Image::FighterSpecular,
-            Model::Tanker => Image::TankerSpecular,
-            Model::Carrier => Image::CarrierSpecular,
-            Model::Miner => Image::MinerSpecular,
-            _ => return None,
-        })
+    pub fn missile() -> Self {
+        Self::new("Missile")
+    }
+
+    pub fn planet() -> Self {
+        Self::new("Planet")
+    }
+
+    pub fn station() -> Self {
+        Self::new("Station")
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -160,9 +135,7 @@ pub struct ObjModel {
 }
 
 impl ObjModel {
-    fn new(device: &wgpu::Device, bytes: &[u8], model: Model) -> io::Result<(Self, TriMesh<f32>)> {
-        let vertices = load_wavefront(bytes, model);
-
+    fn new(device: &wgpu::Device, vertices: &[Vertex]) -> (Self, TriMesh<f32>) {
         let points: Vec<_> = vertices.iter()
             .map(|vertex| Point3::new(vertex.position[0], vertex.position[1], vertex.position[2]))
             .collect();
@@ -171,18 +144,18 @@ impl ObjModel {
             .map(|i| Point3::new(i * 3, i * 3 + 1, i * 3 + 2))
             .collect();
 
-        Ok((
+        (
             Self {
                 vertices_len: vertices.len(),
                 vertices: device.create_buffer_with_data(vertices.as_bytes(), wgpu::BufferUsage::VERTEX),
             },
             TriMesh::new(points, faces, None)
-        ))
+        )
     }
 }
 
-pub type MeshArray = ArrayVec<[TriMesh<f32>; 6]>;
-type Models = ArrayVec<[ObjModel; 6]>;
+pub type MeshMap = HashMap<Model, TriMesh<f32>>;
+type Models = HashMap<Model, ObjModel>;
 
 pub struct Resources {
     pub models: Models,
@@ -191,31 +164,22 @@ pub struct Resources {
 }
 
 impl Resources {
-    pub fn new(encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) -> Result<(Self, MeshArray), failure::Error> {
-        let mut meshes = MeshArray::new();
+    pub fn new(encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device, assets: &Assets) -> (Self, MeshMap) {
+        let mut meshes = MeshMap::new();
         let mut models = Models::new();
 
-        add_model(&mut meshes, &mut models, device, load_resource!("models/fighter.obj"),  Model::Fighter)?;
-        add_model(&mut meshes, &mut models, device, load_resource!("models/tanker.obj"),   Model::Tanker)?;
-        add_model(&mut meshes, &mut models, device, load_resource!("models/carrier.obj"),  Model::Carrier)?;
-        add_model(&mut meshes, &mut models, device, load_resource!("models/asteroid.obj"), Model::Asteroid)?;
-        add_model(&mut meshes, &mut models, device, load_resource!("models/miner.obj"),    Model::Miner)?;
-        add_model(&mut meshes, &mut models, device, load_resource!("models/missile.obj"),  Model::Missile)?;
+        for (model, vertices) in &assets.models {
+            let (object, mesh) = ObjModel::new(device, vertices);
+            models.insert(model.clone(), object);
+            meshes.insert(model.clone(), mesh);
+        }
 
-        Ok((
+        (
             Self {
                 models,
-                image: load_image(encoder, device, load_resource!("output/packed.png")),
+                image: load_image(encoder, device, &assets.textures),
             },
             meshes
-        ))
+        )
     }
 }
-
-pub fn add_model(meshes: &mut MeshArray, models: &mut Models, device: &wgpu::Device, obj: &[u8], model: Model) -> Result<(), failure::Error> {
-    let (object, mesh) = ObjModel::new(device, obj, model)?;
-    models.push(object);
-    meshes.push(mesh);
-
-    Ok(())
-}
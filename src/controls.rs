@@ -3,7 +3,13 @@
 // dragging -> dragged -> up
 
 use std::mem::swap;
+use std::collections::HashSet;
 use specs::*;
+use crate::input::Action;
+use winit::event::MouseButton;
+
+// The most seconds apart two clicks can be for them to count as a double click
+const DOUBLE_CLICK_TIME: f32 = 0.4;
 
 #[derive(is_enum_variant, Debug, Clone)]
 enum MouseState {
@@ -62,14 +68,15 @@ pub struct Controls {
     right_state: MouseState,
     middle_state: MouseState,
 
-    pub left: bool,
-    pub right: bool,
-    pub forwards: bool,
-    pub back: bool,
-    pub shift: bool,
-    pub delete: bool,
-    pub save: bool,
-    pub load: bool
+    // The seconds since the last left click and the entity it was on
+    last_left_click: Option<(f32, Entity)>,
+    // The entity that was double clicked this frame
+    left_double_clicked: Option<Entity>,
+
+    // Actions whose keys are being held down
+    held: HashSet<Action>,
+    // Actions whose keys were pressed this frame
+    triggered: HashSet<Action>
 }
 
 impl Controls {
@@ -77,6 +84,18 @@ impl Controls {
         self.right_state.is_dragging()
     }
 
+    pub fn right_dragged(&self) -> bool {
+        self.right_state.is_dragged()
+    }
+
+    // Where the right mouse button was pressed down, while it's being dragged or was just released
+    pub fn right_drag_start(&self) -> Option<(f32, f32)> {
+        match self.right_state {
+            MouseState::Dragging(x, y) | MouseState::Dragged(x, y) => Some((x, y)),
+            _ => None
+        }
+    }
+
     pub fn mouse(&self) -> (f32, f32) {
         self.mouse
     }
@@ -85,17 +104,103 @@ impl Controls {
         self.mouse = (x, y);
     }
 
-    pub fn update(&mut self) {
-        self.save = false;
-        self.load = false;
+    // Forget the held keys and buttons, as they can be released while a menu is open without the game seeing it
+    pub fn release_all(&mut self) {
+        *self = Self {
+            mouse: self.mouse,
+            ..Self::default()
+        };
+    }
+
+    // Called at the end of each frame with the entity that was under the mouse during it
+    pub fn update(&mut self, secs: f32, entity_under_mouse: Option<Entity>) {
+        if self.left_state.is_clicked() {
+            // Don't let a third click count as another double click
+            self.last_left_click = match self.left_double_clicked {
+                Some(_) => None,
+                None => entity_under_mouse.map(|entity| (0.0, entity))
+            };
+        } else if let Some((secs_since, _)) = self.last_left_click.as_mut() {
+            *secs_since += secs;
+        }
+
+        self.triggered.clear();
+        self.left_double_clicked = None;
 
         self.left_state.update(self.mouse);
         self.middle_state.update(self.mouse);
         self.right_state.update(self.mouse);
     }
 
+    pub fn handle_action(&mut self, action: Action, pressed: bool) {
+        if pressed {
+            // Ignore key repeats
+            if self.held.insert(action) {
+                self.triggered.insert(action);
+            }
+        } else {
+            self.held.remove(&action);
+        }
+    }
+
+    // Do an action without its key being pressed, e.g. from a button
+    pub fn trigger(&mut self, action: Action) {
+        self.triggered.insert(action);
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    pub fn triggered(&self, action: Action) -> bool {
+        self.triggered.contains(&action)
+    }
+
+    // The control group whose key was pressed this frame
+    pub fn group(&self) -> Option<usize> {
+        self.triggered.iter()
+            .filter_map(|action| match action {
+                Action::Group(group) => Some(*group as usize),
+                _ => None
+            })
+            .next()
+    }
+
+    // The event choice whose key was pressed this frame
+    pub fn choice(&self) -> Option<usize> {
+        self.triggered.iter()
+            .filter_map(|action| match action {
+                Action::Choice(choice) => Some(*choice as usize),
+                _ => None
+            })
+            .next()
+    }
+
+    pub fn shift(&self) -> bool {
+        self.held(Action::Shift)
+    }
+
+    pub fn control(&self) -> bool {
+        self.held(Action::Control)
+    }
+
     pub fn handle_left(&mut self, pressed: bool) {
         self.left_state.handle(self.mouse, pressed);
+
+        if self.left_state.is_clicked() {
+            self.left_double_clicked = self.last_left_click
+                .filter(|(secs_since, _)| *secs_since <= DOUBLE_CLICK_TIME)
+                .map(|(_, entity)| entity);
+        }
+    }
+
+    pub fn handle_mouse(&mut self, button: MouseButton, pressed: bool) {
+        match button {
+            MouseButton::Left => self.handle_left(pressed),
+            MouseButton::Right => self.handle_right(pressed),
+            MouseButton::Middle => self.handle_middle(pressed),
+            _ => {}
+        }
     }
 
     pub fn handle_right(&mut self, pressed: bool) {
@@ -114,6 +219,11 @@ impl Controls {
         self.left_state.is_clicked()
     }
 
+    // The entity that the first click of a double click was on
+    pub fn left_double_clicked(&self) -> Option<Entity> {
+        self.left_double_clicked
+    }
+
     pub fn right_clicked(&self) -> bool {
         self.right_state.is_clicked()
     }
@@ -0,0 +1,146 @@
+// Random events, loaded from a config file so that they can be written without touching the code
+
+use crate::ships::ShipType;
+use crate::components::Side;
+use std::path::Path;
+use std::fs::File;
+
+pub const ENCOUNTER_CONFIG: &str = "encounters.ron";
+
+// The encounters that are built into the game, for when the config file can't be read
+const DEFAULT_ENCOUNTERS: &str = include_str!("../encounters.ron");
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    // Arriving in a new star system
+    Arrival,
+    // The fleet's food stores dropping below a fraction of their capacity
+    LowFood(f32),
+    // A number of seconds having passed since the start of the game
+    Time(f32)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Condition {
+    MinPopulation(usize),
+    MaxPopulation(usize),
+    MinMaterials(f32),
+    MinShips(usize)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum Outcome {
+    Message(String),
+    // Spread across (or taken from) the fleet's ships
+    Materials(f32),
+    Food(f32),
+    // People joining or leaving the fleet
+    Population(i32),
+    SpawnShips {
+        tag: ShipType,
+        count: usize,
+        side: Side
+    },
+    // Asteroids appearing somewhere near the fleet
+    SpawnAsteroids {
+        count: usize,
+        resources: f32
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Choice {
+    pub text: String,
+    pub outcomes: Vec<Outcome>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Encounter {
+    pub name: String,
+    pub text: String,
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    // The chance of the encounter happening each time it's triggered
+    #[serde(default = "always")]
+    pub chance: f32,
+    #[serde(default)]
+    pub repeatable: bool,
+    pub choices: Vec<Choice>
+}
+
+fn always() -> f32 {
+    1.0
+}
+
+// The parts of the game state that encounters can depend on
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FleetStatus {
+    pub population: usize,
+    pub ships: usize,
+    pub materials: f32,
+    pub food: f32,
+    pub food_capacity: f32,
+    pub time: f32,
+    pub arrived: bool
+}
+
+impl Condition {
+    fn met(self, status: &FleetStatus) -> bool {
+        match self {
+            Condition::MinPopulation(population) => status.population >= population,
+            Condition::MaxPopulation(population) => status.population <= population,
+            Condition::MinMaterials(materials) => status.materials >= materials,
+            Condition::MinShips(ships) => status.ships >= ships
+        }
+    }
+}
+
+impl Encounter {
+    pub fn triggered(&self, status: &FleetStatus) -> bool {
+        let triggered = match self.trigger {
+            Trigger::Arrival => status.arrived,
+            Trigger::LowFood(fraction) => status.food_capacity > 0.0 && status.food < status.food_capacity * fraction,
+            Trigger::Time(time) => status.time >= time
+        };
+
+        triggered && self.conditions.iter().all(|condition| condition.met(status))
+    }
+}
+
+#[derive(Default)]
+pub struct Encounters(pub Vec<Encounter>);
+
+impl Encounters {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, failure::Error> {
+        let encounters: Vec<Encounter> = ron::de::from_reader(File::open(path)?)?;
+        Self::validate(encounters)
+    }
+
+    pub fn built_in() -> Self {
+        // This is checked by a test, so it won't fail
+        Self::validate(ron::de::from_str(DEFAULT_ENCOUNTERS).unwrap()).unwrap()
+    }
+
+    fn validate(encounters: Vec<Encounter>) -> Result<Self, failure::Error> {
+        for encounter in &encounters {
+            // There are only keys for 4 choices
+            if encounter.choices.is_empty() || encounter.choices.len() > 4 {
+                return Err(failure::format_err!("Encounter '{}' has {} choices, but it needs between 1 and 4", encounter.name, encounter.choices.len()));
+            }
+        }
+
+        Ok(Encounters(encounters))
+    }
+}
+
+// Which encounters have happened, and the one waiting for the player to choose what to do
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct EncounterState {
+    pub happened: Vec<String>,
+    pub active: Option<Encounter>,
+    // The location of the last star system that the fleet arrived in
+    pub system: Option<(f32, f32)>,
+    // When to next check whether anything has been triggered
+    pub next_check: f32
+}
@@ -8,36 +8,124 @@ use specs::saveload::*;
 use crate::util::*;
 use crate::Marker;
 
-pub fn create_ship(world: &mut World, tag: ShipType, position: Vector3<f32>, rotation: Quaternion<f32>, side: Side) -> Entity {
-    let components = tag.default_components(0);
+// The stats that come from a ship's definition and components, shared by new ships and ones whose definitions were reloaded
+fn ship_stats(definition: &ShipDefinition, components: &Components) -> (MaxSpeed, TurnRate, MinimumCrew, Option<CanAttack>) {
+    let can_attack = if definition.armed {
+        Some(CanAttack {
+            delay: 1.0,
+            time: 1.0,
+            range: 50.0
+        })
+    } else {
+        None
+    };
+
+    (
+        MaxSpeed(components.thrust() / definition.mass),
+        TurnRate::new(components.thrust(), definition.mass),
+        MinimumCrew(definition.minimum_crew + components.crew()),
+        can_attack
+    )
+}
+
+// Returns None if the ship type isn't defined
+pub fn create_ship(world: &mut World, tag: ShipType, position: Vector3<f32>, rotation: Quaternion<f32>, side: Side) -> Option<Entity> {
+    let (definition, components) = {
+        let definitions = world.fetch::<ShipDefinitions>();
+        (definitions.get(&tag)?.clone(), definitions.default_components(&tag, 0)?)
+    };
+
+    let (max_speed, turn_rate, minimum_crew, can_attack) = ship_stats(&definition, &components);
 
     let mut entity = world.create_entity()
         .with(Position(position))
-        .with(Size(tag.size()))
-        .with(tag.model())
-        .with(MaxSpeed(components.thrust() / tag.mass()))
-        .with(Health(tag.mass()))
+        .with(Size(definition.size))
+        .with(definition.model.clone())
+        .with(max_speed)
+        .with(turn_rate)
+        .with(AngularVelocity::default())
+        .with(Bank::default())
+        .with(Health(definition.max_health()))
         .with(tag)
         .with(Rotation(rotation))
         .with(Commands(Vec::new()))
         // todo: make materials optional
         .with(Materials(StoredResource::empty(500.0)))
+        .with(Food(StoredResource::full(definition.food_capacity())))
         .with(Selectable::new(false))
         .with(Velocity(Vector3::zero()))
+        .with(minimum_crew)
         .with(side)
-        .with(CanAttack {
-            delay: 1.0,
-            time: 1.0,
-            range: 50.0
-        })
         .marked::<Marker>();
 
+    if let Some(can_attack) = can_attack {
+        entity = entity.with(can_attack);
+    }
+
     if let Some(speed) = components.drill_speed() {
         entity = entity.with(DrillSpeed(speed));
     }
 
-    entity.build()
+    if let Some(capacity) = definition.hangar_capacity {
+        entity = entity.with(Hangar {capacity});
+    }
 
+    Some(entity.build())
+}
+
+// Bring existing ships in line with the ship definitions, after they've been reloaded
+pub fn update_ship_stats(world: &mut World) {
+    let (entities, definitions, tags, mut size, mut model, mut max_speed, mut turn_rate, mut minimum_crew, mut health, mut food, mut can_attack, mut drill_speed, mut hangar): (
+        Entities, Read<ShipDefinitions>, ReadStorage<ShipType>,
+        WriteStorage<Size>, WriteStorage<Model>, WriteStorage<MaxSpeed>, WriteStorage<TurnRate>, WriteStorage<MinimumCrew>,
+        WriteStorage<Health>, WriteStorage<Food>, WriteStorage<CanAttack>, WriteStorage<DrillSpeed>, WriteStorage<Hangar>
+    ) = world.system_data();
+
+    for (entity, tag) in (&entities, &tags).join() {
+        // Ships whose type has been removed keep their old stats
+        let (definition, components) = match (definitions.get(tag), definitions.default_components(tag, 0)) {
+            (Some(definition), Some(components)) => (definition, components),
+            _ => continue
+        };
+
+        let (new_max_speed, new_turn_rate, new_minimum_crew, new_can_attack) = ship_stats(definition, &components);
+
+        size.insert(entity, Size(definition.size)).unwrap();
+        model.insert(entity, definition.model.clone()).unwrap();
+        max_speed.insert(entity, new_max_speed).unwrap();
+        turn_rate.insert(entity, new_turn_rate).unwrap();
+        minimum_crew.insert(entity, new_minimum_crew).unwrap();
+
+        if let Some(health) = health.get_mut(entity) {
+            health.0 = health.0.min(definition.max_health());
+        }
+
+        if let Some(food) = food.get_mut(entity) {
+            food.set_capacity(definition.food_capacity());
+        }
+
+        // Keep the reload timer of ships that were already armed
+        match new_can_attack {
+            Some(new_can_attack) if !can_attack.contains(entity) => {
+                can_attack.insert(entity, new_can_attack).unwrap();
+            },
+            Some(_) => {},
+            None => {
+                can_attack.remove(entity);
+            }
+        }
+
+        if let Some(speed) = components.drill_speed() {
+            drill_speed.insert(entity, DrillSpeed(speed)).unwrap();
+        } else {
+            drill_speed.remove(entity);
+        }
+
+        // Hangars aren't removed, so that docked ships don't get stuck
+        if let Some(capacity) = definition.hangar_capacity {
+            hangar.insert(entity, Hangar {capacity}).unwrap();
+        }
+    }
 }
 
 
@@ -50,61 +138,149 @@ pub fn create_person(parent: Entity, world: &mut World, occupation: Occupation)
         .build();
 }
 
-pub fn add_asteroid(rng: &mut ThreadRng, world: &mut World) {
-    let size: f32 = rng.gen_range(5.0, 50.0);
+pub fn add_feature<R: Rng>(feature: SystemFeature, rng: &mut R, world: &mut World) {
+    match feature {
+        SystemFeature::Asteroid {position, size, resources} => add_asteroid(position, size, resources, rng, world),
+        SystemFeature::Planetoid {position, size} => {
+            world.create_entity()
+                .with(Model::planet())
+                .with(ObjectSpin::random(rng))
+                .with(Position(position))
+                .with(Size(size))
+                .with(Selectable::new(false))
+                .with(Side::Neutral)
+                .marked::<Marker>()
+                .build();
+        },
+        SystemFeature::Moon {orbit, size, resources} => {
+            let position = orbit.position();
+            let moon = add_asteroid(position, size, resources, rng, world);
 
-    let x = rng.gen_range(500.0, 1000.0) * rng.gen_range(-1.0, 1.0);
-    let y = rng.gen_range(-100.0, 100.0);
-    let z = rng.gen_range(500.0, 1000.0) * rng.gen_range(-1.0, 1.0);
+            world.write_storage().insert(moon, orbit).unwrap();
+        },
+        // Nebulae are drawn as a big puff of smoke
+        SystemFeature::NebulaCloud {position, radius} => {
+            world.create_entity()
+                .with(Position(position))
+                .with(Size(radius))
+                .with(Image::Smoke)
+                .with(NoCollide)
+                .with(Nebula {radius})
+                .marked::<Marker>()
+                .build();
+        },
+        SystemFeature::BlackHole {position, size, well} => {
+            world.create_entity()
+                .with(Position(position))
+                .with(Size(size))
+                .with(NoCollide)
+                .with(well)
+                .marked::<Marker>()
+                .build();
+        },
+        SystemFeature::Station {position, recruits} => {
+            world.create_entity()
+                .with(Model::station())
+                .with(ObjectSpin::random(rng))
+                .with(Position(position))
+                .with(Size(15.0))
+                .with(Selectable::new(false))
+                .with(Side::Neutral)
+                .with(Station {recruits})
+                .marked::<Marker>()
+                .build();
+        },
+        SystemFeature::Derelict {position, tag, salvage, survivors} => {
+            let derelict = match create_ship(world, tag, position, look_at(uniform_sphere_distribution(rng)), Side::Neutral) {
+                Some(derelict) => derelict,
+                None => return
+            };
 
-    let resources = size.powi(3) * rng.gen_range(0.1, 1.0);
+            let max_health = world.read_storage::<Health>().get(derelict).unwrap().0;
 
+            world.write_storage().insert(derelict, Salvage(StoredResource::full(salvage))).unwrap();
+            world.write_storage().insert(derelict, Health(max_health * rng.gen_range(0.1, 0.5))).unwrap();
+
+            for _ in 0 .. survivors {
+                create_person(derelict, world, Occupation::Worker);
+            }
+        }
+    }
+}
+
+fn add_asteroid<R: Rng>(position: Vector3<f32>, size: f32, resources: f32, rng: &mut R, world: &mut World) -> Entity {
     world.create_entity()
-        .with(Model::Asteroid)
+        .with(Model::asteroid())
         .with(ObjectSpin::random(rng))
-        .with(Position(Vector3::new(x, y, z)))
+        .with(Position(position))
         .with(MineableMaterials(StoredResource::full(resources)))
         .with(Size(size))
         .with(Selectable::new(false))
         .with(Side::Neutral)
         .marked::<Marker>()
-        .build();
+        .build()
 }
 
-pub fn add_starting_entities(world: &mut World, rng: &mut ThreadRng) {
-    let carrier = create_ship(world, ShipType::Carrier, Vector3::new(0.0, 0.0, 1.0), Quaternion::zero(), Side::Friendly);
+// Create a ship with people aboard, unless its type isn't defined
+fn create_crewed_ship(world: &mut World, tag: ShipType, position: Vector3<f32>, rotation: Quaternion<f32>, side: Side, crew: &[(Occupation, usize)]) {
+    let ship = match create_ship(world, tag, position, rotation, side) {
+        Some(ship) => ship,
+        None => return
+    };
 
-    for _ in 0 .. 45 {
-        create_person(carrier, world, Occupation::Worker);
+    for &(occupation, count) in crew {
+        for _ in 0 .. count {
+            create_person(ship, world, occupation);
+        }
     }
+}
 
-    for _ in 0 .. 20 {
-        create_person(carrier, world, Occupation::Marine);
-    }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
 
-    for _ in 0 .. 25 {
-        create_person(carrier, world, Occupation::Pilot);
+    pub fn starting_fighters(self) -> usize {
+        match self {
+            Difficulty::Easy => 70,
+            Difficulty::Normal => 50,
+            Difficulty::Hard => 30
+        }
     }
 
-    for _ in 0 .. 10 {
-        create_person(carrier, world, Occupation::Government);
+    pub fn enemy_carriers(self) -> usize {
+        match self {
+            Difficulty::Easy | Difficulty::Normal => 1,
+            Difficulty::Hard => 2
+        }
     }
+}
 
-    let tanker = create_ship(world, ShipType::Tanker, Vector3::new(0.0, 0.0, -20.0), Quaternion::zero(), Side::Friendly);
+pub fn add_starting_entities<R: Rng>(world: &mut World, difficulty: Difficulty, rng: &mut R) {
+    let carrier_crew = [(Occupation::Worker, 45), (Occupation::Marine, 20), (Occupation::Pilot, 25), (Occupation::Government, 10)];
+    create_crewed_ship(world, ShipType::carrier(), Vector3::new(0.0, 0.0, 1.0), Quaternion::zero(), Side::Friendly, &carrier_crew);
 
-    for _ in 0 .. 10 {
-        create_person(tanker, world, Occupation::Worker);
-    }
-    
-    for i in 0 .. 50 {
-        let x = (50.0 - i as f32) * 3.0;
-        let fighter = create_ship(world, ShipType::Fighter, Vector3::new(x, 5.0, 0.0), Quaternion::zero(), Side::Friendly);
-        create_person(fighter, world, Occupation::Pilot);
+    create_crewed_ship(world, ShipType::tanker(), Vector3::new(0.0, 0.0, -20.0), Quaternion::zero(), Side::Friendly, &[(Occupation::Worker, 10)]);
+
+    let fighters = difficulty.starting_fighters();
+
+    for i in 0 .. fighters {
+        let x = (fighters - i) as f32 * 3.0;
+        create_crewed_ship(world, ShipType::fighter(), Vector3::new(x, 5.0, 0.0), Quaternion::zero(), Side::Friendly, &[(Occupation::Pilot, 1)]);
     }
 
     for i in 0 .. 2 {
-        create_ship(world, ShipType::Miner, Vector3::new(0.0, 2.5 - i as f32 * 15.0, 30.0), Quaternion::zero(), Side::Friendly);
+        let position = Vector3::new(0.0, 2.5 - i as f32 * 15.0, 30.0);
+        create_crewed_ship(world, ShipType::miner(), position, Quaternion::zero(), Side::Friendly, &[(Occupation::Worker, 5)]);
     }
 
-    create_ship(world, ShipType::Carrier, Vector3::new(100.0, 0.0, 100.0), Quaternion::between_vectors(UP, uniform_sphere_distribution(rng)), Side::Enemy);
+    for i in 0 .. difficulty.enemy_carriers() {
+        let position = Vector3::new(100.0 + i as f32 * 60.0, 0.0, 100.0);
+        create_crewed_ship(world, ShipType::carrier(), position, Quaternion::between_vectors(UP, uniform_sphere_distribution(rng)), Side::Enemy, &[(Occupation::Worker, 60)]);
+    }
 }
@@ -0,0 +1,395 @@
+// Maps keys to the actions they perform, so that the keys can be rebound in a config file
+
+use winit::event::{VirtualKeyCode, MouseButton};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::fs::File;
+use failure::Fail;
+use crate::components::MAX_CONTROL_GROUPS;
+
+pub const INPUT_CONFIG: &str = "input.ron";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum Action {
+    CameraLeft,
+    CameraRight,
+    CameraForwards,
+    CameraBack,
+    CentreCamera,
+    Shift,
+    Control,
+    Delete,
+    Board,
+    Launch,
+    Guard,
+    Patrol,
+    Trade,
+    SetFacing,
+    HoldPosition,
+    Wait,
+    Repeat,
+    RemoveLast,
+    Cycle,
+    Group(u8),
+    Choice(u8),
+    FormationLeft,
+    FormationRight,
+    Pause,
+    Debug,
+    Help,
+    Menu,
+    Save,
+    Load
+}
+
+impl Action {
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::CameraLeft => "move the camera left",
+            Action::CameraRight => "move the camera right",
+            Action::CameraForwards => "move the camera forwards",
+            Action::CameraBack => "move the camera back",
+            Action::CentreCamera => "center the camera on the selected ships",
+            Action::Shift => "hold to add to the selection, queue orders, or move the plane of movement with the mouse",
+            Action::Control => "hold while right clicking to do something before the current order",
+            Action::Delete => "destroy the selected ships",
+            Action::Board => "hold while right clicking an enemy ship to send marines to board it",
+            Action::Launch => "launch the ships docked in the selected carriers",
+            Action::Guard => "hold while right clicking a friendly ship to guard it",
+            Action::Patrol => "hold while right clicking to patrol (shift adds waypoints)",
+            Action::Trade => "hold while right clicking a station to trade materials for recruits instead of repairing",
+            Action::SetFacing => "hold while dragging the right mouse button to choose which way a formation faces",
+            Action::HoldPosition => "hold position",
+            Action::Wait => "wait for a few seconds",
+            Action::Repeat => "repeat the queued orders",
+            Action::RemoveLast => "remove the last queued order",
+            Action::Cycle => "move the current order to the back of the queue",
+            Action::Group(_) => "select a control group (hold control to put the selected ships in it)",
+            Action::Choice(_) => "choose what to do in an event",
+            Action::FormationLeft => "rotate left through the formation list",
+            Action::FormationRight => "rotate right through the formation list",
+            Action::Pause => "pause/unpause",
+            Action::Debug => "toggle the debug view",
+            Action::Help => "toggle this text",
+            Action::Menu => "open the pause menu",
+            Action::Save => "choose a slot to save the game in",
+            Action::Load => "choose a saved game to load"
+        }
+    }
+}
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum InputError {
+    #[fail(display = "There are only {} control groups, so group {} can't be bound", _0, _1)]
+    InvalidGroup(usize, u8)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InputMap {
+    bindings: BTreeMap<Action, Vec<VirtualKeyCode>>
+}
+
+impl InputMap {
+    // Load the bindings from a file, with any actions that aren't in it keeping their default keys
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, failure::Error> {
+        let mut input_map = Self::default();
+
+        if !path.as_ref().exists() {
+            return Ok(input_map);
+        }
+
+        let file: Self = ron::de::from_reader(File::open(path)?)?;
+        input_map.bind_all(file)?;
+        Ok(input_map)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, failure::Error> {
+        let mut input_map = Self::default();
+        input_map.bind_all(ron::de::from_str(text)?)?;
+        Ok(input_map)
+    }
+
+    fn bind_all(&mut self, file: Self) -> Result<(), InputError> {
+        // Control groups are stored as bits in a `ControlGroups`
+        let invalid_group = file.bindings.keys().find_map(|action| match *action {
+            Action::Group(group) if group as usize >= MAX_CONTROL_GROUPS => Some(group),
+            _ => None
+        });
+
+        if let Some(group) = invalid_group {
+            return Err(InputError::InvalidGroup(MAX_CONTROL_GROUPS, group));
+        }
+
+        for (action, keys) in file.bindings {
+            self.bind(action, keys);
+        }
+
+        Ok(())
+    }
+
+    pub fn bind(&mut self, action: Action, keys: Vec<VirtualKeyCode>) {
+        // A key can only do one thing
+        for bound in self.bindings.values_mut() {
+            bound.retain(|key| !keys.contains(key));
+        }
+
+        self.bindings.insert(action, keys);
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings.iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    // The names of the keys bound to an action
+    pub fn key_names(&self, action: Action) -> String {
+        self.bindings.get(&action)
+            .map(|keys| keys.iter().map(|key| key_name(*key)).collect::<Vec<_>>().join(", "))
+            .unwrap_or_default()
+    }
+
+    pub fn help_text(&self) -> Vec<String> {
+        let mut text = Vec::new();
+        let mut group_keys = Vec::new();
+        let mut choice_keys = Vec::new();
+
+        for (action, keys) in &self.bindings {
+            if keys.is_empty() {
+                continue;
+            }
+
+            // Put all the control groups and choices on one line each
+            match action {
+                Action::Group(_) => {
+                    group_keys.extend(keys.iter().map(|key| key_name(*key)));
+                    continue;
+                },
+                Action::Choice(_) => {
+                    choice_keys.extend(keys.iter().map(|key| key_name(*key)));
+                    continue;
+                },
+                _ => {}
+            }
+
+            text.push(help_line(keys.iter().map(|key| key_name(*key)).collect(), *action));
+        }
+
+        if !group_keys.is_empty() {
+            text.push(help_line(group_keys, Action::Group(0)));
+        }
+
+        if !choice_keys.is_empty() {
+            text.push(help_line(choice_keys, Action::Choice(0)));
+        }
+
+        text
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use self::VirtualKeyCode::*;
+
+        let mut bindings = BTreeMap::new();
+
+        bindings.insert(Action::CameraLeft, vec![A, Left]);
+        bindings.insert(Action::CameraRight, vec![D, Right]);
+        bindings.insert(Action::CameraForwards, vec![W, Up]);
+        bindings.insert(Action::CameraBack, vec![S, Down]);
+        bindings.insert(Action::CentreCamera, vec![C]);
+        bindings.insert(Action::Shift, vec![LShift, T]);
+        bindings.insert(Action::Control, vec![LControl, RControl]);
+        bindings.insert(Action::Delete, vec![Back, Delete]);
+        bindings.insert(Action::Board, vec![B]);
+        bindings.insert(Action::Launch, vec![U]);
+        bindings.insert(Action::Guard, vec![G]);
+        bindings.insert(Action::Patrol, vec![Q]);
+        bindings.insert(Action::Trade, vec![K]);
+        bindings.insert(Action::SetFacing, vec![LAlt]);
+        bindings.insert(Action::HoldPosition, vec![E]);
+        bindings.insert(Action::Wait, vec![V]);
+        bindings.insert(Action::Repeat, vec![R]);
+        bindings.insert(Action::RemoveLast, vec![X]);
+        bindings.insert(Action::Cycle, vec![Tab]);
+
+        let numbers = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+
+        for (group, key) in numbers.iter().enumerate() {
+            bindings.insert(Action::Group(group as u8), vec![*key]);
+        }
+
+        for (choice, key) in [F1, F2, F3, F4].iter().enumerate() {
+            bindings.insert(Action::Choice(choice as u8), vec![*key]);
+        }
+
+        bindings.insert(Action::FormationLeft, vec![Comma]);
+        bindings.insert(Action::FormationRight, vec![Period]);
+        bindings.insert(Action::Pause, vec![P]);
+        bindings.insert(Action::Debug, vec![Slash]);
+        bindings.insert(Action::Help, vec![H]);
+        bindings.insert(Action::Menu, vec![Escape]);
+        bindings.insert(Action::Save, vec![Z]);
+        bindings.insert(Action::Load, vec![L]);
+
+        Self {
+            bindings
+        }
+    }
+}
+
+fn help_line(keys: Vec<String>, action: Action) -> String {
+    format!("{}: {}", keys.join(", "), action.description())
+}
+
+fn key_name(key: VirtualKeyCode) -> String {
+    match key {
+        VirtualKeyCode::Comma => ",".into(),
+        VirtualKeyCode::Period => ".".into(),
+        VirtualKeyCode::Slash => "/".into(),
+        key => {
+            let name = format!("{:?}", key);
+
+            // Number keys are called Key0 to Key9
+            if name.len() == 4 && name.starts_with("Key") {
+                name[3..].to_string()
+            } else {
+                name
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Start,
+    Select
+}
+
+// Gamepad buttons either stand in for a mouse button or do the same thing as a key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadBinding {
+    Mouse(MouseButton),
+    Action(Action)
+}
+
+impl GamepadBinding {
+    fn description(self) -> &'static str {
+        match self {
+            GamepadBinding::Mouse(MouseButton::Left) => "left click",
+            GamepadBinding::Mouse(MouseButton::Right) => "right click",
+            GamepadBinding::Mouse(_) => "middle click",
+            GamepadBinding::Action(action) => action.description()
+        }
+    }
+}
+
+const GAMEPAD_BINDINGS: [(GamepadButton, GamepadBinding); 12] = [
+    (GamepadButton::South, GamepadBinding::Mouse(MouseButton::Left)),
+    (GamepadButton::East, GamepadBinding::Mouse(MouseButton::Right)),
+    (GamepadButton::North, GamepadBinding::Mouse(MouseButton::Middle)),
+    (GamepadButton::West, GamepadBinding::Action(Action::Shift)),
+    (GamepadButton::LeftBumper, GamepadBinding::Action(Action::CameraLeft)),
+    (GamepadButton::RightBumper, GamepadBinding::Action(Action::CameraRight)),
+    (GamepadButton::DPadUp, GamepadBinding::Action(Action::CameraForwards)),
+    (GamepadButton::DPadDown, GamepadBinding::Action(Action::CameraBack)),
+    (GamepadButton::DPadLeft, GamepadBinding::Action(Action::FormationLeft)),
+    (GamepadButton::DPadRight, GamepadBinding::Action(Action::FormationRight)),
+    (GamepadButton::Start, GamepadBinding::Action(Action::Pause)),
+    (GamepadButton::Select, GamepadBinding::Action(Action::Help))
+];
+
+pub fn gamepad_binding(button: GamepadButton) -> Option<GamepadBinding> {
+    GAMEPAD_BINDINGS.iter()
+        .find(|(bound, _)| *bound == button)
+        .map(|(_, binding)| *binding)
+}
+
+pub fn gamepad_help_text() -> Vec<String> {
+    let mut text = vec!["With a gamepad, the left stick moves the cursor and the right stick rotates the camera".to_string()];
+
+    for (button, binding) in &GAMEPAD_BINDINGS {
+        text.push(format!("{:?}: {}", button, binding.description()));
+    }
+
+    text
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    Axis(GamepadAxis, f32),
+    Button(GamepadButton, bool)
+}
+
+impl GamepadEvent {
+    #[cfg(feature = "gamepad")]
+    pub fn from_gilrs(event: gilrs::EventType) -> Option<Self> {
+        use gilrs::{EventType, Axis, Button};
+
+        let button = |button| match button {
+            Button::South => Some(GamepadButton::South),
+            Button::East => Some(GamepadButton::East),
+            Button::North => Some(GamepadButton::North),
+            Button::West => Some(GamepadButton::West),
+            Button::LeftTrigger => Some(GamepadButton::LeftBumper),
+            Button::RightTrigger => Some(GamepadButton::RightBumper),
+            Button::DPadUp => Some(GamepadButton::DPadUp),
+            Button::DPadDown => Some(GamepadButton::DPadDown),
+            Button::DPadLeft => Some(GamepadButton::DPadLeft),
+            Button::DPadRight => Some(GamepadButton::DPadRight),
+            Button::Start => Some(GamepadButton::Start),
+            Button::Select => Some(GamepadButton::Select),
+            _ => None
+        };
+
+        match event {
+            EventType::ButtonPressed(pressed, _) => button(pressed).map(|button| GamepadEvent::Button(button, true)),
+            EventType::ButtonReleased(released, _) => button(released).map(|button| GamepadEvent::Button(button, false)),
+            EventType::AxisChanged(axis, value, _) => match axis {
+                Axis::LeftStickX => Some(GamepadEvent::Axis(GamepadAxis::LeftStickX, value)),
+                Axis::LeftStickY => Some(GamepadEvent::Axis(GamepadAxis::LeftStickY, value)),
+                Axis::RightStickX => Some(GamepadEvent::Axis(GamepadAxis::RightStickX, value)),
+                Axis::RightStickY => Some(GamepadEvent::Axis(GamepadAxis::RightStickY, value)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+// The state of the sticks, which are used every frame instead of when they change
+#[derive(Default)]
+pub struct Gamepad {
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32)
+}
+
+impl Gamepad {
+    pub fn handle(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Axis(GamepadAxis::LeftStickX, value) => self.left_stick.0 = value,
+            GamepadEvent::Axis(GamepadAxis::LeftStickY, value) => self.left_stick.1 = value,
+            GamepadEvent::Axis(GamepadAxis::RightStickX, value) => self.right_stick.0 = value,
+            GamepadEvent::Axis(GamepadAxis::RightStickY, value) => self.right_stick.1 = value,
+            GamepadEvent::Button(_, _) => {}
+        }
+    }
+}
@@ -34,6 +34,9 @@ extern crate wgpu;
 extern crate winit;
 extern crate futures;
 extern crate zerocopy;
+extern crate ron;
+#[cfg(feature = "gamepad")]
+extern crate gilrs;
 
 use rand::*;
 use rand::rngs::*;
@@ -52,6 +55,14 @@ mod systems;
 mod entities;
 mod tests;
 mod resources;
+mod input;
+mod pathfinding;
+mod encounters;
+mod particles;
+mod ui;
+mod menus;
+#[cfg(feature = "native")]
+mod watcher;
 
 use crate::star_system::*;
 use controls::*;
@@ -61,6 +72,7 @@ use crate::util::*;
 use crate::ships::*;
 use entities::*;
 use crate::resources::*;
+use crate::menus::{GameState, MenuEvent};
 
 pub struct SaveLoad;
 
@@ -72,82 +84,129 @@ struct Game {
     world: specs::World,
     update_dispatcher: Dispatcher<'static, 'static>,
     render_dispatcher: Dispatcher<'static, 'static>,
+    // The HUD and other things that are only drawn while playing
+    ui_render_dispatcher: Dispatcher<'static, 'static>,
+    menus: menus::Menus,
+    quit: bool
 }
 
 impl Game {
     async fn new(mut world: World, events_loop: &event_loop::EventLoop<()>) -> Self {
         let update_builder = DispatcherBuilder::new()
             .with(EventHandlerSystem, "events", &[])
+            .with(GamepadSystem, "gamepad", &["events"])
             .with(SeekSystem, "seek", &[])
             .with(AvoidanceSystem, "avoidance", &[])
             .with(FrictionSystem, "friction", &[])
+            .with(FlockingSystem, "flocking", &[])
+            .with(CrewSystem, "crew", &[])
+            .with(FoodSystem, "food", &["events"])
 
             // these have to wait for events because of stuff like paused being pressed
-            .with(SetMouseRay, "mouse_ray", &["events"])
+            .with(SetMouseRay, "mouse_ray", &["events", "gamepad"])
+            .with(HudSystem, "hud", &["events"])
             .with(TimeStepSystem, "time step", &["events"])
             .with(StepLogSystem, "step log", &["events"])
-            .with(ReduceAttackTime, "reduce_attack", &["events"])
+            .with(ReduceAttackTime, "reduce_attack", &["events", "crew"])
             .with(TickTimedEntities, "tick_timed", &["events"])
             .with(TestDeleteSystem, "test_delete", &["events"])
             .with(SpinSystem, "spin", &["events"])
+            .with(AnimationSystem, "animation", &["events"])
+            .with(OrbitSystem, "orbit", &["events"])
             .with(MiddleClickSystem, "middle_click", &["events"])
-            .with(SaveSystem, "save", &["events"])
-            .with(LoadSystem, "load", &["events"])
+            .with(MenuKeysSystem, "menu_keys", &["events"])
     
-            .with(MergeForceSystem, "merge", &["events", "seek", "avoidance", "friction"])
+            .with(MergeForceSystem, "merge", &["events", "seek", "avoidance", "friction", "flocking", "crew"])
 
-            .with(ApplyVelocitySystem, "apply", &["merge"])
+            .with(GravitySystem, "gravity", &["merge"])
+            .with(ApplyVelocitySystem, "apply", &["merge", "gravity"])
             .with(SetRotationSystem, "set_rotation", &["merge"])
-
-            .with(ShipMovementSystem, "ship_movement", &["apply"])
-            .with(SpawnSmokeSystem, "spawn_smoke", &["apply"])
+            .with(TurnSystem, "turn", &["merge"])
+
+            .with(FormationSystem, "formations", &["apply"])
+            .with(ShipMovementSystem, "ship_movement", &["apply", "formations"])
+            .with(LaunchBoardingSystem, "launch_boarding", &["ship_movement"])
+            .with(BoardingSystem, "boarding", &["launch_boarding"])
+            .with(LaunchOrderSystem, "launch_order", &["events", "hud"])
+            .with(HangarSystem, "hangar", &["ship_movement", "launch_order"])
+            .with(DockedSystem, "docked", &["hangar"])
+            .with(QueueEditSystem, "queue_edit", &["events", "hud"])
+            .with(GuardSystem, "guard", &["ship_movement"])
+            .with(PathfindingSystem, "pathfinding", &["ship_movement"])
+            .with(SalvageSystem, "salvage", &["ship_movement"])
+            .with(StationSystem, "stations", &["ship_movement"])
+            .with(EncounterSystem, "encounters", &["events", "time step"])
+            .with(EmitterSystem, "emitters", &["apply"])
+            .with(MiningSparksSystem, "mining_sparks", &["ship_movement"])
+            .with(StepParticles, "step_particles", &["events"])
             .with(AveragePositionSystem, "avg_pos", &["apply"])
-            .with(DragSelectSystem, "drag", &["apply"])
-            .with(ShootStuffSystem, "shooting", &["apply"])
+            .with(DragSelectSystem, "drag", &["apply", "hud"])
+            .with(ShootStuffSystem, "shooting", &["apply", "guard"])
             .with(KamikazeSystem, "kamikaze", &["apply"])
             .with(StepCameraSystem, "camera", &[])
 
-            .with(FinishSeekSystem, "finish_seek", &["apply", "set_rotation"])
+            .with(FinishSeekSystem, "finish_seek", &["apply", "set_rotation", "turn"])
 
-            .with(EntityUnderMouseSystem, "mouse_entity", &["mouse_ray", "apply", "set_rotation", "spin"])
+            .with(EntityUnderMouseSystem, "mouse_entity", &["mouse_ray", "apply", "set_rotation", "turn", "spin", "orbit"])
 
             .with(RightClickInteractionSystem, "right_click_interaction", &["mouse_entity"])
-            .with(LeftClickSystem, "left_click", &["mouse_entity"])
+            .with(LeftClickSystem, "left_click", &["mouse_entity", "hud"])
+            .with(DoubleClickSelectSystem, "double_click", &["left_click"])
+            .with(ControlGroupSystem, "control_groups", &["left_click", "drag"])
 
-            .with(RightClickSystem, "right_click", &["right_click_interaction"])
+            .with(RightClickSystem, "right_click", &["right_click_interaction", "hud"])
             
             .with(DestroyShips, "destroy_ships", &["kamikaze"])
 
-            .with(StepExplosion, "step_explosion", &["destroy_ships"])
-
-            .with(UpdateControlsSystem, "update_controls", &["left_click", "middle_click", "right_click"]);
+            .with(UpdateControlsSystem, "update_controls", &["left_click", "middle_click", "right_click", "double_click", "control_groups", "menu_keys"]);
 
         let render_builder = DispatcherBuilder::new()
             .with(RenderCommandPaths, "RenderCommandPaths", &[])
             .with(RenderSystem, "RenderSystem", &[])
             .with(ObjectRenderer, "ObjectRenderer", &[])
             .with(RenderBillboards, "RenderBillboards", &[])
+            .with(RenderGravityWells, "RenderGravityWells", &[])
             .with(RenderDebug, "RenderDebug", &[])
 
             .with(RenderSelected, "RenderSelected", &[])
-            .with(RenderMovementPlane, "RenderMovementPlane", &[])
+            .with(RenderMovementPlane, "RenderMovementPlane", &[]);
+
+        let ui_render_builder = DispatcherBuilder::new()
             .with(RenderUI, "RenderUI", &[])
+            .with(RenderHud, "RenderHud", &[])
             .with(RenderLogSystem, "RenderLogSystem", &[])
             .with(RenderDragSelection, "RenderDragSelection", &[])
             .with(RenderMouse, "RenderMouse", &[]);
 
         info!("Update dispatcher graph:\n{:?}", update_builder);
         info!("Render dispatcher graph:\n{:?}", render_builder);
+        info!("UI render dispatcher graph:\n{:?}", ui_render_builder);
+
+        #[cfg(feature = "native")]
+        let assets = context::Assets::load(context::ASSET_DIR).unwrap_or_else(|error| {
+            error!("Failed to load the assets in {}: {}", context::ASSET_DIR, error);
+            world.write_resource::<Log>().append(format!("Failed to load the assets in {}, using the built-in ones", context::ASSET_DIR));
+            context::Assets::embedded()
+        });
+        #[cfg(not(feature = "native"))]
+        let assets = context::Assets::embedded();
+
+        let (context, meshes) = context::Context::new(events_loop, &assets).await;
 
-        let (context, meshes) = context::Context::new(events_loop).await;
+        world.insert(assets.atlas);
 
         world.insert(Meshes::new(meshes));
         world.insert(Dpi(context.dpi()));
+        let (width, height) = context.screen_dimensions();
+        world.insert(pedot::Gui::new(width, height));
 
         Self {
             context, world,
             update_dispatcher: update_builder.build(),
             render_dispatcher: render_builder.build(),
+            ui_render_dispatcher: ui_render_builder.build(),
+            menus: menus::Menus::default(),
+            quit: false
         }
     }
 
@@ -155,14 +214,94 @@ impl Game {
         *self.world.write_resource() = Secs(secs);
         *self.world.write_resource() = ScreenDimensions(self.context.screen_dimensions().into());
 
-        self.update_dispatcher.dispatch(&self.world);
+        if self.menus.in_game() {
+            self.update_dispatcher.dispatch(&self.world);
+
+            self.world.maintain();
+
+            if let Some(state) = self.world.write_resource::<MenuRequest>().0.take() {
+                self.menus.open(state);
+            }
+        } else {
+            // The input is for the menus, so the game shouldn't see it when it's resumed
+            MenuGamepadSystem.run_now(&self.world);
+            self.world.write_resource::<Controls>().release_all();
+
+            let event = self.menus.update(&self.world.read_resource::<pedot::Gui>());
+            self.world.write_resource::<pedot::Gui>().clear();
+
+            if let Some(event) = event {
+                self.handle_menu_event(event);
+            }
+        }
+    }
+
+    fn handle_menu_event(&mut self, event: MenuEvent) {
+        match event {
+            MenuEvent::StartGame(seed, difficulty) => {
+                let mut world = create_world();
+                populate_world(&mut world, seed, difficulty);
+                world.write_resource::<Log>().append(format!("Started a {:?} game with the seed {}", difficulty, seed));
+                self.replace_world(world);
+            },
+            MenuEvent::Save(path) => match save_game(&self.world, &path) {
+                Ok(()) => {
+                    self.world.write_resource::<Log>().append(format!("Saved the game to {}", path.display()));
+                    self.menus.open(GameState::Playing);
+                },
+                Err(error) => {
+                    error!("Failed to save the game to {}: {}", path.display(), error);
+                    self.menus.message = Some(format!("Failed to save the game: {}", error));
+                }
+            },
+            MenuEvent::Load(path) => {
+                let world = create_world();
+
+                match load_game(&world, &path) {
+                    Ok(()) => self.replace_world(world),
+                    Err(error) => {
+                        error!("Failed to load the game from {}: {}", path.display(), error);
+                        self.menus.message = Some(format!("Failed to load the game: {}", error));
+                    }
+                }
+            },
+            MenuEvent::Quit => self.quit = true
+        }
+    }
 
-        self.world.maintain();
+    // Switch to a new game, keeping the assets and settings from the current one
+    fn replace_world(&mut self, mut world: World) {
+        carry_over::<context::TextureAtlas>(&mut self.world, &mut world);
+        carry_over::<Meshes>(&mut self.world, &mut world);
+        carry_over::<Dpi>(&mut self.world, &mut world);
+        carry_over::<pedot::Gui>(&mut self.world, &mut world);
+        carry_over::<input::InputMap>(&mut self.world, &mut world);
+        carry_over::<encounters::Encounters>(&mut self.world, &mut world);
+        carry_over::<particles::Effects>(&mut self.world, &mut world);
+        carry_over::<ShipDefinitions>(&mut self.world, &mut world);
+
+        world.maintain();
+
+        self.world = world;
+        self.menus.open(GameState::Playing);
     }
 
     fn render(&mut self) {
         self.render_dispatcher.dispatch(&self.world);
 
+        if self.menus.in_game() {
+            self.ui_render_dispatcher.dispatch(&self.world);
+        } else {
+            let mut primitives = Vec::new();
+            self.menus.draw(&self.world.read_resource::<pedot::Gui>(), &mut primitives);
+
+            let (dpi, screen_dims, mut lines, mut text): (
+                specs::Read<Dpi>, specs::Read<ScreenDimensions>,
+                specs::Write<context::LineBuffers>, specs::Write<context::TextBuffer>
+            ) = self.world.system_data();
+            push_primitives(primitives, &mut lines, &mut text, dpi.0, screen_dims.0);
+        }
+
         let (camera, mut system, mut buffers, mut line_buffers, mut billboards, mut text): (
             specs::Read<camera::Camera>, specs::Write<star_system::StarSystem>,
             specs::Write<context::ModelBuffers>, specs::Write<context::LineBuffers>,
@@ -189,6 +328,58 @@ impl Game {
     fn request_redraw(&self) {
         self.context.request_redraw();
     }
+
+    // Reload the assets, ship definitions or effects if their files have changed, keeping the old ones if that fails
+    #[cfg(feature = "native")]
+    fn reload(&mut self, changed: &[std::path::PathBuf]) {
+        if changed.iter().any(|path| path.starts_with(context::ASSET_DIR)) {
+            match context::Assets::load(context::ASSET_DIR) {
+                Ok(assets) => {
+                    let meshes = self.context.reload_assets(&assets);
+                    self.world.insert(Meshes::new(meshes));
+                    self.world.insert(assets.atlas);
+                    self.world.write_resource::<Log>().append(format!("Reloaded the assets in {}", context::ASSET_DIR));
+                },
+                Err(error) => {
+                    error!("Failed to reload the assets in {}: {}", context::ASSET_DIR, error);
+                    self.world.write_resource::<Log>().append(format!("Failed to reload the assets in {}: {}", context::ASSET_DIR, error));
+                }
+            }
+        }
+
+        if changed.iter().any(|path| path == std::path::Path::new(SHIP_CONFIG)) {
+            match ShipDefinitions::load(SHIP_CONFIG) {
+                Ok(definitions) => {
+                    self.world.insert(definitions);
+                    update_ship_stats(&mut self.world);
+                    self.world.write_resource::<Log>().append(format!("Reloaded {}", SHIP_CONFIG));
+                },
+                Err(error) => {
+                    error!("Failed to reload {}: {}", SHIP_CONFIG, error);
+                    self.world.write_resource::<Log>().append(format!("Failed to reload {}: {}", SHIP_CONFIG, error));
+                }
+            }
+        }
+
+        if changed.iter().any(|path| path == std::path::Path::new(particles::EFFECT_CONFIG)) {
+            match particles::Effects::load(particles::EFFECT_CONFIG) {
+                Ok(effects) => {
+                    self.world.insert(effects);
+                    self.world.write_resource::<Log>().append(format!("Reloaded {}", particles::EFFECT_CONFIG));
+                },
+                Err(error) => {
+                    error!("Failed to reload {}: {}", particles::EFFECT_CONFIG, error);
+                    self.world.write_resource::<Log>().append(format!("Failed to reload {}: {}", particles::EFFECT_CONFIG, error));
+                }
+            }
+        }
+    }
+}
+
+fn carry_over<R: specs::shred::Resource>(from: &mut World, to: &mut World) {
+    if let Some(resource) = from.remove::<R>() {
+        to.insert(resource);
+    }
 }
 
 use std::alloc::System;
@@ -215,29 +406,100 @@ async fn run() {
 
     let events_loop = event_loop::EventLoop::new();
     
-    let mut game = Game::new(create_world(), &events_loop).await;
+    // A random game is shown behind the main menu
+    let mut world = create_world();
+    populate_world(&mut world, rand::random(), Difficulty::Normal);
+
+    let mut game = Game::new(world, &events_loop).await;
+
+    #[cfg(feature = "native")]
+    match input::InputMap::load(input::INPUT_CONFIG) {
+        Ok(input_map) => game.world.insert(input_map),
+        Err(error) => {
+            error!("Failed to load {}: {}", input::INPUT_CONFIG, error);
+            game.world.write_resource::<Log>().append(format!("Failed to load {}, using the default controls", input::INPUT_CONFIG));
+        }
+    }
+
+    #[cfg(feature = "native")]
+    match encounters::Encounters::load(encounters::ENCOUNTER_CONFIG) {
+        Ok(encounters) => game.world.insert(encounters),
+        Err(error) => {
+            error!("Failed to load {}: {}", encounters::ENCOUNTER_CONFIG, error);
+            game.world.write_resource::<Log>().append(format!("Failed to load {}, using the built-in events", encounters::ENCOUNTER_CONFIG));
+        }
+    }
+
+    #[cfg(feature = "native")]
+    match particles::Effects::load(particles::EFFECT_CONFIG) {
+        Ok(effects) => game.world.insert(effects),
+        Err(error) => {
+            error!("Failed to load {}: {}", particles::EFFECT_CONFIG, error);
+            game.world.write_resource::<Log>().append(format!("Failed to load {}, using the built-in effects", particles::EFFECT_CONFIG));
+        }
+    }
 
     let mut time = wasm_timer::Instant::now();
 
+    #[cfg(feature = "native")]
+    let mut watcher = watcher::FileWatcher::new(&[
+        std::path::Path::new(context::ASSET_DIR),
+        std::path::Path::new(context::ASSET_DIR).join("models").as_path(),
+        std::path::Path::new(SHIP_CONFIG),
+        std::path::Path::new(particles::EFFECT_CONFIG)
+    ]);
+
+    #[cfg(feature = "gamepad")]
+    let mut gilrs = gilrs::Gilrs::new()
+        .map_err(|error| error!("Failed to set up gamepad input: {}", error))
+        .ok();
+
     events_loop.run(move |event, _, control_flow| match event {
         winit::event::Event::WindowEvent {event, ..} => {
-            game.context.copy_event(&event);
+            game.world.write_resource::<pedot::Gui>().update(&event);
 
             match event {
                 winit::event::WindowEvent::CloseRequested => *control_flow = winit::event_loop::ControlFlow::Exit,
                 winit::event::WindowEvent::Resized(size) => {
                     game.context.resize(size.width, size.height);
                 }
-                event => game.world.write_resource::<Events>().push(event.to_static().unwrap())
+                event => game.world.write_resource::<Events>().push(InputEvent::Window(event.to_static().unwrap()))
             }
         },
         winit::event::Event::MainEventsCleared => {
+            #[cfg(feature = "gamepad")]
+            {
+                if let Some(gilrs) = gilrs.as_mut() {
+                    let mut events = game.world.write_resource::<Events>();
+
+                    while let Some(gilrs::Event {event, ..}) = gilrs.next_event() {
+                        if let Some(event) = input::GamepadEvent::from_gilrs(event) {
+                            events.push(InputEvent::Gamepad(event));
+                        }
+                    }
+                }
+            }
+
+            #[cfg(feature = "native")]
+            {
+                let changed = watcher.poll();
+
+                if !changed.is_empty() {
+                    game.reload(&changed);
+                }
+            }
+
             let now = wasm_timer::Instant::now();
         
             let secs = now.duration_since(time).subsec_nanos() as f32 / 10.0_f32.powi(9);
             time = now;
 
             game.update(secs);
+
+            if game.quit {
+                *control_flow = winit::event_loop::ControlFlow::Exit;
+            }
+
             game.request_redraw();
         },
         winit::event::Event::RedrawRequested(_) => game.render(),
@@ -258,6 +520,8 @@ fn create_world() -> World {
     world.insert(MovementPlane(0.0));
     world.insert(Debug(false));
     world.insert(Help(true));
+    world.insert(encounters::EncounterState::default());
+    world.insert(SpareComponents::default());
 
     world.insert(context::ModelBuffers::default());
     world.insert(context::LineBuffers::default());
@@ -280,17 +544,33 @@ fn create_world() -> World {
     world.register::<CreationTime>();
     world.register::<DrillSpeed>();
     world.register::<MineableMaterials>();
+    world.register::<MinimumCrew>();
     
     world.register::<Materials>();
     world.register::<TimeLeft>();
     world.register::<context::Image>();
     world.register::<CanAttack>();
-    world.register::<SpawnSmoke>();
+    world.register::<particles::Emitter>();
     world.register::<AttackTarget>();
     world.register::<Health>();
     world.register::<NoCollide>();
     world.register::<ExplosionSize>();
-    world.register::<Explosion>();
+    world.register::<Boarder>();
+    world.register::<BoardingAction>();
+    world.register::<Hangar>();
+    world.register::<Docked>();
+    world.register::<ControlGroups>();
+    world.register::<FormationGroup>();
+    world.register::<TurnRate>();
+    world.register::<AngularVelocity>();
+    world.register::<Bank>();
+    world.register::<Orbit>();
+    world.register::<GravityWell>();
+    world.register::<Nebula>();
+    world.register::<Salvage>();
+    world.register::<Station>();
+    world.register::<Food>();
+    world.register::<Animated>();
 
     // Temp generated stuff
     
@@ -303,21 +583,52 @@ fn create_world() -> World {
     world.insert(MouseRay::default());
     world.insert(MarkerAllocator::new());
     world.insert(ScreenDimensions::default());
+    world.insert(input::InputMap::default());
+    world.insert(input::Gamepad::default());
+    world.insert(encounters::Encounters::built_in());
+    world.insert(particles::Effects::built_in());
+    world.insert(particles::Particles::default());
+    world.insert(ui::Hud::default());
+    world.insert(MenuRequest::default());
+    world.insert(pedot::Gui::default());
     
     world.register::<SeekPosition>();
     world.register::<SeekForce>();
     world.register::<AvoidanceForce>();
     world.register::<FrictionForce>();
+    world.register::<FlockingForce>();
+    world.register::<Path>();
+    world.register::<CrewEfficiency>();
 
     world.register::<Marker>();
 
-    let mut rng = rand::thread_rng();
+    #[cfg(feature = "native")]
+    let definitions = ShipDefinitions::load(SHIP_CONFIG).unwrap_or_else(|error| {
+        error!("Failed to load {}: {}", SHIP_CONFIG, error);
+        world.write_resource::<Log>().append(format!("Failed to load {}, using the built-in ships", SHIP_CONFIG));
+        ShipDefinitions::built_in()
+    });
+    #[cfg(not(feature = "native"))]
+    let definitions = ShipDefinitions::built_in();
+
+    world.insert(definitions);
+    world.insert(StarSystem::default());
+
+    world
+}
+
+// Generate a star system and the starting fleets. The same seed and difficulty always give the same game.
+fn populate_world(world: &mut World, seed: u64, difficulty: Difficulty) {
+    let mut rng = StdRng::seed_from_u64(seed);
 
     use cgmath::Vector2;
-    let system = StarSystem::new(Vector2::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)), &mut rng, &mut world);
+    let system = StarSystem::new(Vector2::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)), &mut rng, world);
+
+    // Starting out in a system isn't arriving in it
+    world.write_resource::<encounters::EncounterState>().system = Some((system.location.x, system.location.y));
     world.insert(system);
 
-    add_starting_entities(&mut world, &mut rng);
+    add_starting_entities(world, difficulty, &mut rng);
 
-    world
+    world.insert(GameRng(StdRng::seed_from_u64(rng.gen())));
 }
@@ -0,0 +1,296 @@
+// The screens around the game itself: the main menu, the new game options, the pause menu and the save browser.
+// The world's update dispatcher only runs while playing, so these are handled by `Game` directly.
+
+use pedot::*;
+use winit::event::VirtualKeyCode;
+use std::path::PathBuf;
+use crate::entities::Difficulty;
+
+pub const SAVE_DIR: &str = "saves";
+const SAVE_EXTENSION: &str = "sav";
+
+const MAX_SEED_LENGTH: usize = 20;
+const MAX_SLOT_NAME_LENGTH: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaveMode {
+    Save,
+    Load
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameState {
+    MainMenu,
+    NewGame,
+    Playing,
+    PauseMenu,
+    Saves(SaveMode)
+}
+
+pub struct NewGameOptions {
+    pub seed: String,
+    pub difficulty: Difficulty
+}
+
+impl NewGameOptions {
+    // Numbers are used as they are and any other text is hashed, so that e.g. 'asteroids' works as a seed.
+    // An empty seed gives a random game.
+    pub fn seed(&self) -> u64 {
+        if self.seed.is_empty() {
+            return rand::random();
+        }
+
+        self.seed.parse().unwrap_or_else(|_| fnv_hash(&self.seed))
+    }
+}
+
+// FNV-1a, which unlike the standard library's hasher is guaranteed to give the same seed in every version of the game
+fn fnv_hash(text: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    text.bytes().fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME))
+}
+
+impl Default for NewGameOptions {
+    fn default() -> Self {
+        Self {
+            seed: String::new(),
+            difficulty: Difficulty::Normal
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuAction {
+    Open(GameState),
+    // An index into `Difficulty::ALL`
+    SetDifficulty(usize),
+    StartGame,
+    // An index into the list of save slots
+    SelectSlot(usize),
+    Confirm,
+    Back,
+    Quit
+}
+
+// The things that the menus can't do themselves
+#[derive(Debug, PartialEq)]
+pub enum MenuEvent {
+    StartGame(u64, Difficulty),
+    Save(PathBuf),
+    Load(PathBuf),
+    Quit
+}
+
+pub struct Menus {
+    pub state: GameState,
+    panel: Panel<MenuAction>,
+    pub options: NewGameOptions,
+    // The slot name that's typed in or picked in the save browser
+    pub slot_name: String,
+    slots: Vec<String>,
+    // Where the save browser goes back to
+    previous: GameState,
+    // Shown at the bottom of the menu, e.g. when saving fails
+    pub message: Option<String>
+}
+
+impl Menus {
+    pub fn in_game(&self) -> bool {
+        self.state == GameState::Playing
+    }
+
+    pub fn open(&mut self, state: GameState) {
+        if let GameState::Saves(_) = state {
+            match self.state {
+                GameState::Saves(_) => {},
+                current => self.previous = current
+            }
+
+            self.slots = list_slots();
+        }
+
+        self.state = state;
+        self.message = None;
+    }
+
+    // Handle the typing and clicks for this frame, returning anything that the game needs to do
+    pub fn update(&mut self, gui: &Gui) -> Option<MenuEvent> {
+        match self.state {
+            GameState::NewGame => text_input(gui, &mut self.options.seed, MAX_SEED_LENGTH, char::is_alphanumeric),
+            GameState::Saves(SaveMode::Save) => text_input(gui, &mut self.slot_name, MAX_SLOT_NAME_LENGTH, valid_slot_character),
+            _ => {}
+        }
+
+        self.panel.widgets = self.widgets();
+        self.panel.layout(gui);
+
+        let action = if gui.key_pressed(VirtualKeyCode::Escape) {
+            Some(MenuAction::Back)
+        } else if gui.key_pressed(VirtualKeyCode::Return) {
+            match self.state {
+                GameState::NewGame => Some(MenuAction::StartGame),
+                GameState::Saves(_) => Some(MenuAction::Confirm),
+                _ => None
+            }
+        } else {
+            self.panel.interact(gui)
+        };
+
+        action.and_then(|action| self.handle(action))
+    }
+
+    fn handle(&mut self, action: MenuAction) -> Option<MenuEvent> {
+        match action {
+            MenuAction::Open(state) => self.open(state),
+            MenuAction::SetDifficulty(index) => self.options.difficulty = Difficulty::ALL[index],
+            MenuAction::StartGame => return Some(MenuEvent::StartGame(self.options.seed(), self.options.difficulty)),
+            MenuAction::SelectSlot(index) => self.slot_name = self.slots[index].clone(),
+            MenuAction::Confirm => {
+                let name = self.slot_name.trim();
+
+                if name.is_empty() {
+                    self.message = Some("Pick a save slot first".into());
+                    return None;
+                }
+
+                match self.state {
+                    GameState::Saves(SaveMode::Save) => return Some(MenuEvent::Save(slot_path(name))),
+                    GameState::Saves(SaveMode::Load) => return Some(MenuEvent::Load(slot_path(name))),
+                    _ => {}
+                }
+            },
+            MenuAction::Back => {
+                let state = match self.state {
+                    GameState::NewGame => GameState::MainMenu,
+                    GameState::PauseMenu => GameState::Playing,
+                    GameState::Saves(_) => self.previous,
+                    state => state
+                };
+
+                self.open(state);
+            },
+            MenuAction::Quit => return Some(MenuEvent::Quit)
+        }
+
+        None
+    }
+
+    fn widgets(&self) -> Vec<Widget<MenuAction>> {
+        let mut widgets = match self.state {
+            GameState::MainMenu => vec![
+                Widget::label("Fleet Commander"),
+                Widget::button("New game", MenuAction::Open(GameState::NewGame)),
+                Widget::button("Load game", MenuAction::Open(GameState::Saves(SaveMode::Load))),
+                Widget::button("Quit", MenuAction::Quit)
+            ],
+            GameState::NewGame => {
+                let mut difficulties = List::new(Difficulty::ALL.iter().map(|difficulty| format!("{:?}", difficulty)).collect());
+                difficulties.set_index(Difficulty::ALL.iter().position(|difficulty| *difficulty == self.options.difficulty).unwrap());
+
+                vec![
+                    Widget::label("New game"),
+                    Widget::label(format!("Seed: {}_", self.options.seed))
+                        .with_tooltip("Type a seed to play the same game again, or leave it empty for a random one"),
+                    Widget::list(difficulties, MenuAction::SetDifficulty)
+                        .with_tooltip("Harder games start with fewer fighters and more enemies"),
+                    Widget::button("Start", MenuAction::StartGame),
+                    Widget::button("Back", MenuAction::Back)
+                ]
+            },
+            GameState::Playing => Vec::new(),
+            GameState::PauseMenu => vec![
+                Widget::label("Paused"),
+                Widget::button("Resume", MenuAction::Back),
+                Widget::button("Save game", MenuAction::Open(GameState::Saves(SaveMode::Save))),
+                Widget::button("Load game", MenuAction::Open(GameState::Saves(SaveMode::Load))),
+                Widget::button("Quit to the main menu", MenuAction::Open(GameState::MainMenu))
+            ],
+            GameState::Saves(mode) => {
+                let mut widgets = match mode {
+                    SaveMode::Save => vec![
+                        Widget::label("Save game"),
+                        Widget::label(format!("Name: {}_", self.slot_name))
+                            .with_tooltip("Type a name for a new slot, or pick one to overwrite it")
+                    ],
+                    SaveMode::Load => vec![
+                        Widget::label("Load game"),
+                        Widget::label(format!("Slot: {}", self.slot_name))
+                    ]
+                };
+
+                if self.slots.is_empty() {
+                    widgets.push(Widget::label("There aren't any saved games"));
+                }
+
+                widgets.extend(self.slots.iter().enumerate().map(|(index, slot)| Widget::button(slot.clone(), MenuAction::SelectSlot(index))));
+
+                let confirm = match mode {
+                    SaveMode::Save => "Save",
+                    SaveMode::Load => "Load"
+                };
+
+                widgets.push(Widget::button(confirm, MenuAction::Confirm));
+                widgets.push(Widget::button("Back", MenuAction::Back));
+                widgets
+            }
+        };
+
+        if let Some(message) = &self.message {
+            widgets.push(Widget::label(message.clone()));
+        }
+
+        widgets
+    }
+
+    pub fn draw(&self, gui: &Gui, primitives: &mut Vec<Primitive>) {
+        self.panel.draw(gui, primitives);
+        self.panel.draw_tooltip(gui, primitives);
+    }
+}
+
+impl Default for Menus {
+    fn default() -> Self {
+        Self {
+            state: GameState::MainMenu,
+            panel: Panel::new(HorizontalAlign::Middle(0.0), VerticalAlign::Middle(0.0), 300.0),
+            options: NewGameOptions::default(),
+            slot_name: String::new(),
+            slots: Vec::new(),
+            previous: GameState::MainMenu,
+            message: None
+        }
+    }
+}
+
+fn text_input<P: Fn(char) -> bool>(gui: &Gui, text: &mut String, max_length: usize, predicate: P) {
+    if gui.key_pressed(VirtualKeyCode::Back) {
+        text.pop();
+    } else if text.chars().count() < max_length {
+        gui.key_input(text, predicate);
+    }
+}
+
+// Slot names are used as file names, so they're kept simple
+pub fn valid_slot_character(character: char) -> bool {
+    character.is_ascii_alphanumeric() || character == ' ' || character == '-' || character == '_'
+}
+
+pub fn slot_path(name: &str) -> PathBuf {
+    PathBuf::from(SAVE_DIR).join(format!("{}.{}", name, SAVE_EXTENSION))
+}
+
+// The names of the saved games, in alphabetical order
+fn list_slots() -> Vec<String> {
+    let mut slots: Vec<String> = std::fs::read_dir(SAVE_DIR).into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|extension| extension == SAVE_EXTENSION).unwrap_or(false))
+        .filter_map(|path| path.file_stem().and_then(|name| name.to_str()).map(String::from))
+        .collect();
+
+    slots.sort();
+    slots
+}
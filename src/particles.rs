@@ -0,0 +1,176 @@
+// Short-lived visual effects like engine trails and explosions. Particles aren't entities,
+// so they don't use up entity ids or get saved, and they're kept in one reused buffer.
+
+use crate::context::{Image, Animation};
+use crate::util::uniform_sphere_distribution;
+use cgmath::Vector3;
+use rand::Rng;
+use std::collections::HashMap;
+use std::path::Path;
+use std::fs::File;
+
+pub const EFFECT_CONFIG: &str = "effects.ron";
+
+// The effects that are built into the game, for when the config file can't be read
+const DEFAULT_EFFECTS: &str = include_str!("../effects.ron");
+
+// Particles past this are dropped, so that huge battles don't slow everything down
+const MAX_PARTICLES: usize = 10_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Effect {
+    EngineTrail,
+    MuzzleFlash,
+    WeaponImpact,
+    MiningSparks,
+    Explosion
+}
+
+impl Effect {
+    const ALL: [Effect; 5] = [Effect::EngineTrail, Effect::MuzzleFlash, Effect::WeaponImpact, Effect::MiningSparks, Effect::Explosion];
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Sprite {
+    Image(Image),
+    // Played from the start when a particle is created
+    Animation(Animation)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EffectDefinition {
+    pub sprite: Sprite,
+    pub lifetime: f32,
+    // The size of a particle when it's created and when it disappears
+    pub size: (f32, f32),
+    // Particles per second for emitters
+    #[serde(default)]
+    pub rate: f32,
+    // Particles created at once for bursts
+    #[serde(default = "one")]
+    pub count: usize,
+    // Particles fly off in a random direction at up to this speed
+    #[serde(default)]
+    pub speed: f32
+}
+
+fn one() -> usize {
+    1
+}
+
+impl EffectDefinition {
+    fn image(&self, age: f32) -> Image {
+        match self.sprite {
+            Sprite::Image(image) => image,
+            Sprite::Animation(animation) => animation.frame(age)
+        }
+    }
+
+    fn size(&self, age: f32) -> f32 {
+        let t = (age / self.lifetime).min(1.0);
+        self.size.0 + (self.size.1 - self.size.0) * t
+    }
+}
+
+pub struct Effects(HashMap<Effect, EffectDefinition>);
+
+impl Effects {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, failure::Error> {
+        Self::validate(ron::de::from_reader(File::open(path)?)?)
+    }
+
+    pub fn built_in() -> Self {
+        // This is checked by a test, so it won't fail
+        Self::validate(ron::de::from_str(DEFAULT_EFFECTS).unwrap()).unwrap()
+    }
+
+    fn validate(definitions: HashMap<Effect, EffectDefinition>) -> Result<Self, failure::Error> {
+        for effect in &Effect::ALL {
+            let definition = definitions.get(effect)
+                .ok_or_else(|| failure::format_err!("Effect '{:?}' isn't defined", effect))?;
+
+            if definition.lifetime <= 0.0 {
+                return Err(failure::format_err!("Effect '{:?}' needs a lifetime above 0", effect));
+            }
+        }
+
+        Ok(Effects(definitions))
+    }
+
+    pub fn get(&self, effect: Effect) -> &EffectDefinition {
+        &self.0[&effect]
+    }
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self::built_in()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Particle {
+    pub position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    age: f32,
+    // Bursts can be scaled up, e.g. for the explosions of bigger ships
+    scale: f32,
+    effect: Effect
+}
+
+impl Particle {
+    pub fn image(&self, effects: &Effects) -> Image {
+        effects.get(self.effect).image(self.age)
+    }
+
+    pub fn size(&self, effects: &Effects) -> f32 {
+        effects.get(self.effect).size(self.age) * self.scale
+    }
+}
+
+// Pooled so that creating particles doesn't allocate once the buffer has grown
+#[derive(Default)]
+pub struct Particles(Vec<Particle>);
+
+impl Particles {
+    fn spawn<R: Rng>(&mut self, definition: &EffectDefinition, effect: Effect, position: Vector3<f32>, scale: f32, count: usize, rng: &mut R) {
+        let count = count.min(MAX_PARTICLES - self.0.len());
+
+        self.0.extend((0 .. count).map(|_| Particle {
+            position,
+            velocity: uniform_sphere_distribution(rng) * rng.gen_range(0.0, 1.0) * definition.speed * scale,
+            age: 0.0,
+            scale, effect
+        }));
+    }
+
+    pub fn burst<R: Rng>(&mut self, effects: &Effects, effect: Effect, position: Vector3<f32>, scale: f32, rng: &mut R) {
+        let definition = effects.get(effect);
+        self.spawn(definition, effect, position, scale, definition.count, rng);
+    }
+
+    // Emit particles at the effect's rate, rounding randomly so that low rates still work at high framerates
+    pub fn emit<R: Rng>(&mut self, effects: &Effects, effect: Effect, position: Vector3<f32>, secs: f32, rng: &mut R) {
+        let definition = effects.get(effect);
+        let count = (definition.rate * secs + rng.gen_range(0.0, 1.0)) as usize;
+        self.spawn(definition, effect, position, 1.0, count, rng);
+    }
+
+    pub fn step(&mut self, effects: &Effects, secs: f32) {
+        for particle in &mut self.0 {
+            particle.age += secs;
+            particle.position += particle.velocity * secs;
+        }
+
+        // The buffer keeps its capacity, so it's only reallocated when there are more particles than ever before
+        self.0.retain(|particle| particle.age < effects.get(particle.effect).lifetime);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Particle> {
+        self.0.iter()
+    }
+}
+
+// Attached to entities that leave a trail of particles
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Emitter(pub Effect);
@@ -0,0 +1,133 @@
+// Plans paths around big things like asteroids, using a sparse graph of points around their bounding spheres
+
+use cgmath::{Vector3, InnerSpace, MetricSpace};
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use ordered_float::OrderedFloat;
+
+// How far out from an obstacle's bounding sphere the points around it are, so that
+// the straight lines between neighbouring points don't clip the sphere
+const MARGIN: f32 = 1.25;
+
+// The directions of the points around each obstacle: a ring around the middle, and above and below
+const NODE_DIRECTIONS: [[f32; 3]; 10] = [
+    [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0], [-1.0, 0.0, 1.0],
+    [-1.0, 0.0, 0.0], [-1.0, 0.0, -1.0], [0.0, 0.0, -1.0], [1.0, 0.0, -1.0],
+    [0.0, 1.0, 0.0], [0.0, -1.0, 0.0]
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    pub position: Vector3<f32>,
+    pub radius: f32
+}
+
+impl Obstacle {
+    pub fn new(position: Vector3<f32>, radius: f32) -> Self {
+        Self {
+            position, radius
+        }
+    }
+
+    fn contains(&self, point: Vector3<f32>, clearance: f32) -> bool {
+        point.distance(self.position) < self.radius + clearance
+    }
+
+    // Whether a straight line from start to end passes through the obstacle
+    pub fn blocks(&self, start: Vector3<f32>, end: Vector3<f32>, clearance: f32) -> bool {
+        let segment = end - start;
+        let length2 = segment.magnitude2();
+
+        let along = if length2 > 0.0 {
+            ((self.position - start).dot(segment) / length2).max(0.0).min(1.0)
+        } else {
+            0.0
+        };
+
+        self.contains(start + segment * along, clearance)
+    }
+
+    fn nodes(&self, clearance: f32) -> impl Iterator<Item=Vector3<f32>> {
+        let position = self.position;
+        let distance = (self.radius + clearance) * MARGIN;
+
+        NODE_DIRECTIONS.iter()
+            .map(move |direction| position + Vector3::from(*direction).normalize_to(distance))
+    }
+}
+
+// The waypoints from start to end (not including the start), going around the obstacles.
+// A ship with the size of the clearance can fly in straight lines between them
+pub fn find_path(start: Vector3<f32>, end: Vector3<f32>, obstacles: &[Obstacle], clearance: f32) -> Vec<Vector3<f32>> {
+    // Ignore obstacles that are too far away to matter, and ones that the ship is already in or is going into
+    let reach = start.distance(end) / 2.0;
+    let middle = (start + end) / 2.0;
+
+    let obstacles: Vec<Obstacle> = obstacles.iter()
+        .filter(|obstacle| obstacle.position.distance(middle) < reach + (obstacle.radius + clearance) * MARGIN)
+        .filter(|obstacle| !obstacle.contains(start, clearance) && !obstacle.contains(end, clearance))
+        .cloned()
+        .collect();
+
+    let clear = |a: Vector3<f32>, b: Vector3<f32>| !obstacles.iter().any(|obstacle| obstacle.blocks(a, b, clearance));
+
+    if clear(start, end) {
+        return vec![end];
+    }
+
+    let mut nodes = vec![start, end];
+
+    nodes.extend(
+        obstacles.iter()
+            .flat_map(|obstacle| obstacle.nodes(clearance))
+            .filter(|node| !obstacles.iter().any(|obstacle| obstacle.contains(*node, clearance)))
+    );
+
+    // A* from the start (0) to the end (1)
+    let mut cost = vec![std::f32::INFINITY; nodes.len()];
+    let mut came_from = vec![None; nodes.len()];
+    let mut open = BinaryHeap::new();
+
+    cost[0] = 0.0;
+    open.push(Reverse((OrderedFloat(start.distance(end)), 0)));
+
+    while let Some(Reverse((estimate, current))) = open.pop() {
+        // A cheaper way to this node has been found since this entry was pushed
+        if estimate.0 > cost[current] + nodes[current].distance(end) {
+            continue;
+        }
+
+        if current == 1 {
+            let mut path = vec![end];
+            let mut node = current;
+
+            while let Some(previous) = came_from[node] {
+                if previous != 0 {
+                    path.push(nodes[previous]);
+                }
+
+                node = previous;
+            }
+
+            path.reverse();
+            return path;
+        }
+
+        for next in 0 .. nodes.len() {
+            if next == current || !clear(nodes[current], nodes[next]) {
+                continue;
+            }
+
+            let next_cost = cost[current] + nodes[current].distance(nodes[next]);
+
+            if next_cost < cost[next] {
+                cost[next] = next_cost;
+                came_from[next] = Some(current);
+                open.push(Reverse((OrderedFloat(next_cost + nodes[next].distance(end)), next)));
+            }
+        }
+    }
+
+    // There's no way around, so just head straight there and let avoidance deal with it
+    vec![end]
+}
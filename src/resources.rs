@@ -8,6 +8,8 @@ use ncollide3d::shape::*;
 use ncollide3d::query::Ray;
 use ncollide3d::bounding_volume::*;
 use crate::util::*;
+use crate::input::GamepadEvent;
+use rand::{SeedableRng, rngs::StdRng};
 
 #[derive(Component, Default, NewtypeProxy)]
 pub struct Secs(pub f32);
@@ -33,6 +35,21 @@ impl Help {
     }
 }
 
+// Used for anything random that changes how the game plays out, such as boarding fights and evacuations,
+// so that a game's seed decides them. It isn't saved, so a loaded game starts a new sequence.
+#[derive(Component)]
+pub struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+// Components salvaged from derelicts, which the fleet keeps until they're needed
+#[derive(Component, Default, Serialize, Deserialize, Clone)]
+pub struct SpareComponents(pub Vec<ShipComponent>);
+
 #[derive(Component, Default)]
 pub struct EntityUnderMouse(pub Option<(Entity, Vector3<f32>)>);
 
@@ -41,14 +58,25 @@ pub struct EntityUnderMouse(pub Option<(Entity, Vector3<f32>)>);
 #[derive(Component, Default)]
 pub struct RightClickOrder {
     pub to_move: Vec<Entity>,
-    pub command: Option<Command>
+    pub command: Option<Command>,
+    // The way a formation should face, if it was chosen by dragging
+    pub facing: Option<Vector3<f32>>
 }
 
+// A menu that was opened with a key while playing. It's opened by `Game` after the update.
+#[derive(Component, Default)]
+pub struct MenuRequest(pub Option<crate::menus::GameState>);
+
 #[derive(Component, Default)]
 pub struct AveragePosition(pub Option<Vector3<f32>>);
 
+pub enum InputEvent {
+    Window(event::WindowEvent<'static>),
+    Gamepad(GamepadEvent)
+}
+
 #[derive(Component, Default, NewtypeProxy)]
-pub struct Events(pub Vec<event::WindowEvent<'static>>);
+pub struct Events(pub Vec<InputEvent>);
 
 #[derive(Component, Default, Clone, Serialize, Deserialize)]
 pub struct MovementPlane(pub f32);
@@ -110,58 +138,67 @@ pub struct Debug(pub bool);
 
 #[derive(Component)]
 pub struct Meshes {
-    meshes: context::MeshArray
+    meshes: context::MeshMap
 }
 
 impl Meshes {
-    pub fn new(meshes: context::MeshArray) -> Self {
+    pub fn new(meshes: context::MeshMap) -> Self {
         Self {
             meshes
         }
     }
 
-    pub fn get_mesh(&self, model: context::Model) -> &TriMesh<f32> {
-        &self.meshes[model as usize]
+    // Models can come from config files, so they might not have a mesh
+    pub fn get_mesh(&self, model: &context::Model) -> Option<&TriMesh<f32>> {
+        self.meshes.get(model)
     }
 
-    pub fn get_bbox(&self, model: context::Model, pos: Vector3<f32>, rot: Quaternion<f32>, size: f32) -> AABB<f32> {
-        let bbox: AABB<f32> = self.get_mesh(model).bounding_volume(&make_iso(Vector3::zero(), rot));
+    // The radius of a sphere around the mesh at a size of 1
+    pub fn get_radius(&self, model: &context::Model) -> Option<f32> {
+        let aabb = self.get_mesh(model)?.aabb();
+        Some(aabb.mins().coords.norm().max(aabb.maxs().coords.norm()))
+    }
+
+    pub fn get_bbox(&self, model: &context::Model, pos: Vector3<f32>, rot: Quaternion<f32>, size: f32) -> Option<AABB<f32>> {
+        let bbox: AABB<f32> = self.get_mesh(model)?.bounding_volume(&make_iso(Vector3::zero(), rot));
 
         let pos = vector_to_na_vector(pos);
 
-        AABB::new(*bbox.mins() * size + pos, *bbox.maxs() * size + pos)
+        Some(AABB::new(*bbox.mins() * size + pos, *bbox.maxs() * size + pos))
     }
 
-    fn get_mesh_at_size(&self, model: context::Model, size: f32) -> TriMesh<f32> {
-        self.get_mesh(model).clone().scaled(&nalgebra::Vector3::new(size, size, size))
+    fn get_mesh_at_size(&self, model: &context::Model, size: f32) -> Option<TriMesh<f32>> {
+        self.get_mesh(model).map(|mesh| mesh.clone().scaled(&nalgebra::Vector3::new(size, size, size)))
     }
 
     pub fn intersects(
         &self,
-        model_a: context::Model, pos_a: Vector3<f32>, rot_a: Quaternion<f32>, size_a: f32,
-        model_b: context::Model, pos_b: Vector3<f32>, rot_b: Quaternion<f32>, size_b: f32
+        model_a: &context::Model, pos_a: Vector3<f32>, rot_a: Quaternion<f32>, size_a: f32,
+        model_b: &context::Model, pos_b: Vector3<f32>, rot_b: Quaternion<f32>, size_b: f32
     ) -> bool {
-        
-        let bb_a = self.get_bbox(model_a, pos_a, rot_a, size_a);
-        let bb_b = self.get_bbox(model_b, pos_b, rot_b, size_b);
+        let (bb_a, bb_b) = match (self.get_bbox(model_a, pos_a, rot_a, size_a), self.get_bbox(model_b, pos_b, rot_b, size_b)) {
+            (Some(bb_a), Some(bb_b)) => (bb_a, bb_b),
+            _ => return false
+        };
 
         if !bb_a.intersects(&bb_b) {
             return false;
         }
 
-        ncollide3d::query::distance(
-            &make_iso(pos_a, rot_a),
-            &self.get_mesh_at_size(model_a, size_a),
-            &make_iso(pos_b, rot_b),
-            &self.get_mesh_at_size(model_b, size_b)
-        ) == 0.0
+        match (self.get_mesh_at_size(model_a, size_a), self.get_mesh_at_size(model_b, size_b)) {
+            (Some(mesh_a), Some(mesh_b)) => ncollide3d::query::distance(
+                &make_iso(pos_a, rot_a), &mesh_a,
+                &make_iso(pos_b, rot_b), &mesh_b
+            ) == 0.0,
+            _ => false
+        }
     }
 }
 
 impl Default for Meshes {
     fn default() -> Self {
         Self {
-            meshes: context::MeshArray::default()
+            meshes: context::MeshMap::default()
         }
     }
 }
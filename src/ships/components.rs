@@ -1,55 +1,32 @@
-#[derive(Debug, Clone, Serialize, Deserialize, Copy)]
-pub enum ShipComponentType {
-    AX2900Drive,
-    HG900Drive,
-    HG43WarpDrive,
-    Boltor89Cannons,
-    AX17KXDrive,
-    FoodRecycler,
-    MiningDrill
+// The stats of a type of ship component, loaded from the ship definitions file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComponentDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub thrust: f32,
+    #[serde(default)]
+    pub can_warp: bool,
+    #[serde(default)]
+    pub drill_speed: f32,
+    #[serde(default)]
+    pub crew: usize
 }
 
-impl ShipComponentType {
-    pub fn thrust(self) -> f32 {
-        match self {
-            ShipComponentType::AX2900Drive => 1.0,
-            ShipComponentType::HG900Drive => 5.0,
-            ShipComponentType::AX17KXDrive => 100.0,
-            _ => 0.0
-        }
-    }
-
-    pub fn can_warp(self) -> bool {
-        match self {
-            ShipComponentType::HG43WarpDrive => true,
-            ShipComponentType::AX17KXDrive => true,
-            _ => false
-        }
-    }
-
-    pub fn drill_speed(self) -> f32 {
-        match self {
-            ShipComponentType::MiningDrill => 0.01,
-            _ => 0.0
-        }
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShipComponent {
     age: u8,
-    tag: ShipComponentType
+    definition: ComponentDefinition
 }
 
 impl ShipComponent {
-    pub fn new(tag: ShipComponentType, age: u8) -> Self {
+    pub fn new(definition: ComponentDefinition, age: u8) -> Self {
         Self {
-            tag, age
+            definition, age
         }
     }
 
-    pub fn tag(&self) -> ShipComponentType {
-        self.tag
+    pub fn definition(&self) -> &ComponentDefinition {
+        &self.definition
     }
 }
 
@@ -65,16 +42,28 @@ impl Components {
         }
     }
 
-    fn component_types(&self) -> impl Iterator<Item=ShipComponentType> + '_ {
-        self.inner.iter().map(ShipComponent::tag)
+    pub fn into_inner(self) -> Vec<ShipComponent> {
+        self.inner
+    }
+
+    fn definitions(&self) -> impl Iterator<Item=&ComponentDefinition> + '_ {
+        self.inner.iter().map(ShipComponent::definition)
     }
 
     pub fn thrust(&self) -> f32 {
-        self.component_types().map(ShipComponentType::thrust).sum() 
+        self.definitions().map(|definition| definition.thrust).sum()
+    }
+
+    pub fn crew(&self) -> usize {
+        self.definitions().map(|definition| definition.crew).sum()
+    }
+
+    pub fn can_warp(&self) -> bool {
+        self.definitions().any(|definition| definition.can_warp)
     }
 
     pub fn drill_speed(&self) -> Option<f32> {
-        let speed = self.component_types().map(ShipComponentType::drill_speed).sum();
+        let speed = self.definitions().map(|definition| definition.drill_speed).sum();
         Some(speed).filter(|speed| *speed > 0.0)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,163 @@
+// Ship and component stats, loaded from a config file so that new ships can be added without recompiling
+
+use super::*;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::fs::File;
+use failure::Fail;
+
+pub const SHIP_CONFIG: &str = "ships.ron";
+
+// The definitions that are built into the game, for when the config file can't be read
+const DEFAULT_SHIPS: &str = include_str!("../../ships.ron");
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShipDefinition {
+    pub name: String,
+    pub model: Model,
+    pub mass: f32,
+    pub size: f32,
+    pub crew_capacity: usize,
+    // The crew needed to fly the hull itself, not counting the crew its components need
+    pub minimum_crew: usize,
+    #[serde(default)]
+    pub armed: bool,
+    #[serde(default)]
+    pub hangar_capacity: Option<usize>,
+    // Only small ships fit into a hangar
+    #[serde(default)]
+    pub can_dock: bool,
+    // The number of components the ship can have
+    pub slots: usize,
+    // The names of the components it starts with
+    pub loadout: Vec<String>
+}
+
+impl ShipDefinition {
+    pub fn max_health(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn food_capacity(&self) -> f32 {
+        self.crew_capacity as f32 * 50.0
+    }
+}
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum DefinitionError {
+    #[fail(display = "'{}' is defined more than once", _0)]
+    Duplicate(String),
+    #[fail(display = "Ship '{}' has a loadout with '{}', which isn't a component", _0, _1)]
+    UnknownComponent(String, String),
+    #[fail(display = "Ship '{}' has {} components in its loadout but only {} slots", _0, _1, _2)]
+    TooManyComponents(String, usize, usize),
+    #[fail(display = "Ship '{}' needs a positive mass and size", _0)]
+    InvalidDimensions(String),
+    #[fail(display = "Ship '{}' needs {} crew to fly but can only carry {}", _0, _1, _2)]
+    NotEnoughRoom(String, usize, usize),
+    #[fail(display = "The game needs a ship called '{}'", _0)]
+    Missing(ShipType)
+}
+
+#[derive(Deserialize)]
+struct DefinitionsFile {
+    components: Vec<ComponentDefinition>,
+    ships: Vec<ShipDefinition>
+}
+
+pub struct ShipDefinitions {
+    ships: BTreeMap<ShipType, ShipDefinition>,
+    components: HashMap<String, ComponentDefinition>
+}
+
+impl ShipDefinitions {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, failure::Error> {
+        let file: DefinitionsFile = ron::de::from_reader(File::open(path)?)?;
+        Ok(Self::validate(file)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, failure::Error> {
+        let file: DefinitionsFile = ron::de::from_str(text)?;
+        Ok(Self::validate(file)?)
+    }
+
+    pub fn built_in() -> Self {
+        // This is checked by a test, so it won't fail
+        Self::parse(DEFAULT_SHIPS).unwrap()
+    }
+
+    fn validate(file: DefinitionsFile) -> Result<Self, DefinitionError> {
+        let mut components = HashMap::new();
+
+        for component in file.components {
+            if components.contains_key(&component.name) {
+                return Err(DefinitionError::Duplicate(component.name));
+            }
+
+            components.insert(component.name.clone(), component);
+        }
+
+        let mut ships = BTreeMap::new();
+
+        for ship in file.ships {
+            let tag = ShipType::new(&ship.name);
+
+            if ships.contains_key(&tag) {
+                return Err(DefinitionError::Duplicate(ship.name));
+            }
+
+            if let Some(unknown) = ship.loadout.iter().find(|name| !components.contains_key(*name)) {
+                return Err(DefinitionError::UnknownComponent(ship.name.clone(), unknown.clone()));
+            }
+
+            if ship.loadout.len() > ship.slots {
+                return Err(DefinitionError::TooManyComponents(ship.name.clone(), ship.loadout.len(), ship.slots));
+            }
+
+            if ship.mass <= 0.0 || ship.size <= 0.0 {
+                return Err(DefinitionError::InvalidDimensions(ship.name));
+            }
+
+            let crew_needed = ship.minimum_crew + ship.loadout.iter().map(|name| components[name].crew).sum::<usize>();
+
+            if crew_needed > ship.crew_capacity {
+                return Err(DefinitionError::NotEnoughRoom(ship.name, crew_needed, ship.crew_capacity));
+            }
+
+            ships.insert(tag, ship);
+        }
+
+        let required = [ShipType::carrier(), ShipType::tanker(), ShipType::fighter(), ShipType::miner(), ShipType::escape_pod()];
+
+        if let Some(missing) = required.iter().find(|tag| !ships.contains_key(*tag)) {
+            return Err(DefinitionError::Missing(missing.clone()));
+        }
+
+        Ok(Self {
+            ships, components
+        })
+    }
+
+    // Ships in a loaded game or from before the definitions were reloaded can have types that aren't in them
+    pub fn get(&self, tag: &ShipType) -> Option<&ShipDefinition> {
+        self.ships.get(tag)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&ShipDefinition> {
+        self.ships.values()
+    }
+
+    pub fn default_components(&self, tag: &ShipType, age: u8) -> Option<Components> {
+        Some(Components::new(
+            self.get(tag)?.loadout.iter()
+                .map(|name| ShipComponent::new(self.components[name].clone(), age))
+                .collect()
+        ))
+    }
+}
+
+impl Default for ShipDefinitions {
+    fn default() -> Self {
+        Self::built_in()
+    }
+}
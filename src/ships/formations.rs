@@ -3,76 +3,204 @@ use crate::util::*;
 use std::f32::consts::*;
 use specs::*;
 
-#[derive(Deserialize, Serialize, Debug, Component, Clone)]
+#[derive(Deserialize, Serialize, Debug, Component, Clone, Copy, PartialEq)]
 pub enum Formation {
     Screen,
     DeltaWing,
+    Wedge,
+    Column,
+    Wall,
+    Sphere,
     GoTo
 }
 
+const FORMATIONS: [Formation; 7] = [
+    Formation::Screen,
+    Formation::DeltaWing,
+    Formation::Wedge,
+    Formation::Column,
+    Formation::Wall,
+    Formation::Sphere,
+    Formation::GoTo
+];
+
+// The space left between neighbouring ships in a formation
+const FORMATION_GAP: f32 = 2.0;
+
 impl Formation {
-    pub fn arrange(&self, ships: usize, position: Vector3<f32>, target: Vector3<f32>, distance: f32) -> Vec<Vector3<f32>> {
-        if ships == 0 {
-            return Vec::new();
+    pub fn arrange(&self, sizes: &[f32], target: Vector3<f32>, facing: Vector3<f32>) -> Vec<Vector3<f32>> {
+        self.slots(sizes).into_iter()
+            .map(|offset| formation_point(target, facing, offset))
+            .collect()
+    }
+
+    // The offset for each ship, with the biggest ships put in the most central slots
+    // and each row and column spaced out to fit the ships on either side of it
+    pub fn slots(&self, sizes: &[f32]) -> Vec<Vector3<f32>> {
+        let mut offsets = self.offsets(sizes.len(), 1.0);
+        offsets.sort_by(|a, b| a.magnitude2().partial_cmp(&b.magnitude2()).unwrap());
+
+        let mut by_size: Vec<usize> = (0 .. sizes.len()).collect();
+        by_size.sort_by(|a, b| sizes[*b].partial_cmp(&sizes[*a]).unwrap());
+
+        let mut slots = vec![Vector3::zero(); sizes.len()];
+
+        for (ship, offset) in by_size.into_iter().zip(offsets) {
+            slots[ship] = offset;
         }
 
-        let mut step = target - position;
-        step.y = 0.0;
-        let step = step.normalize_to(distance);
+        // A sphere isn't made of rows, so it's spaced to fit the biggest ship to keep it round
+        if *self == Formation::Sphere {
+            let largest = sizes.iter().cloned().fold(0.0, f32::max);
+
+            return slots.into_iter()
+                .map(|slot| slot * (largest * 2.0 + FORMATION_GAP))
+                .collect();
+        }
+
+        let xs = spread(slots.iter().map(|slot| slot.x), sizes);
+        let ys = spread(slots.iter().map(|slot| slot.y), sizes);
+        let zs = spread(slots.iter().map(|slot| slot.z), sizes);
+
+        (0 .. sizes.len())
+            .map(|i| Vector3::new(xs[i], ys[i], zs[i]))
+            .collect()
+    }
 
-        let step_sideways = Quaternion::from_angle_y(Rad(FRAC_PI_2)).rotate_vector(step);
+    // Where each ship goes relative to the centre of the formation, as (sideways, up, forwards)
+    pub fn offsets(&self, ships: usize, distance: f32) -> Vec<Vector3<f32>> {
+        if ships == 0 {
+            return Vec::new();
+        }
 
-        match *self {
-            Formation::Screen => {                
-                let step_up = UP * distance;
+        let middle = (ships - 1) as f32 / 2.0;
 
+        let offsets: Vec<Vector3<f32>> = match *self {
+            Formation::Screen => {
                 let width = (ships as f32).sqrt().ceil() as usize;
 
                 let middle_x = (width - 1) as f32 / 2.0;
 
                 let middle_y = (ships as f32 / width as f32).floor() / 2.0;
 
+                (0 .. ships)
+                    .map(|i| Vector3::new((i % width) as f32 - middle_x, (i / width) as f32 - middle_y, 0.0))
+                    .collect()
+            },
+            Formation::DeltaWing => {
                 (0 .. ships)
                     .map(|i| {
-                        let x = (i % width) as f32 - middle_x;
-                        let y = (i / width) as f32 - middle_y;
-
-                        target + step_sideways * x + step_up * y
+                        let x = i as f32 - middle;
+                        Vector3::new(x, 0.0, -x.abs())
                     })
                     .collect()
             },
-            Formation::DeltaWing => {
-                let middle_x = (ships - 1) as f32 / 2.0;
+            // A filled in triangle, with each row having one more ship than the one in front
+            Formation::Wedge => {
+                let mut offsets = Vec::new();
+                let mut row = 0;
+
+                while offsets.len() < ships {
+                    for i in 0 ..= row {
+                        offsets.push(Vector3::new(i as f32 - row as f32 / 2.0, 0.0, -(row as f32)));
+                    }
+
+                    row += 1;
+                }
+
+                offsets.truncate(ships);
+                offsets
+            },
+            Formation::Column => (0 .. ships).map(|i| Vector3::new(0.0, 0.0, middle - i as f32)).collect(),
+            Formation::Wall => (0 .. ships).map(|i| Vector3::new(i as f32 - middle, 0.0, 0.0)).collect(),
+            // Spread the ships evenly over the surface of a sphere with a fibonacci spiral
+            Formation::Sphere => {
+                if ships == 1 {
+                    return vec![Vector3::zero()];
+                }
+
+                let radius = ((ships as f32).sqrt() * 0.6).max(1.0);
+                let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
 
                 (0 .. ships)
                     .map(|i| {
-                        let x = i as f32 - middle_x;
+                        let y = 1.0 - 2.0 * (i as f32 + 0.5) / ships as f32;
+                        let ring = (1.0 - y * y).sqrt();
+                        let theta = golden_angle * i as f32;
 
-                        let y = -(i as f32 - middle_x).abs();
-
-                        target + step * y + step_sideways * x
+                        Vector3::new(theta.cos() * ring, y, theta.sin() * ring) * radius
                     })
                     .collect()
             },
-            Formation::GoTo => (0 .. ships).map(|_| target).collect()
-        }
+            Formation::GoTo => (0 .. ships).map(|_| Vector3::zero()).collect()
+        };
+
+        offsets.into_iter().map(|offset| offset * distance).collect()
+    }
+
+    // Whether ships ordered into the formation should move together as a group
+    pub fn keeps_shape(&self) -> bool {
+        *self != Formation::GoTo
+    }
+
+    fn index(&self) -> usize {
+        FORMATIONS.iter().position(|formation| formation == self).unwrap()
     }
 
     pub fn rotate_right(&mut self) {
-        match *self {
-            Formation::Screen    => *self = Formation::DeltaWing,
-            Formation::DeltaWing => *self = Formation::GoTo,
-            Formation::GoTo      => *self = Formation::Screen,    
-        }
+        *self = FORMATIONS[(self.index() + 1) % FORMATIONS.len()];
     }
 
     pub fn rotate_left(&mut self) {
-        match *self {
-            Formation::Screen    => *self = Formation::GoTo,
-            Formation::DeltaWing => *self = Formation::Screen,
-            Formation::GoTo      => *self = Formation::DeltaWing,    
+        *self = FORMATIONS[(self.index() + FORMATIONS.len() - 1) % FORMATIONS.len()];
+    }
+}
+
+// Spread out the ships' coordinates along one axis, so that each row is far enough from the
+// next to fit the biggest ships in both of them
+fn spread<I: Iterator<Item=f32>>(coordinates: I, sizes: &[f32]) -> Vec<f32> {
+    let coordinates: Vec<f32> = coordinates.collect();
+
+    // Each row with the size of the biggest ship in it
+    let mut rows: Vec<(f32, f32)> = Vec::new();
+
+    for (&coordinate, &size) in coordinates.iter().zip(sizes) {
+        match rows.iter_mut().find(|(row, _)| (*row - coordinate).abs() < std::f32::EPSILON) {
+            Some(row) => row.1 = row.1.max(size),
+            None => rows.push((coordinate, size))
         }
     }
+
+    rows.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut spread = vec![0.0; rows.len()];
+
+    for i in 1 .. rows.len() {
+        let ((previous, previous_size), (row, size)) = (rows[i - 1], rows[i]);
+        spread[i] = spread[i - 1] + (row - previous) * (previous_size + size + FORMATION_GAP);
+    }
+
+    // Where a coordinate ends up, going past the outside rows as if the next row had ships the same size
+    let place = |coordinate: f32| {
+        let next = rows.iter().position(|(row, _)| *row >= coordinate).unwrap_or(rows.len());
+
+        if next > 0 && next < rows.len() {
+            let ((previous, _), (row, _)) = (rows[next - 1], rows[next]);
+            let t = (coordinate - previous) / (row - previous);
+            spread[next - 1] + (spread[next] - spread[next - 1]) * t
+        } else {
+            let i = next.min(rows.len() - 1);
+            let (row, size) = rows[i];
+            spread[i] + (coordinate - row) * (size * 2.0 + FORMATION_GAP)
+        }
+    };
+
+    // Keep the centre of the formation in the same place
+    let centre = if rows.is_empty() { 0.0 } else { place(0.0) };
+
+    coordinates.into_iter()
+        .map(|coordinate| place(coordinate) - centre)
+        .collect()
 }
 
 impl Default for Formation {
@@ -80,3 +208,21 @@ impl Default for Formation {
         Formation::DeltaWing
     }
 }
+
+// The flat direction from a position to a target
+pub fn formation_facing(position: Vector3<f32>, target: Vector3<f32>) -> Vector3<f32> {
+    let mut step = target - position;
+    step.y = 0.0;
+
+    if step.magnitude2() > 0.0 {
+        step.normalize()
+    } else {
+        -Vector3::unit_z()
+    }
+}
+
+pub fn formation_point(centre: Vector3<f32>, facing: Vector3<f32>, offset: Vector3<f32>) -> Vector3<f32> {
+    let sideways = Quaternion::from_angle_y(Rad(FRAC_PI_2)).rotate_vector(facing);
+
+    centre + sideways * offset.x + UP * offset.y + facing * offset.z
+}
@@ -2,114 +2,120 @@ use cgmath::Vector3;
 use crate::context::*;
 use specs::{DenseVecStorage, Component, Entity, ReadStorage, error::*, saveload::*};
 use crate::components::*;
+use std::fmt;
 
 pub mod components;
 mod storage;
 mod formations;
+mod definitions;
 
 pub use self::components::*;
 pub use self::storage::*;
 pub use self::formations::*;
+pub use self::definitions::*;
 
 #[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
 pub enum Interaction {
     Follow,
     Mine,
-    Attack
+    Attack,
+    Board,
+    Dock,
+    Salvage,
+    Trade,
+    Repair
 }
 
 impl Interaction {
     pub fn image(self) -> Image {
         match self {
-            Interaction::Follow => Image::Move,
-            Interaction::Mine => Image::Mine,
-            Interaction::Attack => Image::Attack
+            Interaction::Follow | Interaction::Dock | Interaction::Trade | Interaction::Repair => Image::Move,
+            Interaction::Mine | Interaction::Salvage => Image::Mine,
+            Interaction::Attack | Interaction::Board => Image::Attack
         }
     }
 }
 
-#[derive(Debug, ConvertSaveload, Clone)]
+#[derive(Debug, ConvertSaveload, Clone, PartialEq)]
 pub enum Command {
     MoveTo(Vector3<f32>),
-    GoToAnd(Entity, Interaction)
+    GoToAnd(Entity, Interaction),
+    Launch,
+    // Loop through the waypoints, heading towards the one at the index
+    Patrol(Vec<Vector3<f32>>, usize),
+    Guard(Entity),
+    // Where to hold, which is filled in with where the ship is when it starts holding
+    HoldPosition(Option<Vector3<f32>>),
+    // Seconds left, total seconds
+    Wait(f32, f32),
+    // Commands that finish before this are added back in before it
+    Repeat,
+    // Keep to a slot in a formation group, as an offset from the centre of the formation
+    InFormation(Entity, Vector3<f32>)
 }
 
 impl Command {
     pub fn point(&self, positions: &ReadStorage<Position>) -> Option<Vector3<f32>> {
         match *self {
             Command::MoveTo(point) => Some(point),
-            Command::GoToAnd(entity, _) => positions.get(entity).map(|position| position.0),
+            Command::GoToAnd(entity, _) | Command::Guard(entity) | Command::InFormation(entity, _) => positions.get(entity).map(|position| position.0),
+            Command::Patrol(ref waypoints, next) => waypoints.get(next).cloned(),
+            Command::HoldPosition(point) => point,
+            Command::Launch | Command::Wait(_, _) | Command::Repeat => None
         }
     }
-}
-
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, PartialOrd, Ord, Component)]
-pub enum ShipType {
-    Fighter,
-    Tanker,
-    Carrier,
-    Miner
-}
 
-impl ShipType {
-    pub fn model(&self) -> Model {
+    pub fn is_repeat(&self) -> bool {
         match *self {
-            ShipType::Fighter => Model::Fighter,
-            ShipType::Tanker => Model::Tanker,
-            ShipType::Carrier => Model::Carrier,
-            ShipType::Miner => Model::Miner
+            Command::Repeat => true,
+            _ => false
         }
     }
 
-    fn crew_capacity(&self) -> usize {
+    // Put the command back into the state it was first given in, so that it can be repeated
+    pub fn reset(&mut self) {
         match *self {
-            ShipType::Fighter => 1,
-            ShipType::Tanker => 10,
-            ShipType::Carrier => 100,
-            ShipType::Miner => 5
+            Command::Wait(ref mut left, total) => *left = total,
+            Command::HoldPosition(ref mut point) => *point = None,
+            _ => {}
         }
     }
+}
 
-    pub fn default_components(&self, age: u8) -> Components {
-        Components::new(
-            match *self {
-                ShipType::Fighter => vec![
-                    ShipComponent::new(ShipComponentType::AX2900Drive, age),
-                    ShipComponent::new(ShipComponentType::Boltor89Cannons, age)
-                ],
-                ShipType::Tanker => vec![
-                    ShipComponent::new(ShipComponentType::HG900Drive, age),
-                    ShipComponent::new(ShipComponentType::HG43WarpDrive, age)
-                ],
-                ShipType::Carrier => vec![
-                    ShipComponent::new(ShipComponentType::AX17KXDrive, age),
-                    ShipComponent::new(ShipComponentType::AX17KXDrive, age),
-                    ShipComponent::new(ShipComponentType::FoodRecycler, age)
-                ],
-                ShipType::Miner => vec![
-                    ShipComponent::new(ShipComponentType::HG900Drive, age),
-                    ShipComponent::new(ShipComponentType::HG43WarpDrive, age),
-                    ShipComponent::new(ShipComponentType::MiningDrill, age),
-                ]
-            }
-        )
-    } 
-
-    pub fn mass(&self) -> f32 {
-        match *self {
-            ShipType::Fighter => 2.0,
-            ShipType::Tanker => 100.0,
-            ShipType::Carrier => 2000.0,
-            ShipType::Miner => 20.0
-        }
+// The name of a type of ship in the ship definitions file
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, PartialOrd, Ord, Hash, Component)]
+#[serde(transparent)]
+pub struct ShipType(String);
+
+impl ShipType {
+    pub fn new(name: &str) -> Self {
+        ShipType(name.into())
     }
 
-    pub fn size(&self) -> f32 {
-        match *self {
-            ShipType::Fighter => 1.0,
-            ShipType::Tanker => 2.0,
-            ShipType::Carrier => 4.0,
-            ShipType::Miner => 2.0
-        }
+    // Ship types that the game creates by name, so they have to be defined
+    pub fn carrier() -> Self {
+        Self::new("Carrier")
+    }
+
+    pub fn tanker() -> Self {
+        Self::new("Tanker")
+    }
+
+    pub fn fighter() -> Self {
+        Self::new("Fighter")
+    }
+
+    pub fn miner() -> Self {
+        Self::new("Miner")
+    }
+
+    pub fn escape_pod() -> Self {
+        Self::new("EscapePod")
+    }
+}
+
+impl fmt::Display for ShipType {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.0)
     }
 }
@@ -37,6 +37,20 @@ impl StoredResource {
         increased_by
     }
 
+    // Anything over the new capacity is lost
+    pub fn set_capacity(&mut self, capacity: f32) {
+        self.capacity = capacity;
+        self.amount = self.amount.min(capacity);
+    }
+
+    pub fn amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn capacity(&self) -> f32 {
+        self.capacity
+    }
+
     pub fn is_empty(&self) -> bool {
         self.amount == 0.0
     }
@@ -3,13 +3,15 @@ use super::*;
 use specs::{DenseVecStorage, World, Component};
 use crate::context;
 use crate::util::*;
+use crate::components::{Orbit, GravityWell};
+use crate::ships::{ShipType, ShipDefinitions};
 use entities::*;
 use spade::delaunay::FloatDelaunayTriangulation;
 use tint::Colour;
 use zerocopy::AsBytes;
 
-#[derive(Debug)]
-enum SystemType {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SystemType {
     Asteroids,
     Planetoid,
     Nebula,
@@ -17,7 +19,7 @@ enum SystemType {
 }
 
 impl SystemType {
-    fn random(rng: &mut ThreadRng) -> Self {
+    fn random<R: Rng>(rng: &mut R) -> Self {
         let num = rng.gen_range(0, 100);
 
         match num {
@@ -30,6 +32,173 @@ impl SystemType {
     }
 }
 
+// Something in a star system, before it's been added to the world
+#[derive(Debug, Clone, PartialEq)]
+pub enum SystemFeature {
+    Asteroid {
+        position: Vector3<f32>,
+        size: f32,
+        resources: f32
+    },
+    Planetoid {
+        position: Vector3<f32>,
+        size: f32
+    },
+    Moon {
+        orbit: Orbit,
+        size: f32,
+        resources: f32
+    },
+    NebulaCloud {
+        position: Vector3<f32>,
+        radius: f32
+    },
+    BlackHole {
+        position: Vector3<f32>,
+        size: f32,
+        well: GravityWell
+    },
+    Station {
+        position: Vector3<f32>,
+        recruits: usize
+    },
+    Derelict {
+        position: Vector3<f32>,
+        tag: ShipType,
+        salvage: f32,
+        survivors: usize
+    }
+}
+
+// Work out what's in a system. The same rng seed always gives the same features
+pub fn generate_features<R: Rng>(system_type: SystemType, definitions: &ShipDefinitions, rng: &mut R) -> Vec<SystemFeature> {
+    let mut features = Vec::new();
+
+    match system_type {
+        // A dense belt of smaller asteroids around the middle of the system
+        SystemType::Asteroids => {
+            let radius = rng.gen_range(600.0, 900.0);
+
+            for _ in 0 .. rng.gen_range(40, 60) {
+                let angle = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
+                let distance = radius + rng.gen_range(-100.0, 100.0);
+
+                let position = Vector3::new(angle.cos() * distance, rng.gen_range(-30.0, 30.0), angle.sin() * distance);
+                let size = rng.gen_range(5.0, 25.0);
+
+                features.push(asteroid(position, size, rng));
+            }
+        },
+        SystemType::Planetoid => {
+            let position = scattered_position(rng);
+            let size = rng.gen_range(150.0, 250.0);
+
+            features.push(SystemFeature::Planetoid {position, size});
+
+            for i in 0 .. rng.gen_range(2, 5) {
+                let moon_size = rng.gen_range(20.0, 50.0);
+
+                features.push(SystemFeature::Moon {
+                    orbit: Orbit {
+                        centre: position,
+                        radius: size * 2.0 + i as f32 * 150.0,
+                        angle: rng.gen_range(0.0, 2.0 * std::f32::consts::PI),
+                        speed: rng.gen_range(0.01, 0.03)
+                    },
+                    size: moon_size,
+                    resources: resources(moon_size, rng)
+                });
+            }
+        },
+        SystemType::Nebula => {
+            for _ in 0 .. rng.gen_range(4, 8) {
+                features.push(SystemFeature::NebulaCloud {
+                    position: scattered_position(rng),
+                    radius: rng.gen_range(150.0, 400.0)
+                });
+            }
+        },
+        SystemType::BlackHole => {
+            let size = rng.gen_range(20.0, 40.0);
+
+            features.push(SystemFeature::BlackHole {
+                position: scattered_position(rng),
+                size,
+                well: GravityWell {
+                    radius: size * rng.gen_range(15.0, 25.0),
+                    strength: rng.gen_range(0.002, 0.005)
+                }
+            });
+        }
+    }
+
+    // Every system has at least a few normal asteroids to mine
+    let asteroids = match system_type {
+        SystemType::Asteroids => 0,
+        _ => rng.gen_range(5, 10)
+    };
+
+    for _ in 0 .. asteroids {
+        let position = scattered_position(rng);
+        let size = rng.gen_range(5.0, 50.0);
+
+        features.push(asteroid(position, size, rng));
+    }
+
+    // Stations are more likely out in the open than in dangerous systems
+    let station_chance = match system_type {
+        SystemType::BlackHole => 0.1,
+        _ => 0.3
+    };
+
+    if rng.gen_bool(station_chance) {
+        features.push(SystemFeature::Station {
+            position: scattered_position(rng),
+            recruits: rng.gen_range(5, 30)
+        });
+    }
+
+    for _ in 0 .. rng.gen_range(0, 3) {
+        let tag = match rng.gen_range(0, 3) {
+            0 => ShipType::fighter(),
+            1 => ShipType::tanker(),
+            _ => ShipType::miner()
+        };
+
+        let crew_capacity = definitions.get(&tag).map(|definition| definition.crew_capacity).unwrap_or(0);
+        let survivors = rng.gen_range(0, crew_capacity + 1);
+
+        features.push(SystemFeature::Derelict {
+            position: scattered_position(rng),
+            salvage: rng.gen_range(50.0, 250.0),
+            tag, survivors
+        });
+    }
+
+    features
+}
+
+fn asteroid<R: Rng>(position: Vector3<f32>, size: f32, rng: &mut R) -> SystemFeature {
+    SystemFeature::Asteroid {
+        position, size,
+        resources: resources(size, rng)
+    }
+}
+
+// Bigger rocks have more in them
+fn resources<R: Rng>(size: f32, rng: &mut R) -> f32 {
+    size.powi(3) * rng.gen_range(0.1, 1.0)
+}
+
+// Somewhere away from the middle of the system, where the starting ships are
+fn scattered_position<R: Rng>(rng: &mut R) -> Vector3<f32> {
+    let distance = rng.gen_range(500.0, 1000.0);
+    let angle: f32 = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
+    let y = rng.gen_range(-100.0, 100.0);
+
+    Vector3::new(angle.cos() * distance, y, angle.sin() * distance)
+}
+
 #[derive(Serialize, Deserialize, Component)]
 pub struct StarSystem {
     pub location: Vector2<f32>,
@@ -38,7 +207,9 @@ pub struct StarSystem {
     pub background: Vec<context::Vertex>,
     pub ambient_colour: [f32; 3],
     #[serde(skip)]
-    pub star_buffer: Option<wgpu::Buffer>
+    pub star_buffer: Option<wgpu::Buffer>,
+    #[serde(skip)]
+    pub background_buffer: Option<wgpu::Buffer>
 }
 
 impl Clone for StarSystem {
@@ -49,13 +220,14 @@ impl Clone for StarSystem {
             light: self.light,
             background: self.background.clone(),
             ambient_colour: self.ambient_colour,
-            star_buffer: None
+            star_buffer: None,
+            background_buffer: None
         }
     }
 }
 
 impl StarSystem {
-    pub fn new(location: Vector2<f32>, rng: &mut ThreadRng, world: &mut World) -> Self {
+    pub fn new<R: Rng>(location: Vector2<f32>, rng: &mut R, world: &mut World) -> Self {
         let _distance_from_center = location.magnitude();
 
         let stars = 10000;
@@ -71,8 +243,10 @@ impl StarSystem {
 
         info!("Generated a {:?} system at {:?}.", system_type, location);
 
-        for _ in 0 .. rng.gen_range(5, 10) {
-            add_asteroid(rng, world);
+        let features = generate_features(system_type, &world.fetch::<ShipDefinitions>(), rng);
+
+        for feature in features {
+            add_feature(feature, rng, world);
         }
 
         let (background, ambient_colour) = make_background(rng);
@@ -80,13 +254,19 @@ impl StarSystem {
         Self {
             light, background, stars, location, ambient_colour,
             star_buffer: None,
+            background_buffer: None,
         }
     }
 
-    pub fn star_buffer(&mut self, device: &wgpu::Device) -> &wgpu::Buffer {
-        let Self { star_buffer, stars, .. } = self;
+    // The background and star buffers, which are only created once for each system
+    pub fn buffers(&mut self, device: &wgpu::Device) -> (&wgpu::Buffer, &wgpu::Buffer) {
+        let Self { star_buffer, stars, background_buffer, background, .. } = self;
 
-        star_buffer.get_or_insert_with(|| {
+        let background_buffer = background_buffer.get_or_insert_with(|| {
+            device.create_buffer_with_data(background.as_bytes(), wgpu::BufferUsage::VERTEX)
+        });
+
+        let star_buffer = star_buffer.get_or_insert_with(|| {
             let vec = stars.iter()
                 .map(|&(x, y, brightness)| (uniform_sphere_distribution_from_coords(x, y), brightness))
                 .map(|(position, brightness)| {
@@ -103,7 +283,9 @@ impl StarSystem {
                 .collect::<Vec<_>>();
 
             device.create_buffer_with_data(vec.as_bytes(), wgpu::BufferUsage::VERTEX)
-        })
+        });
+
+        (background_buffer, star_buffer)
     }
 }
 
@@ -116,12 +298,13 @@ impl Default for StarSystem {
             background: Vec::new(),
             ambient_colour: [0.0; 3],
             star_buffer: None,
+            background_buffer: None,
         }
     }
 }
 
 // https://www.redblobgames.com/x/1842-delaunay-voronoi-sphere/#delaunay
-fn make_background(rng: &mut ThreadRng) -> (Vec<context::Vertex>, [f32; 3]) {
+fn make_background<R: Rng>(rng: &mut R) -> (Vec<context::Vertex>, [f32; 3]) {
     let nebula_colour = Colour::new(rng.gen_range(0.0, 360.0), 1.0, rng.gen_range(0.5, 1.0), 1.0).from_hsv();
     let nebula_colour = Vector3::new(nebula_colour.red as f32, nebula_colour.green as f32, nebula_colour.blue as f32);
     let colour_mod = rng.gen_range(-0.5, 1.0);
@@ -174,7 +357,7 @@ struct ColouredVertex {
 }
 
 impl ColouredVertex {
-    fn rand(rng: &mut ThreadRng, rotation_quat: Quaternion<f32>, colour: Vector3<f32>, colour_mod: f64) -> Self {
+    fn rand<R: Rng>(rng: &mut R, rotation_quat: Quaternion<f32>, colour: Vector3<f32>, colour_mod: f64) -> Self {
         use noise::{NoiseFn, Seedable};
 
         let vector = uniform_sphere_distribution(rng);
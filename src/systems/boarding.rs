@@ -0,0 +1,217 @@
+use super::*;
+use rand::Rng;
+
+// How close a ship needs to be to shuttle its marines across
+pub const SHUTTLE_RANGE: f32 = 20.0;
+// How long each round of fighting between boarders and defenders takes
+pub const BOARDING_ROUND_TIME: f32 = 2.0;
+
+const ATTACKER_HIT_CHANCE: f64 = 0.3;
+// Defenders know the ship and have cover
+const DEFENDER_HIT_CHANCE: f64 = 0.4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardingCasualties {
+    pub attackers: usize,
+    pub defenders: usize
+}
+
+// Both sides fire at the same time, with each marine getting one shot per round
+pub fn boarding_round<R: Rng>(attackers: usize, defenders: usize, rng: &mut R) -> BoardingCasualties {
+    let mut hits = |shooters: usize, chance: f64| (0 .. shooters).filter(|_| rng.gen_bool(chance)).count();
+
+    let defender_casualties = hits(attackers, ATTACKER_HIT_CHANCE).min(defenders);
+    let attacker_casualties = hits(defenders, DEFENDER_HIT_CHANCE).min(attackers);
+
+    BoardingCasualties {
+        attackers: attacker_casualties,
+        defenders: defender_casualties
+    }
+}
+
+// Fight rounds until one side is wiped out, returning the survivors on each side
+pub fn resolve_boarding<R: Rng>(mut attackers: usize, mut defenders: usize, rng: &mut R) -> (usize, usize) {
+    while attackers > 0 && defenders > 0 {
+        let casualties = boarding_round(attackers, defenders, rng);
+        attackers -= casualties.attackers;
+        defenders -= casualties.defenders;
+    }
+
+    (attackers, defenders)
+}
+
+pub struct LaunchBoardingSystem;
+
+impl<'a> System<'a> for LaunchBoardingSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Paused>,
+        Write<'a, Log>,
+        WriteStorage<'a, Commands>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, Boarder>,
+        WriteStorage<'a, BoardingAction>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (entities, paused, mut log, mut commands, mut parent, mut boarder, mut action, occupation, pos, side, docked): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        for (entity, commands, entity_side) in (&entities, &mut commands, &side).join() {
+            let target = match commands.first() {
+                Some(Command::GoToAnd(target, Interaction::Board)) => *target,
+                _ => continue
+            };
+
+            let (entity_pos, target_pos) = match (pos.get(entity), pos.get(target)) {
+                (Some(entity_pos), Some(target_pos)) => (entity_pos.0, target_pos.0),
+                _ => {
+                    commands.remove(0);
+                    continue;
+                }
+            };
+
+            // The target has already been captured
+            if side.get(target) == Some(entity_side) {
+                commands.remove(0);
+                continue;
+            }
+
+            // Marines can walk across between ships that are docked together instead of taking a shuttle
+            let docked_in = |ship: Entity, carrier: Entity| docked.contains(ship) && parent.get(ship).map(|parent| parent.0) == Some(carrier);
+            let docked_together = docked_in(entity, target) || docked_in(target, entity);
+
+            if !docked_together && entity_pos.distance(target_pos) > SHUTTLE_RANGE {
+                continue;
+            }
+
+            let marines: Vec<Entity> = (&entities, &occupation, &parent, !&boarder).join()
+                .filter(|(_, occupation, parent, _)| **occupation == Occupation::Marine && parent.0 == entity)
+                .map(|(marine, _, _, _)| marine)
+                .collect();
+
+            if marines.is_empty() {
+                log.append("No marines left to board with".into());
+            } else {
+                log.append(format!("{} marines are boarding the enemy ship", marines.len()));
+
+                for marine in marines {
+                    parent.insert(marine, Parent(target)).unwrap();
+                    boarder.insert(marine, Boarder {side: entity_side.clone(), origin: entity}).unwrap();
+                }
+
+                if action.get(target).is_none() {
+                    action.insert(target, BoardingAction::default()).unwrap();
+                }
+            }
+
+            commands.remove(0);
+        }
+    }
+}
+
+pub struct BoardingSystem;
+
+impl<'a> System<'a> for BoardingSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Secs>,
+        Read<'a, Paused>,
+        Write<'a, Log>,
+        Write<'a, GameRng>,
+        WriteStorage<'a, BoardingAction>,
+        WriteStorage<'a, Boarder>,
+        WriteStorage<'a, Side>,
+        WriteStorage<'a, Materials>,
+        WriteStorage<'a, Commands>,
+        WriteStorage<'a, AttackTarget>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (entities, secs, paused, mut log, mut rng, mut action, mut boarder, mut side, mut materials, mut commands, mut attack_target, occupation, parent, docked): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let mut finished = Vec::new();
+
+        for (ship, action) in (&entities, &mut action).join() {
+            action.time += secs.0;
+
+            let attackers: Vec<Entity> = (&entities, &boarder, &parent).join()
+                .filter(|(_, _, parent)| parent.0 == ship)
+                .map(|(entity, _, _)| entity)
+                .collect();
+
+            let defenders: Vec<Entity> = (&entities, &occupation, &parent, !&boarder).join()
+                .filter(|(_, occupation, parent, _)| **occupation == Occupation::Marine && parent.0 == ship)
+                .map(|(entity, _, _, _)| entity)
+                .collect();
+
+            if action.time >= BOARDING_ROUND_TIME && !attackers.is_empty() && !defenders.is_empty() {
+                action.time -= BOARDING_ROUND_TIME;
+
+                let casualties = boarding_round(attackers.len(), defenders.len(), &mut rng.0);
+
+                attackers.iter().take(casualties.attackers)
+                    .chain(defenders.iter().take(casualties.defenders))
+                    .for_each(|marine| entities.delete(*marine).unwrap());
+
+                finished.push((ship, attackers[casualties.attackers ..].to_vec(), defenders.len() - casualties.defenders));
+            } else if attackers.is_empty() || defenders.is_empty() {
+                finished.push((ship, attackers, defenders.len()));
+            }
+        }
+
+        for (ship, attackers, defenders) in finished {
+            if attackers.is_empty() {
+                action.remove(ship);
+                log.append("The boarding party was repelled".into());
+            } else if defenders == 0 {
+                action.remove(ship);
+
+                let (new_side, origin) = {
+                    let boarder = boarder.get(attackers[0]).unwrap();
+                    (boarder.side.clone(), boarder.origin)
+                };
+
+                // The surviving marines stay on as the new owners
+                for marine in &attackers {
+                    boarder.remove(*marine);
+                }
+
+                // The ships in its hangar are taken along with it
+                let captured: Vec<Entity> = (&entities, &docked, &parent).join()
+                    .filter(|(_, _, parent)| parent.0 == ship)
+                    .map(|(entity, _, _)| entity)
+                    .chain(std::iter::once(ship))
+                    .collect();
+
+                for captured in captured {
+                    side.insert(captured, new_side.clone()).unwrap();
+                    if let Some(commands) = commands.get_mut(captured) {
+                        commands.clear();
+                    }
+                    attack_target.remove(captured);
+                }
+
+                if entities.is_alive(origin) {
+                    transfer_between_same(&mut materials, ship, origin, std::f32::MAX);
+                }
+
+                log.append(if new_side == Side::Friendly {
+                    "The enemy ship was captured".into()
+                } else {
+                    "One of our ships was captured by the enemy".into()
+                });
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use super::*;
 use cgmath::Zero;
 use crate::{Marker, MarkerAllocator};
+use crate::entities::create_ship;
+use rand::Rng;
+use crate::particles::*;
 
 pub struct TestDeleteSystem;
 
@@ -12,7 +15,7 @@ impl<'a> System<'a> for TestDeleteSystem {
     );
 
     fn run(&mut self, (controls, selectable, mut health): Self::SystemData) {
-        if controls.delete {
+        if controls.held(Action::Delete) {
             (&mut health, &selectable).join()
                 .filter(|(_, selectable)| selectable.selected)
                 .for_each(|(health, _)| {
@@ -28,6 +31,8 @@ impl<'a> System<'a> for ShootStuffSystem {
     type SystemData = (
         Entities<'a>,
         Write<'a, MarkerAllocator>,
+        Read<'a, Effects>,
+        Write<'a, Particles>,
         WriteStorage<'a, CanAttack>,
         
         WriteStorage<'a, Position>,
@@ -38,24 +43,34 @@ impl<'a> System<'a> for ShootStuffSystem {
         WriteStorage<'a, TimeLeft>,
         WriteStorage<'a, Selectable>,
         WriteStorage<'a, Side>,
-        WriteStorage<'a, SpawnSmoke>,
+        WriteStorage<'a, Emitter>,
         WriteStorage<'a, AttackTarget>,
         WriteStorage<'a, MaxSpeed>,
         WriteStorage<'a, Health>,
         WriteStorage<'a, NoCollide>,
         WriteStorage<'a, ExplosionSize>,
 
-        WriteStorage<'a, Marker>
+        WriteStorage<'a, Marker>,
+
+        ReadStorage<'a, CrewEfficiency>,
+        ReadStorage<'a, Docked>
     );
 
     fn run(&mut self, (
-        entities, mut allocator,
+        entities, mut allocator, effects, mut particles,
         mut attack,
-        mut pos, mut rot, mut vel, mut size, mut model, mut time, mut selectable, mut side, mut smoke, mut target, mut speed, mut health, mut nocollide, mut explosion_size,
-        mut markers
+        mut pos, mut rot, mut vel, mut size, mut model, mut time, mut selectable, mut side, mut emitters, mut target, mut speed, mut health, mut nocollide, mut explosion_size,
+        mut markers,
+        efficiency, docked
     ): Self::SystemData) {
+        let mut rng = rand::thread_rng();
+
+        // Ships can't fire from inside a hangar
+        for (entity, attack, _) in (&entities, &mut attack, !&docked).join() {
+            if !efficiency.get(entity).map(CrewEfficiency::is_crewed).unwrap_or(true) {
+                continue;
+            }
 
-        for (entity, attack) in (&entities, &mut attack).join() {
             let entity_pos = *pos.get(entity).unwrap();
             let entity_rot = rot.get(entity).unwrap().clone();
 
@@ -68,16 +83,18 @@ impl<'a> System<'a> for ShootStuffSystem {
 
                 attack.time = attack.delay;
 
+                particles.burst(&effects, Effect::MuzzleFlash, entity_pos.0, 1.0, &mut rng);
+
                 entities.build_entity()
                     .with(entity_pos, &mut pos)
                     .with(entity_rot, &mut rot)
                     .with(Velocity(Vector3::zero()), &mut vel)
                     .with(Size(0.1), &mut size)
-                    .with(Model::Missile, &mut model)
+                    .with(Model::missile(), &mut model)
                     .with(TimeLeft(20.0), &mut time)
                     .with(Selectable::new(false), &mut selectable)
                     .with(Side::Friendly, &mut side)
-                    .with(SpawnSmoke(0), &mut smoke)
+                    .with(Emitter(Effect::EngineTrail), &mut emitters)
                     .with(AttackTarget {entity: target_entity, kamikaze: true}, &mut target)
                     .with(MaxSpeed(5.0), &mut speed)
                     .with(Health(1.0), &mut health)
@@ -95,61 +112,19 @@ pub struct ReduceAttackTime;
 impl<'a> System<'a> for ReduceAttackTime {
     type SystemData = (
         Read<'a, Secs>,
-        Read<'a, Paused>,
-        WriteStorage<'a, CanAttack>,
-    );
-
-    fn run(&mut self, (secs, paused, mut attack): Self::SystemData) {
-        if paused.0 {
-            return;
-        }
-
-        for attack in (&mut attack).join() {
-            attack.time = move_towards(attack.time, 0.0, secs.0);
-        }
-    }
-}
-
-pub struct SpawnSmokeSystem;
-
-impl<'a> System<'a> for SpawnSmokeSystem {
-    type SystemData = (
         Entities<'a>,
         Read<'a, Paused>,
-        Write<'a, MarkerAllocator>,
-
-        WriteStorage<'a, SpawnSmoke>,
-        ReadStorage<'a, Velocity>,
-        
-        WriteStorage<'a, Position>,
-        WriteStorage<'a, Size>,
-        WriteStorage<'a, TimeLeft>,
-        WriteStorage<'a, Image>,
-        WriteStorage<'a, NoCollide>,
-
-        WriteStorage<'a, Marker>
+        WriteStorage<'a, CanAttack>,
+        ReadStorage<'a, CrewEfficiency>
     );
 
-    fn run(&mut self, (entities, paused, mut allocator, mut smoke, vel, mut pos, mut size, mut time, mut image, mut nocollide, mut markers): Self::SystemData) {
+    fn run(&mut self, (secs, entities, paused, mut attack, efficiency): Self::SystemData) {
         if paused.0 {
             return;
         }
 
-        for (entity, vel, mut smoke) in (&entities, &vel, &mut smoke).join() {
-            if let Some(p) = pos.get(entity).map(|p| p.0) {
-                if smoke.0 % 2 == 0 {
-                    entities.build_entity()
-                        .with(Position(p - vel.0), &mut pos)
-                        .with(Size(2.0), &mut size)
-                        .with(TimeLeft(2.0), &mut time)
-                        .with(Image::Smoke, &mut image)
-                        .with(NoCollide, &mut nocollide)
-                        .marked(&mut markers, &mut allocator)
-                        .build();
-                }
-
-                smoke.0 = (smoke.0 + 1) % 2;
-            }
+        for (entity, attack) in (&entities, &mut attack).join() {
+            attack.time = move_towards(attack.time, 0.0, secs.0 * crew_efficiency(&efficiency, entity));
         }
     }
 }
@@ -160,6 +135,8 @@ impl<'a> System<'a> for KamikazeSystem {
     type SystemData = (
         Entities<'a>,
         Read<'a, Meshes>,
+        Read<'a, Effects>,
+        Write<'a, Particles>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Size>,
         ReadStorage<'a, components::Rotation>,
@@ -169,7 +146,9 @@ impl<'a> System<'a> for KamikazeSystem {
         WriteStorage<'a, Health>
     );
 
-    fn run(&mut self, (entities, meshes, position, size, rotation, target, model, mut seek, mut health): Self::SystemData) {
+    fn run(&mut self, (entities, meshes, effects, mut particles, position, size, rotation, target, model, mut seek, mut health): Self::SystemData) {
+        let mut rng = rand::thread_rng();
+
         for (entity, target) in (&entities, &target).join() {
             if target.kamikaze {
                 let entity_pos = position.get(entity).unwrap().0;
@@ -183,9 +162,10 @@ impl<'a> System<'a> for KamikazeSystem {
                     let target_size = size.get(target.entity).unwrap().0;
                     let target_model = model.get(target.entity).unwrap();
 
-                    if meshes.intersects(*entity_model, entity_pos, entity_rot, entity_size, *target_model, target_pos, target_rot, target_size) {
+                    if meshes.intersects(entity_model, entity_pos, entity_rot, entity_size, target_model, target_pos, target_rot, target_size) {
                         health.get_mut(target.entity).unwrap().0 -= 25.0;
                         health.get_mut(entity).unwrap().0 = 0.0;
+                        particles.burst(&effects, Effect::WeaponImpact, entity_pos, 1.0, &mut rng);
                     } else {
                         seek.insert(entity, SeekPosition::to_point(target_pos, false)).unwrap();
                     }
@@ -202,84 +182,103 @@ pub struct DestroyShips;
 impl<'a> System<'a> for DestroyShips {
     type SystemData = (
         Entities<'a>,
-        Write<'a, MarkerAllocator>,
+        Read<'a, LazyUpdate>,
+        Read<'a, ShipDefinitions>,
+        Read<'a, Effects>,
+        Write<'a, Particles>,
+        Write<'a, GameRng>,
 
         ReadStorage<'a, Health>,
         ReadStorage<'a, ExplosionSize>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, components::Rotation>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Size>,
         
-        WriteStorage<'a, Position>,
-        WriteStorage<'a, Size>,
-        WriteStorage<'a, TimeLeft>,
-        WriteStorage<'a, NoCollide>,
-        WriteStorage<'a, Explosion>,
-
-        WriteStorage<'a, Marker>,
-        
-        ReadStorage<'a, Parent>
+        WriteStorage<'a, Parent>
     );
 
-    fn run(&mut self, (entities, mut allocator, health, explosion_size, mut position, mut size, mut time, mut nocollide, mut explosion, mut markers, parents): Self::SystemData) {
+    fn run(&mut self, (
+        entities, lazy, definitions, effects, mut particles, mut game_rng,
+        health, explosion_size, tag, occupation, side, rotation, position, size,
+        mut parents
+    ): Self::SystemData) {
+        let mut rng = rand::thread_rng();
+
         for (entity, health) in (&entities, &health).join() {
             if health.0 <= 0.0 {
+                if let (Some(tag), Some(pos), Some(side)) = (tag.get(entity), position.get(entity), side.get(entity)) {
+                    let rotation = rotation.get(entity).map(|rotation| rotation.0).unwrap_or_else(Quaternion::zero);
+                    evacuate_crew(entity, tag, &definitions, pos.0, rotation, side, &entities, &occupation, &mut parents, &lazy, &mut game_rng.0);
+                }
+
                 delete_entity(entity, &entities, &parents);
                 if let Some(pos) = position.get(entity).cloned() {
                     let explosion_size = explosion_size.get(entity).map(|size| size.0)
                         .or_else(|| size.get(entity).map(|size| size.0 * 2.0))
                         .unwrap_or(10.0);
                     
-                    create_explosion(pos.0, explosion_size, &entities, &mut position, &mut size, &mut time, &mut nocollide, &mut explosion, &mut markers, &mut allocator);
+                    particles.burst(&effects, Effect::Explosion, pos.0, explosion_size, &mut rng);
                 }
             }
         }
     }
 }
 
-fn create_explosion(
-    position: Vector3<f32>, explosion_size: f32,
-    entities: &Entities, pos: &mut WriteStorage<Position>, size: &mut WriteStorage<Size>, time: &mut WriteStorage<TimeLeft>, nocollide: &mut WriteStorage<NoCollide>, explosion: &mut WriteStorage<Explosion>,
-    markers: &mut WriteStorage<Marker>, allocator: &mut Write<MarkerAllocator>
-) -> Entity {
-    entities.build_entity()
-        .with(Position(position), pos)
-        .with(Size(explosion_size), size)
-        .with(TimeLeft(1.0), time)
-        .with(NoCollide, nocollide)
-        .with(Explosion, explosion)
-        .marked(markers, allocator)
-        .build()
-}
+pub const EVACUATION_CHANCE: f64 = 0.75;
+
+// Crew members that make it off a destroyed ship are unparented straight away so that they don't get deleted with it,
+// then put into escape pods once the ship is gone.
+fn evacuate_crew<R: Rng>(
+    ship: Entity, tag: &ShipType, definitions: &ShipDefinitions, position: Vector3<f32>, rotation: Quaternion<f32>, side: &Side,
+    entities: &Entities, occupation: &ReadStorage<Occupation>, parents: &mut WriteStorage<Parent>, lazy: &LazyUpdate, rng: &mut R
+) {
+    let survivors: Vec<Entity> = (entities, occupation, &*parents).join()
+        .filter(|(_, _, parent)| parent.0 == ship)
+        .map(|(entity, _, _)| entity)
+        .filter(|_| rng.gen_bool(EVACUATION_CHANCE))
+        .collect();
+
+    // Nobody gets out without escape pods to get into
+    let pod_capacity = match definitions.get(&ShipType::escape_pod()) {
+        Some(pod) if pod.crew_capacity > 0 => pod.crew_capacity,
+        _ => return
+    };
+
+    if survivors.is_empty() {
+        return;
+    }
 
-pub struct StepExplosion;
+    for person in &survivors {
+        parents.remove(*person);
+    }
 
-impl<'a> System<'a> for StepExplosion {
-    type SystemData = (
-        Entities<'a>,
-        ReadStorage<'a, TimeLeft>,
-        ReadStorage<'a, Explosion>,
-        WriteStorage<'a, Image>,
-    );
+    let spread = definitions.get(tag).map(|definition| definition.size).unwrap_or(1.0);
+
+    for pod_crew in survivors.chunks(pod_capacity) {
+        let pod_crew = pod_crew.to_vec();
+        let pod_position = position + uniform_sphere_distribution(rng) * spread;
+        let side = side.clone();
 
-    fn run(&mut self, (entities, time, explosion, mut image): Self::SystemData) {
-        for (entity, _) in (&entities, &explosion).join() {
-            let time = time.get(entity).unwrap().0;
-
-            let percentage = time / 1.0;
-
-            let im = if percentage > 5.0 / 6.0 {
-                Image::Explosion1
-            } else if percentage > 4.0 / 6.0 {
-                Image::Explosion2
-            } else if percentage > 3.0 / 6.0 {
-                Image::Explosion3
-            } else if percentage > 2.0 / 6.0 {
-                Image::Explosion4
-            } else if percentage > 1.0 / 6.0 {
-                Image::Explosion5
-            } else {
-                Image::Explosion6
+        lazy.exec_mut(move |world| {
+            let pod = match create_ship(world, ShipType::escape_pod(), pod_position, rotation, side) {
+                Some(pod) => pod,
+                None => return
             };
 
-            image.insert(entity, im).unwrap();
-        }
+            let mut parents = world.write_storage::<Parent>();
+
+            for person in pod_crew {
+                parents.insert(person, Parent(pod)).unwrap();
+            }
+        });
     }
+
+    let tag = tag.clone();
+
+    lazy.exec_mut(move |world| {
+        world.write_resource::<Log>().append(format!("{} crew evacuated from a destroyed {}", survivors.len(), tag));
+    });
 }
@@ -0,0 +1,59 @@
+use super::*;
+use std::collections::HashMap;
+
+pub struct CrewSystem;
+
+impl<'a> System<'a> for CrewSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, MinimumCrew>,
+        WriteStorage<'a, CrewEfficiency>
+    );
+
+    fn run(&mut self, (entities, occupation, parent, minimum, mut efficiency): Self::SystemData) {
+        let mut crew = HashMap::new();
+
+        (&occupation, &parent).join()
+            .filter(|(occupation, _)| occupation.is_crew())
+            .for_each(|(_, parent)| *crew.entry(parent.0).or_insert(0) += 1);
+
+        for (entity, minimum) in (&entities, &minimum).join() {
+            let crew = crew.get(&entity).cloned().unwrap_or(0);
+            efficiency.insert(entity, CrewEfficiency::new(crew, minimum.0)).unwrap();
+        }
+    }
+}
+
+// Food per person per second
+const FOOD_EATEN: f32 = 0.01;
+
+// Everyone eats from the stores of the ship they're on
+pub struct FoodSystem;
+
+impl<'a> System<'a> for FoodSystem {
+    type SystemData = (
+        Read<'a, Paused>,
+        Read<'a, Secs>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Parent>,
+        WriteStorage<'a, Food>
+    );
+
+    fn run(&mut self, (paused, secs, occupation, parent, mut food): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        for (_, parent) in (&occupation, &parent).join() {
+            if let Some(food) = food.get_mut(parent.0) {
+                food.reduce(FOOD_EATEN * secs.0);
+            }
+        }
+    }
+}
+
+pub fn crew_efficiency(efficiency: &ReadStorage<CrewEfficiency>, entity: Entity) -> f32 {
+    efficiency.get(entity).map(|efficiency| efficiency.0).unwrap_or(1.0)
+}
@@ -0,0 +1,211 @@
+use super::*;
+use crate::encounters::*;
+use crate::entities::{create_ship, add_feature};
+use rand::Rng;
+use std::collections::HashMap;
+
+// Seconds between checking whether any encounters have been triggered
+const CHECK_INTERVAL: f32 = 10.0;
+// How far from the middle of the fleet spawned things turn up
+const SPAWN_DISTANCE: f32 = 150.0;
+
+// Starts encounters when they're triggered, pausing the game until the player chooses what to do
+pub struct EncounterSystem;
+
+impl<'a> System<'a> for EncounterSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Controls>,
+        Read<'a, Time>,
+        Read<'a, StarSystem>,
+        Read<'a, Encounters>,
+        Read<'a, ShipDefinitions>,
+        Read<'a, LazyUpdate>,
+        Write<'a, EncounterState>,
+        Write<'a, Paused>,
+        Write<'a, Log>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Parent>,
+        WriteStorage<'a, Materials>,
+        WriteStorage<'a, Food>
+    );
+
+    fn run(&mut self, (entities, controls, time, system, encounters, definitions, lazy, mut state, mut paused, mut log, side, tag, pos, occupation, parent, mut materials, mut food): Self::SystemData) {
+        let fleet: Vec<(Entity, Vector3<f32>, &ShipDefinition)> = (&entities, &side, &tag, &pos).join()
+            .filter(|(_, side, _, _)| **side == Side::Friendly)
+            .filter_map(|(entity, _, tag, pos)| definitions.get(tag).map(|definition| (entity, pos.0, definition)))
+            .collect();
+
+        if let Some(encounter) = state.active.clone() {
+            if let Some(choice) = controls.choice().and_then(|choice| encounter.choices.get(choice)) {
+                for outcome in &choice.outcomes {
+                    apply_outcome(outcome, &fleet, &entities, &lazy, &mut log, &occupation, &parent, &mut materials, &mut food);
+                }
+
+                state.active = None;
+                paused.0 = false;
+            }
+
+            return;
+        }
+
+        if paused.0 {
+            return;
+        }
+
+        let location = (system.location.x, system.location.y);
+        let arrived = state.system != Some(location);
+        state.system = Some(location);
+
+        if !arrived && time.0 < state.next_check {
+            return;
+        }
+
+        state.next_check = time.0 + CHECK_INTERVAL;
+
+        let ships: HashSet<Entity> = fleet.iter().map(|(entity, _, _)| *entity).collect();
+
+        let status = FleetStatus {
+            population: (&occupation, &parent).join().filter(|(_, parent)| ships.contains(&parent.0)).count(),
+            ships: fleet.len(),
+            materials: fleet.iter().filter_map(|(entity, _, _)| materials.get(*entity)).map(|materials| materials.amount()).sum(),
+            food: fleet.iter().filter_map(|(entity, _, _)| food.get(*entity)).map(|food| food.amount()).sum(),
+            food_capacity: fleet.iter().map(|(_, _, definition)| definition.food_capacity()).sum(),
+            time: time.0,
+            arrived
+        };
+
+        let mut rng = rand::thread_rng();
+
+        let encounter = encounters.0.iter()
+            .filter(|encounter| encounter.repeatable || !state.happened.contains(&encounter.name))
+            .filter(|encounter| encounter.triggered(&status))
+            .find(|encounter| rng.gen_range(0.0, 1.0) < encounter.chance);
+
+        if let Some(encounter) = encounter {
+            log.append(encounter.text.clone());
+
+            if !state.happened.contains(&encounter.name) {
+                state.happened.push(encounter.name.clone());
+            }
+
+            state.active = Some(encounter.clone());
+            paused.0 = true;
+        }
+    }
+}
+
+fn apply_outcome(
+    outcome: &Outcome, fleet: &[(Entity, Vector3<f32>, &ShipDefinition)],
+    entities: &EntitiesRes, lazy: &LazyUpdate, log: &mut Log,
+    occupation: &ReadStorage<Occupation>, parent: &ReadStorage<Parent>,
+    materials: &mut WriteStorage<Materials>, food: &mut WriteStorage<Food>
+) {
+    let centre = avg(fleet.iter().map(|(_, pos, _)| *pos)).unwrap_or_else(Vector3::zero);
+
+    match outcome {
+        Outcome::Message(message) => log.append(message.clone()),
+        Outcome::Materials(amount) => share(materials, fleet, *amount),
+        Outcome::Food(amount) => share(food, fleet, *amount),
+        Outcome::Population(change) if *change > 0 => {
+            let mut aboard = HashMap::new();
+
+            (occupation, parent).join()
+                .for_each(|(_, parent)| *aboard.entry(parent.0).or_insert(0) += 1);
+
+            // Fill up the ships with room in them
+            let mut spaces = fleet.iter()
+                .flat_map(|(ship, _, definition)| {
+                    let room = definition.crew_capacity.saturating_sub(aboard.get(ship).cloned().unwrap_or(0));
+                    std::iter::repeat(*ship).take(room)
+                });
+
+            for _ in 0 .. *change {
+                let ship = match spaces.next() {
+                    Some(ship) => ship,
+                    None => break
+                };
+
+                lazy.create_entity(entities)
+                    .with(CreationTime::from_age(30))
+                    .with(Occupation::Worker)
+                    .with(Parent(ship))
+                    .marked::<Marker>()
+                    .build();
+            }
+        },
+        Outcome::Population(change) => {
+            let ships: HashSet<Entity> = fleet.iter().map(|(entity, _, _)| *entity).collect();
+
+            (entities, occupation, parent).join()
+                .filter(|(_, _, parent)| ships.contains(&parent.0))
+                .take(change.abs() as usize)
+                .for_each(|(person, _, _)| entities.delete(person).unwrap());
+        },
+        Outcome::SpawnShips {tag, count, side} => {
+            let (tag, count, side) = (tag.clone(), *count, side.clone());
+
+            lazy.exec_mut(move |world| {
+                let mut rng = rand::thread_rng();
+
+                for _ in 0 .. count {
+                    let position = centre + uniform_sphere_distribution(&mut rng) * SPAWN_DISTANCE;
+                    let ship = match create_ship(world, tag.clone(), position, look_at(centre - position), side.clone()) {
+                        Some(ship) => ship,
+                        None => {
+                            world.write_resource::<Log>().append(format!("Can't spawn '{}' ships because they aren't defined", tag));
+                            return;
+                        }
+                    };
+
+                    // Enemies hold their ground, attacking any ships that they spot
+                    if side == Side::Enemy {
+                        world.write_storage::<Commands>().get_mut(ship).unwrap().push(Command::HoldPosition(None));
+                    }
+                }
+            });
+        },
+        Outcome::SpawnAsteroids {count, resources} => {
+            let (count, resources) = (*count, *resources);
+
+            lazy.exec_mut(move |world| {
+                let mut rng = rand::thread_rng();
+
+                for _ in 0 .. count {
+                    let mut offset = uniform_sphere_distribution(&mut rng) * SPAWN_DISTANCE * 2.0;
+                    offset.y /= 4.0;
+
+                    let feature = SystemFeature::Asteroid {
+                        position: centre + offset,
+                        size: rng.gen_range(10.0, 30.0),
+                        resources
+                    };
+
+                    add_feature(feature, &mut rng, world);
+                }
+            });
+        }
+    }
+}
+
+// Add to or take from the ships in the fleet, one after the other
+fn share<G: StorageGetter>(getter: &mut G, fleet: &[(Entity, Vector3<f32>, &ShipDefinition)], amount: f32) {
+    let mut left = amount.abs();
+
+    for (ship, _, _) in fleet {
+        if let Some(storage) = getter.get_mut(*ship) {
+            left -= if amount > 0.0 {
+                storage.increase(left)
+            } else {
+                storage.reduce(left)
+            };
+        }
+
+        if left <= 0.0 {
+            break;
+        }
+    }
+}
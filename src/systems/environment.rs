@@ -0,0 +1,84 @@
+use super::*;
+
+// How far ships can see into a nebula
+pub const NEBULA_SENSOR_RANGE: f32 = 15.0;
+
+// Move moons and anything else that orbits around
+pub struct OrbitSystem;
+
+impl<'a> System<'a> for OrbitSystem {
+    type SystemData = (
+        Read<'a, Secs>,
+        Read<'a, Paused>,
+        WriteStorage<'a, Orbit>,
+        WriteStorage<'a, Position>
+    );
+
+    fn run(&mut self, (secs, paused, mut orbits, mut pos): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        for (orbit, pos) in (&mut orbits, &mut pos).join() {
+            orbit.angle += orbit.speed * secs.0;
+            pos.0 = orbit.position();
+        }
+    }
+}
+
+// Pull moving things towards black holes
+pub struct GravitySystem;
+
+impl<'a> System<'a> for GravitySystem {
+    type SystemData = (
+        Read<'a, Paused>,
+        ReadStorage<'a, GravityWell>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (paused, wells, pos, mut vel, docked): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let wells: Vec<(Vector3<f32>, &GravityWell)> = (&pos, &wells).join()
+            .map(|(pos, well)| (pos.0, well))
+            .collect();
+
+        for (pos, vel, _) in (&pos, &mut vel, !&docked).join() {
+            for (centre, well) in &wells {
+                let distance = pos.0.distance(*centre);
+
+                if distance > 0.0 && distance < well.radius {
+                    vel.0 += (centre - pos.0).normalize_to(well.strength * (1.0 - distance / well.radius));
+                }
+            }
+        }
+    }
+}
+
+// The centre and radius of every nebula
+pub fn nebula_clouds(pos: &ReadStorage<Position>, nebulae: &ReadStorage<Nebula>) -> Vec<(Vector3<f32>, f32)> {
+    (pos, nebulae).join()
+        .map(|(pos, nebula)| (pos.0, nebula.radius))
+        .collect()
+}
+
+// Whether something at a position can be seen from any of the observers' positions
+pub fn spotted<I: Iterator<Item=Vector3<f32>>>(position: Vector3<f32>, range: f32, mut observers: I, nebulae: &[(Vector3<f32>, f32)]) -> bool {
+    let range = sensor_range(position, range, nebulae);
+    observers.any(|observer| observer.distance(position) < range)
+}
+
+// How far away something at a position can be spotted from, given how far it could be seen normally
+pub fn sensor_range(position: Vector3<f32>, range: f32, nebulae: &[(Vector3<f32>, f32)]) -> f32 {
+    let hidden = nebulae.iter().any(|(centre, radius)| position.distance(*centre) < *radius);
+
+    if hidden {
+        range.min(NEBULA_SENSOR_RANGE)
+    } else {
+        range
+    }
+}
@@ -0,0 +1,102 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+// How far a member can be from its slot before the group waits for it to catch up
+const FORMATION_SLACK: f32 = 2.0;
+
+// Moves formation groups towards their targets at the speed of their slowest member
+pub struct FormationSystem;
+
+impl<'a> System<'a> for FormationSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Paused>,
+        WriteStorage<'a, FormationGroup>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Path>,
+        ReadStorage<'a, Commands>,
+        ReadStorage<'a, MaxSpeed>,
+        ReadStorage<'a, CrewEfficiency>
+    );
+
+    fn run(&mut self, (entities, paused, mut groups, mut pos, paths, commands, speed, efficiency): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        // The members of each group that are following it, with their offsets and speeds. Members that have something else to
+        // do first or that can't move (e.g. because they have no crew) are left behind, so that they don't hold up the rest.
+        let mut members: HashMap<Entity, Vec<(Entity, Vector3<f32>, f32)>> = HashMap::new();
+        // Groups that are still in someone's queue
+        let mut queued = HashSet::new();
+
+        for (entity, commands) in (&entities, &commands).join() {
+            for (i, command) in commands.iter().enumerate() {
+                if let Command::InFormation(group, offset) = command {
+                    queued.insert(*group);
+
+                    let speed = speed.get(entity).map(|speed| speed.0 * crew_efficiency(&efficiency, entity)).unwrap_or(0.0);
+
+                    if i == 0 && speed > 0.0 {
+                        members.entry(*group).or_insert_with(Vec::new).push((entity, *offset, speed));
+                    }
+                }
+            }
+        }
+
+        for (group_entity, group) in (&entities, &mut groups).join() {
+            if !queued.contains(&group_entity) {
+                entities.delete(group_entity).unwrap();
+                continue;
+            }
+
+            let members = match members.get(&group_entity) {
+                Some(members) => members,
+                None => continue
+            };
+
+            let member_positions: Vec<(Vector3<f32>, Vector3<f32>)> = members.iter()
+                .filter_map(|(member, offset, _)| pos.get(*member).map(|pos| (pos.0, *offset)))
+                .collect();
+
+            if member_positions.is_empty() {
+                continue;
+            }
+
+            // Form up around the average position of the members, facing the target unless told otherwise
+            if !group.started {
+                let centre = member_positions.iter().fold(Vector3::zero(), |total, (pos, _)| total + *pos) / member_positions.len() as f32;
+
+                if group.facing.is_none() {
+                    group.facing = Some(formation_facing(centre, group.target));
+                }
+
+                group.started = true;
+                pos.insert(group_entity, Position(centre)).unwrap();
+            }
+
+            let centre = pos.get(group_entity).unwrap().0;
+
+            // Wait for stragglers, including ships that got pushed out of place by avoidance
+            let formed = member_positions.iter()
+                .all(|&(pos, offset)| pos.distance(group.slot(centre, offset)) < FORMATION_SLACK);
+
+            if !formed {
+                continue;
+            }
+
+            let slowest = members.iter()
+                .map(|(_, _, speed)| *speed)
+                .fold(std::f32::MAX, f32::min);
+
+            // Go around obstacles on the way, once `PathfindingSystem` has found a path
+            let next = paths.get(group_entity)
+                .and_then(|path| path.waypoints.first().cloned())
+                .unwrap_or(group.target);
+
+            if slowest < std::f32::MAX {
+                pos.insert(group_entity, Position(move_towards(centre, next, slowest))).unwrap();
+            }
+        }
+    }
+}
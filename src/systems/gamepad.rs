@@ -0,0 +1,92 @@
+use super::*;
+use pedot::Gui;
+
+// Stick movement smaller than this is ignored
+const DEAD_ZONE: f32 = 0.15;
+// Pixels per second at full tilt
+const CURSOR_SPEED: f32 = 800.0;
+// Radians per second at full tilt
+const ROTATION_SPEED: f32 = 2.0;
+
+fn dead_zone(value: f32) -> f32 {
+    if value.abs() < DEAD_ZONE {
+        0.0
+    } else {
+        value
+    }
+}
+
+// Move the virtual cursor with the left stick, returning where it ends up if it moved
+fn move_cursor(gamepad: &Gamepad, secs: f32, screen_dims: &ScreenDimensions, (x, y): (f32, f32)) -> Option<(f32, f32)> {
+    let (cursor_x, cursor_y) = (dead_zone(gamepad.left_stick.0), dead_zone(gamepad.left_stick.1));
+
+    if cursor_x == 0.0 && cursor_y == 0.0 {
+        return None;
+    }
+
+    let (width, height): (f32, f32) = screen_dims.0.into();
+
+    // Stick up is positive but screen coordinates go downwards
+    Some((
+        (x + cursor_x * CURSOR_SPEED * secs).max(0.0).min(width),
+        (y - cursor_y * CURSOR_SPEED * secs).max(0.0).min(height)
+    ))
+}
+
+// The left stick moves a virtual cursor and the right stick rotates the camera.
+// The buttons are handled along with the keys, in EventHandlerSystem
+pub struct GamepadSystem;
+
+impl<'a> System<'a> for GamepadSystem {
+    type SystemData = (
+        Read<'a, Gamepad>,
+        Read<'a, Secs>,
+        Read<'a, ScreenDimensions>,
+        Write<'a, Controls>,
+        Write<'a, Gui>,
+        Write<'a, Camera>
+    );
+
+    fn run(&mut self, (gamepad, secs, screen_dims, mut controls, mut gui, mut camera): Self::SystemData) {
+        if let Some((x, y)) = move_cursor(&gamepad, secs.0, &screen_dims, controls.mouse()) {
+            controls.set_mouse(x, y);
+            gui.set_mouse(x, y);
+        }
+
+        camera.rotate_longitude(dead_zone(gamepad.right_stick.0) * ROTATION_SPEED * secs.0);
+        camera.rotate_latitude(-dead_zone(gamepad.right_stick.1) * ROTATION_SPEED * secs.0);
+    }
+}
+
+// The game isn't updated while a menu is open, so this lets the gamepad move the cursor and click in the menus
+pub struct MenuGamepadSystem;
+
+impl<'a> System<'a> for MenuGamepadSystem {
+    type SystemData = (
+        Write<'a, Events>,
+        Write<'a, Gamepad>,
+        Read<'a, Secs>,
+        Read<'a, ScreenDimensions>,
+        Write<'a, Controls>,
+        Write<'a, Gui>
+    );
+
+    fn run(&mut self, (mut events, mut gamepad, secs, screen_dims, mut controls, mut gui): Self::SystemData) {
+        for event in events.drain(..) {
+            if let InputEvent::Gamepad(event) = event {
+                gamepad.handle(event);
+
+                if let GamepadEvent::Button(button, pressed) = event {
+                    if let Some(GamepadBinding::Mouse(button)) = gamepad_binding(button) {
+                        gui.handle_mouse(button, pressed);
+                    }
+                }
+            }
+        }
+
+        if let Some((x, y)) = move_cursor(&gamepad, secs.0, &screen_dims, gui.mouse()) {
+            controls.set_mouse(x, y);
+            gui.set_mouse(x, y);
+        }
+    }
+}
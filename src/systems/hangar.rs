@@ -0,0 +1,182 @@
+use super::*;
+use std::collections::HashMap;
+
+// Health per second
+const REPAIR_RATE: f32 = 2.0;
+// Materials per second
+const UNLOAD_RATE: f32 = 5.0;
+
+pub struct LaunchOrderSystem;
+
+impl<'a> System<'a> for LaunchOrderSystem {
+    type SystemData = (
+        Read<'a, Controls>,
+        ReadStorage<'a, Selectable>,
+        ReadStorage<'a, Hangar>,
+        ReadStorage<'a, Side>,
+        WriteStorage<'a, Commands>
+    );
+
+    fn run(&mut self, (controls, selectable, hangar, side, mut commands): Self::SystemData) {
+        if !controls.triggered(Action::Launch) {
+            return;
+        }
+
+        (&selectable, &hangar, &side, &mut commands).join()
+            .filter(|(selectable, _, side, _)| selectable.selected && **side == Side::Friendly)
+            .for_each(|(_, _, _, commands)| commands.order(controls.shift(), Command::Launch));
+    }
+}
+
+pub struct HangarSystem;
+
+impl<'a> System<'a> for HangarSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Paused>,
+        Write<'a, Log>,
+        WriteStorage<'a, Commands>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, Docked>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, SeekPosition>,
+        WriteStorage<'a, Selectable>,
+        WriteStorage<'a, AttackTarget>,
+        ReadStorage<'a, Hangar>,
+        ReadStorage<'a, Size>
+    );
+
+    fn run(&mut self, (entities, paused, mut log, mut commands, mut parent, mut docked, mut pos, mut vel, mut seek, mut selectable, mut target, hangar, size): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let mut occupied = HashMap::new();
+
+        (&docked, &parent).join()
+            .for_each(|(_, parent)| *occupied.entry(parent.0).or_insert(0) += 1);
+
+        for (entity, commands) in (&entities, &mut commands).join() {
+            match commands.first().cloned() {
+                Some(Command::GoToAnd(carrier, Interaction::Dock)) => {
+                    let capacity = match hangar.get(carrier) {
+                        Some(hangar) if entities.is_alive(carrier) && docked.get(entity).is_none() => hangar.capacity,
+                        _ => {
+                            commands.remove(0);
+                            continue;
+                        }
+                    };
+
+                    let distance = size.get(entity).map(|size| size.0).unwrap_or(0.0) + size.get(carrier).map(|size| size.0).unwrap_or(0.0);
+
+                    let in_range = match (pos.get(entity), pos.get(carrier)) {
+                        (Some(pos), Some(carrier_pos)) => pos.0.distance(carrier_pos.0) - CLOSE_ENOUGH_DISTANCE < distance,
+                        _ => false
+                    };
+
+                    if !in_range {
+                        continue;
+                    }
+
+                    let occupied = occupied.entry(carrier).or_insert(0);
+
+                    if *occupied >= capacity {
+                        log.append("The hangar is full".into());
+                    } else {
+                        *occupied += 1;
+
+                        parent.insert(entity, Parent(carrier)).unwrap();
+                        docked.insert(entity, Docked).unwrap();
+                        seek.remove(entity);
+                        target.remove(entity);
+
+                        if let Some(vel) = vel.get_mut(entity) {
+                            vel.0 = Vector3::zero();
+                        }
+
+                        if let Some(selectable) = selectable.get_mut(entity) {
+                            selectable.selected = false;
+                        }
+                    }
+
+                    commands.remove(0);
+                },
+                Some(Command::Launch) => {
+                    let (carrier_pos, carrier_size) = match (pos.get(entity), size.get(entity)) {
+                        (Some(pos), Some(size)) => (pos.0, size.0),
+                        _ => {
+                            commands.remove(0);
+                            continue;
+                        }
+                    };
+
+                    let launching: Vec<Entity> = (&entities, &docked, &parent).join()
+                        .filter(|(_, _, parent)| parent.0 == entity)
+                        .map(|(ship, _, _)| ship)
+                        .collect();
+
+                    let middle = (launching.len() as f32 - 1.0) / 2.0;
+
+                    for (i, ship) in launching.into_iter().enumerate() {
+                        docked.remove(ship);
+                        parent.remove(ship);
+
+                        // Spread the ships out in a line underneath the carrier
+                        let offset = Vector3::new((i as f32 - middle) * 2.0, -carrier_size * 2.0, 0.0);
+                        pos.insert(ship, Position(carrier_pos + offset)).unwrap();
+                    }
+
+                    commands.remove(0);
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+// Docked ships ride along inside their carrier while getting repaired and unloading their cargo
+pub struct DockedSystem;
+
+impl<'a> System<'a> for DockedSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Secs>,
+        Read<'a, Paused>,
+        Read<'a, ShipDefinitions>,
+        ReadStorage<'a, Docked>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, ShipType>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, components::Rotation>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Materials>
+    );
+
+    fn run(&mut self, (entities, secs, paused, definitions, docked, parent, tag, mut pos, mut rot, mut vel, mut health, mut materials): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        for (entity, _, parent) in (&entities, &docked, &parent).join() {
+            if let Some(carrier_pos) = pos.get(parent.0).cloned() {
+                pos.insert(entity, carrier_pos).unwrap();
+            }
+
+            if let Some(carrier_rot) = rot.get(parent.0).cloned() {
+                rot.insert(entity, carrier_rot).unwrap();
+            }
+
+            if let Some(vel) = vel.get_mut(entity) {
+                vel.0 = Vector3::zero();
+            }
+
+            if let (Some(health), Some(definition)) = (health.get_mut(entity), tag.get(entity).and_then(|tag| definitions.get(tag))) {
+                health.0 = move_towards(health.0, definition.max_health(), REPAIR_RATE * secs.0);
+            }
+
+            transfer_between_same(&mut materials, entity, parent.0, UNLOAD_RATE * secs.0);
+        }
+    }
+}
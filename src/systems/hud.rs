@@ -0,0 +1,145 @@
+use super::*;
+use crate::ui::*;
+use pedot::{Gui, List, Widget};
+
+pub struct HudSystem;
+
+impl<'a> System<'a> for HudSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, Hud>,
+        Write<'a, Gui>,
+        Write<'a, Controls>,
+        Read<'a, InputMap>,
+        Read<'a, ShipDefinitions>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, Health>,
+        ReadStorage<'a, Materials>,
+        ReadStorage<'a, Food>,
+        ReadStorage<'a, MineableMaterials>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, MinimumCrew>,
+        ReadStorage<'a, CrewEfficiency>,
+        WriteStorage<'a, Selectable>
+    );
+
+    fn run(&mut self, (entities, mut hud, mut gui, mut controls, input_map, definitions, tag, side, health, materials, food, mineable, occupation, parent, minimum_crew, efficiency, mut selectable): Self::SystemData) {
+        // Resource bars
+        let fleet: Vec<Entity> = (&entities, &tag, &side).join()
+            .filter(|(_, _, side)| **side == Side::Friendly)
+            .map(|(entity, _, _)| entity)
+            .collect();
+
+        let (materials_amount, materials_capacity) = fleet.iter()
+            .filter_map(|entity| materials.get(*entity))
+            .fold((0.0, 0.0), |(amount, capacity), materials| (amount + materials.amount(), capacity + materials.capacity()));
+
+        let (food_amount, food_capacity) = fleet.iter()
+            .filter_map(|entity| food.get(*entity))
+            .fold((0.0, 0.0), |(amount, capacity), food| (amount + food.amount(), capacity + food.capacity()));
+
+        hud.resources.widgets = vec![
+            Widget::progress_bar(format!("Materials: {:.0}/{:.0}", materials_amount, materials_capacity), fraction(materials_amount, materials_capacity), MATERIALS_COLOUR)
+                .with_tooltip("Mined from asteroids and salvaged from wrecks"),
+            Widget::progress_bar(format!("Food: {:.0}/{:.0}", food_amount, food_capacity), fraction(food_amount, food_capacity), FOOD_COLOUR)
+                .with_tooltip("Eaten by everyone in the fleet"),
+            Widget::label(format!("Population: {}", occupation.join().count()))
+        ];
+
+        // Selection panel
+        let selected: Vec<Entity> = (&entities, &selectable).join()
+            .filter(|(_, selectable)| selectable.selected)
+            .map(|(entity, _)| entity)
+            .collect();
+
+        let (types, _) = summarize(selected.iter().filter_map(|entity| tag.get(*entity)).cloned());
+
+        let mut widgets = Vec::new();
+
+        if let [entity] = selected[..] {
+            if let (Some(definition), Some(health)) = (tag.get(entity).and_then(|tag| definitions.get(tag)), health.get(entity)) {
+                let max_health = definition.max_health();
+                widgets.push(Widget::progress_bar(format!("Health: {:.0}/{:.0}", health.0, max_health), fraction(health.0, max_health), HEALTH_COLOUR));
+            }
+
+            let people = (&occupation, &parent).join()
+                .filter(|(_, parent)| parent.0 == entity)
+                .map(|(occupation, _)| occupation);
+
+            let (people, total) = summarize(people);
+
+            if let (Some(minimum_crew), Some(efficiency)) = (minimum_crew.get(entity), efficiency.get(entity)) {
+                let crew: u64 = people.iter()
+                    .filter(|(occupation, _)| occupation.is_crew())
+                    .map(|(_, num)| num)
+                    .sum();
+
+                widgets.push(
+                    Widget::progress_bar(format!("Crew: {}/{} ({:.0}% efficiency)", crew, minimum_crew.0, efficiency.0 * 100.0), efficiency.0, CREW_COLOUR)
+                        .with_tooltip("Ships without enough crew move, mine and reload slower")
+                );
+            }
+
+            if let Some(materials) = materials.get(entity) {
+                widgets.push(Widget::label(format!("Materials: {}", materials.0)));
+            }
+
+            if let Some(food) = food.get(entity) {
+                widgets.push(Widget::label(format!("Food: {}", food.0)));
+            }
+
+            if let Some(mineable) = mineable.get(entity) {
+                widgets.push(Widget::label(format!("Mineable Materials: {}", mineable.0)));
+            }
+
+            if total > 0 {
+                let people: Vec<_> = people.iter().map(|(occupation, num)| format!("{} {:?}", num, occupation)).collect();
+                widgets.push(Widget::label(format!("Population: {}", total)).with_tooltip(people.join(", ")));
+            }
+        }
+
+        if !types.is_empty() {
+            let entries = types.iter().map(|(tag, count)| format!("{} x{}", tag, count)).collect();
+            widgets.push(Widget::list(List::new(entries), HudAction::SelectType).with_tooltip("Click to select only ships of this type"));
+        }
+
+        hud.selected_types = types.into_iter().map(|(tag, _)| tag).collect();
+        hud.selection.widgets = widgets;
+
+        // Command card
+        let friendly_selected = selected.iter().any(|entity| side.get(*entity) == Some(&Side::Friendly));
+        hud.commands.widgets = if friendly_selected { Hud::command_buttons(&input_map) } else { Vec::new() };
+
+        for panel in hud.panels_mut().iter_mut() {
+            panel.layout(&gui);
+        }
+
+        let (mouse_x, mouse_y) = gui.mouse();
+        hud.mouse_over = hud.panels().iter().any(|panel| panel.contains(mouse_x, mouse_y));
+
+        let actions: Vec<HudAction> = hud.panels_mut().iter_mut()
+            .filter_map(|panel| panel.interact(&gui))
+            .collect();
+
+        for action in actions {
+            match action {
+                HudAction::Trigger(action) => controls.trigger(action),
+                HudAction::SelectType(index) => {
+                    let selected_type = &hud.selected_types[index];
+
+                    for (entity, selectable) in (&entities, &mut selectable).join() {
+                        selectable.selected &= tag.get(entity) == Some(selected_type);
+                    }
+                }
+            }
+        }
+
+        gui.clear();
+    }
+}
+
+fn fraction(amount: f32, capacity: f32) -> f32 {
+    if capacity > 0.0 { amount / capacity } else { 0.0 }
+}
@@ -6,11 +6,18 @@ use crate::ships::*;
 use cgmath::{Vector3, MetricSpace, Zero, Quaternion};
 use crate::util::*;
 use crate::controls::Controls;
+use crate::input::*;
 use crate::resources::*;
 use crate::star_system::*;
-use ncollide3d::query::RayCast;
+use crate::encounters::{Encounters, EncounterState};
+use ncollide3d::query::{Ray, RayCast};
 use ncollide3d::shape::Plane;
 use nalgebra::Unit;
+use specs::storage::MaskedStorage;
+use std::ops::Deref;
+use std::collections::HashSet;
+use specs::saveload::MarkedBuilder;
+use crate::Marker;
 
 mod rendering;
 mod storage;
@@ -18,12 +25,38 @@ mod steering;
 mod saving;
 mod combat;
 mod setup;
+mod crew;
+mod boarding;
+mod hangar;
+mod orders;
+mod selection;
+mod gamepad;
+mod formations;
+mod pathfinding;
+mod environment;
+mod stations;
+mod encounters;
+mod particles;
+mod hud;
 
 pub use self::rendering::*;
 pub use self::steering::*;
 pub use self::saving::*;
 pub use self::combat::*;
 pub use self::setup::*;
+pub use self::crew::*;
+pub use self::boarding::*;
+pub use self::hangar::*;
+pub use self::orders::*;
+pub use self::selection::*;
+pub use self::gamepad::*;
+pub use self::pathfinding::*;
+pub use self::environment::*;
+pub use self::stations::*;
+pub use self::encounters::*;
+pub use self::formations::*;
+pub use self::particles::*;
+pub use self::hud::*;
 use self::storage::*;
 
 pub struct SpinSystem;
@@ -53,6 +86,29 @@ impl<'a> System<'a> for SpinSystem {
     }
 }
 
+pub struct AnimationSystem;
+
+impl<'a> System<'a> for AnimationSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Secs>,
+        Read<'a, Paused>,
+        WriteStorage<'a, Animated>,
+        WriteStorage<'a, Image>
+    );
+
+    fn run(&mut self, (entities, secs, paused, mut animated, mut images): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        for (entity, animated) in (&entities, &mut animated).join() {
+            animated.time += secs.0;
+            images.insert(entity, animated.animation.frame(animated.time)).unwrap();
+        }
+    }
+}
+
 pub struct DragSelectSystem;
 
 impl<'a> System<'a> for DragSelectSystem {
@@ -60,22 +116,28 @@ impl<'a> System<'a> for DragSelectSystem {
         Read<'a, Controls>,
         Read<'a, Camera>,
         Read<'a, ScreenDimensions>,
+        Read<'a, crate::ui::Hud>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Docked>,
         WriteStorage<'a, Selectable>
     );
 
-    fn run(&mut self, (controls, camera, screen_dims, pos, mut selectable): Self::SystemData) {
+    fn run(&mut self, (controls, camera, screen_dims, hud, pos, docked, mut selectable): Self::SystemData) {
+        if hud.mouse_over {
+            return;
+        }
+
         if let Some((left, top, right, bottom)) = controls.left_drag_rect() {
-            for (pos, selectable) in (&pos, &mut selectable).join() {
+            for (pos, selectable, _) in (&pos, &mut selectable, !&docked).join() {
                 if let Some(pos) = camera.screen_position(pos.0, screen_dims.0.into(), false) {
                     let selected = pos.x >= left && pos.x <= right && pos.y >= top && pos.y <= bottom;
                     
-                    if !controls.shift {
+                    if !controls.shift() {
                         selectable.selected = selected;
                     } else if selected {
                         selectable.selected = !selectable.selected;
                     }
-                } else if !controls.shift {
+                } else if !controls.shift() {
                     selectable.selected = false;
                 }
             }
@@ -89,6 +151,7 @@ impl<'a> System<'a> for ShipMovementSystem {
     type SystemData = (
         Entities<'a>,
         Read<'a, Paused>,
+        Read<'a, Secs>,
         WriteStorage<'a, Commands>,
         WriteStorage<'a, Materials>,
         WriteStorage<'a, MineableMaterials>,
@@ -97,34 +160,46 @@ impl<'a> System<'a> for ShipMovementSystem {
         ReadStorage<'a, Position>,
         ReadStorage<'a, Size>,
         ReadStorage<'a, DrillSpeed>,
-        ReadStorage<'a, CanAttack>
+        ReadStorage<'a, CanAttack>,
+        ReadStorage<'a, CrewEfficiency>,
+        ReadStorage<'a, Docked>,
+        ReadStorage<'a, FormationGroup>,
+        ReadStorage<'a, Nebula>
     );
 
-    fn run(&mut self, (entities, paused, mut commands, mut materials, mut mineable, mut seek, mut attack_target, pos, size, drill_speed, attack): Self::SystemData) {
+    fn run(&mut self, (entities, paused, secs, mut commands, mut materials, mut mineable, mut seek, mut attack_target, pos, size, drill_speed, attack, efficiency, docked, groups, nebulae): Self::SystemData) {
         if paused.0 {
             return;
         }
 
-        for (entity, commands) in (&entities, &mut commands).join() {
+        let nebulae = nebula_clouds(&pos, &nebulae);
+
+        // Docked ships keep their orders until they're launched
+        for (entity, commands, _) in (&entities, &mut commands, !&docked).join() {
             let last = commands.len() == 1;
 
-            let finished = commands.first()
-                .map(|command| handle_command(command, entity, &mut materials, &mut mineable, &size, &drill_speed, &pos, &mut seek, &mut attack_target, last, &attack).unwrap_or(true))
-                .unwrap_or(false);
-            
-            if finished {
-                commands.remove(0);
+            let result = commands.first_mut()
+                .map(|command| handle_command(command, entity, &mut materials, &mut mineable, &size, &drill_speed, &pos, &mut seek, &mut attack_target, last, &attack, &efficiency, &groups, &nebulae, secs.0));
+
+            match result {
+                Some(Some(true)) => commands.finish_current(),
+                // The command can't be carried out any more (e.g. the target was destroyed), so don't repeat it
+                Some(None) => {
+                    commands.remove(0);
+                },
+                _ => {}
             }
         }
     }
 }
 
 fn handle_command(
-    command: &Command,
+    command: &mut Command,
     entity: Entity,
     materials: &mut WriteStorage<Materials>, mineable_materials: &mut WriteStorage<MineableMaterials>,
     size: &ReadStorage<Size>, drill_speed: &ReadStorage<DrillSpeed>, pos: &ReadStorage<Position>,
-    seek: &mut WriteStorage<SeekPosition>, attack_target: &mut WriteStorage<AttackTarget>, last: bool, attack: &ReadStorage<CanAttack>
+    seek: &mut WriteStorage<SeekPosition>, attack_target: &mut WriteStorage<AttackTarget>, last: bool, attack: &ReadStorage<CanAttack>,
+    efficiency: &ReadStorage<CrewEfficiency>, groups: &ReadStorage<FormationGroup>, nebulae: &[(Vector3<f32>, f32)], secs: f32
 ) -> Option<bool> {
     
     let entity_position = pos.get(entity)?.0;
@@ -144,26 +219,103 @@ fn handle_command(
         Command::GoToAnd(target, interaction) => {
             let target_position = pos.get(*target)?.0;
 
-            let distance = if *interaction == Interaction::Attack {
-                attack_target.insert(entity, AttackTarget {entity: *target, kamikaze: false}).unwrap();
-                
-                attack.get(entity)?.range - CLOSE_ENOUGH_DISTANCE * 2.0
-            } else {
-                size.get(*target)?.0 + size.get(entity)?.0
+            let distance = match interaction {
+                // Targets hidden in a nebula have to be chased down before they can be shot at
+                Interaction::Attack => {
+                    let range = attack.get(entity)?.range;
+
+                    if spotted(target_position, range, std::iter::once(entity_position), nebulae) {
+                        attack_target.insert(entity, AttackTarget {entity: *target, kamikaze: false}).unwrap();
+                    }
+
+                    sensor_range(target_position, range, nebulae) - CLOSE_ENOUGH_DISTANCE * 2.0
+                },
+                Interaction::Board => SHUTTLE_RANGE - CLOSE_ENOUGH_DISTANCE * 2.0,
+                _ => size.get(*target)?.0 + size.get(entity)?.0
             };
 
             if entity_position.distance(target_position) - CLOSE_ENOUGH_DISTANCE < distance {
                 match interaction {
                     Interaction::Follow => Some(false),
                     Interaction::Mine => {
-                        transfer_between_different(mineable_materials, materials, *target, entity, drill_speed.get(entity).unwrap().0)
+                        let speed = drill_speed.get(entity).unwrap().0 * crew_efficiency(efficiency, entity);
+
+                        // an uncrewed miner just sits there instead of finishing the order
+                        if speed == 0.0 {
+                            return Some(false);
+                        }
+
+                        transfer_between_different(mineable_materials, materials, *target, entity, speed)
                     },
                     Interaction::Attack => Some(false),
+                    // The marines are sent across by `LaunchBoardingSystem`
+                    Interaction::Board => Some(false),
+                    // Docking is handled by `HangarSystem`
+                    Interaction::Dock => Some(false),
+                    // These are handled by `SalvageSystem` and `StationSystem`
+                    Interaction::Salvage | Interaction::Trade | Interaction::Repair => Some(false)
                 }
             } else {
                 seek.insert(entity, SeekPosition::within_distance(target_position, distance, last)).unwrap();
                 Some(false)
             }
+        },
+        Command::Launch => Some(false),
+        Command::Patrol(waypoints, next) => {
+            let mut waypoint = *waypoints.get(*next)?;
+
+            if close_enough(entity_position, waypoint) {
+                *next = (*next + 1) % waypoints.len();
+                waypoint = waypoints[*next];
+            }
+
+            // Never the last point, so that the ship doesn't stop at each waypoint
+            seek.insert(entity, SeekPosition::to_point(waypoint, false)).unwrap();
+            Some(false)
+        },
+        Command::Guard(target) => {
+            let target_position = pos.get(*target)?.0;
+            let distance = size.get(*target)?.0 + size.get(entity)?.0 + GUARD_DISTANCE;
+
+            // Enemies near the target are picked out by `GuardSystem`
+            if entity_position.distance(target_position) - CLOSE_ENOUGH_DISTANCE >= distance {
+                seek.insert(entity, SeekPosition::within_distance(target_position, distance, last)).unwrap();
+            }
+
+            Some(false)
+        },
+        Command::HoldPosition(point) => {
+            let point = *point.get_or_insert(entity_position);
+            seek.insert(entity, SeekPosition::to_point(point, true)).unwrap();
+            Some(false)
+        },
+        Command::Wait(left, _) => {
+            seek.insert(entity, SeekPosition::to_point(entity_position, true)).unwrap();
+            *left -= secs;
+            Some(*left <= 0.0)
+        },
+        Command::Repeat => Some(true),
+        Command::InFormation(group_entity, offset) => {
+            let group = groups.get(*group_entity)?;
+
+            // Wait for the rest of the group
+            let centre = match pos.get(*group_entity) {
+                Some(centre) if group.started => centre.0,
+                _ => {
+                    seek.insert(entity, SeekPosition::to_point(entity_position, true)).unwrap();
+                    return Some(false);
+                }
+            };
+
+            let slot = group.slot(centre, *offset);
+            let arrived = close_enough(centre, group.target);
+
+            if arrived && close_enough(entity_position, slot) {
+                return Some(true);
+            }
+
+            seek.insert(entity, SeekPosition::to_point(slot, arrived && last)).unwrap();
+            Some(false)
         }
     }
 }
@@ -176,26 +328,87 @@ impl<'a> System<'a> for RightClickSystem {
         Read<'a, Controls>,
         Read<'a, Formation>,
         Read<'a, AveragePosition>,
-        WriteStorage<'a, Commands>
+        WriteStorage<'a, Commands>,
+        ReadStorage<'a, Position>,
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        Read<'a, crate::ui::Hud>,
+        ReadStorage<'a, Size>
     );
 
-    fn run(&mut self, (order, controls, formation, avg_pos, mut commands): Self::SystemData) {
-        if controls.right_clicked() {
+    fn run(&mut self, (order, controls, formation, avg_pos, mut commands, pos, entities, lazy, hud, size): Self::SystemData) {
+        // Clicks on the HUD are for it, not the ships under it
+        if hud.mouse_over {
+            return;
+        }
+
+        // Holding control puts the command in front of the current one instead
+        let give = |commands: &mut Commands, command: Command| if controls.control() {
+            commands.insert_command(0, command);
+        } else {
+            commands.order(controls.shift(), command);
+        };
+
+        if controls.right_clicked() || (controls.right_dragged() && order.facing.is_some()) {
             if let Some(ref command) = order.command {
+                let sizes: Vec<f32> = order.to_move.iter()
+                    .map(|entity| size.get(*entity).map(|size| size.0).unwrap_or(1.0))
+                    .collect();
+
                 match command {
-                    Command::GoToAnd(entity, interaction) => {
+                    Command::GoToAnd(_, _) | Command::Guard(_) => {
                         order.to_move.iter()
-                            .for_each(|e| commands.get_mut(*e).unwrap().order(controls.shift, Command::GoToAnd(*entity, *interaction)));
+                            .for_each(|e| give(commands.get_mut(*e).unwrap(), command.clone()));
+                    },
+                    Command::MoveTo(target) if formation.keeps_shape() && order.to_move.len() > 1 && !controls.held(Action::Patrol) => {
+                        let group = lazy.create_entity(&entities)
+                            .with(FormationGroup::new(*target, order.facing))
+                            .marked::<Marker>()
+                            .build();
+
+                        let offsets = formation.slots(&sizes);
+
+                        order.to_move.iter()
+                            .zip(offsets)
+                            .for_each(|(entity, offset)| give(commands.get_mut(*entity).unwrap(), Command::InFormation(group, offset)));
                     },
                     Command::MoveTo(target) => {
                         if let Some(avg) = avg_pos.0 {
-                            let positions = formation.arrange(order.to_move.len(), avg, *target, 4.0);
-
-                            order.to_move.iter()
-                                .zip(positions)
-                                .for_each(|(entity, position)| commands.get_mut(*entity).unwrap().order(controls.shift, Command::MoveTo(position)));
+                            let facing = order.facing.unwrap_or_else(|| formation_facing(avg, *target));
+                            let positions = formation.arrange(&sizes, *target, facing);
+
+                            for (entity, position) in order.to_move.iter().zip(positions) {
+                                let commands = commands.get_mut(*entity).unwrap();
+
+                                if !controls.held(Action::Patrol) {
+                                    give(commands, Command::MoveTo(position));
+                                    continue;
+                                }
+
+                                // Shift-clicking adds waypoints onto the end of a queued patrol
+                                if controls.shift() {
+                                    if let Some(Command::Patrol(waypoints, _)) = commands.last_mut() {
+                                        waypoints.push(position);
+                                        continue;
+                                    }
+                                }
+
+                                let start = if controls.shift() {
+                                    commands.iter().rev().filter_map(|command| command.point(&pos)).next()
+                                } else {
+                                    None
+                                };
+
+                                let start = match start.or_else(|| pos.get(*entity).map(|pos| pos.0)) {
+                                    Some(start) => start,
+                                    None => continue
+                                };
+
+                                give(commands, Command::Patrol(vec![start, position], 1));
+                            }
                         }
-                    }
+                    },
+                    _ => {}
                 }
             }
         }
@@ -215,22 +428,22 @@ impl<'a> System<'a> for StepCameraSystem {
     fn run(&mut self, (mut camera, controls, pos, mut selectable): Self::SystemData) {
         let mut clear = false;
         
-        if controls.left {
+        if controls.held(Action::CameraLeft) {
             camera.move_sideways(-0.5);
             clear = true;
         }
 
-        if controls.right {
+        if controls.held(Action::CameraRight) {
             camera.move_sideways(0.5);
             clear = true;
         }
 
-        if controls.forwards {
+        if controls.held(Action::CameraForwards) {
             camera.move_forwards(0.5);
             clear = true;
         }
 
-        if controls.back {
+        if controls.held(Action::CameraBack) {
             camera.move_forwards(-0.5);
             clear = true;
         }
@@ -258,12 +471,13 @@ impl<'a> System<'a> for LeftClickSystem {
     type SystemData = (
         Read<'a, Controls>,
         Read<'a, EntityUnderMouse>,
+        Read<'a, crate::ui::Hud>,
         WriteStorage<'a, Selectable>
     );
 
-    fn run(&mut self, (controls, entity, mut selectable): Self::SystemData) {
-        if controls.left_clicked() {
-            if !controls.shift {
+    fn run(&mut self, (controls, entity, hud, mut selectable): Self::SystemData) {
+        if controls.left_clicked() && !hud.mouse_over {
+            if !controls.shift() {
                 (&mut selectable).join().for_each(|selectable| selectable.selected = false);
             }
 
@@ -289,16 +503,74 @@ impl<'a> System<'a> for RightClickInteractionSystem {
         ReadStorage<'a, Side>,
         ReadStorage<'a, Selectable>,
         ReadStorage<'a, DrillSpeed>,
-        ReadStorage<'a, Commands>
+        ReadStorage<'a, Commands>,
+        Read<'a, Controls>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, Boarder>,
+        ReadStorage<'a, Hangar>,
+        ReadStorage<'a, ShipType>,
+        Read<'a, Camera>,
+        Read<'a, ScreenDimensions>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Nebula>,
+        ReadStorage<'a, Salvage>,
+        ReadStorage<'a, Station>,
+        Read<'a, ShipDefinitions>
     );
 
-    fn run(&mut self, (entities, mut order, entity, plane, ray, mineable, side, selectable, drill, commands): Self::SystemData) {
+    fn run(&mut self, (entities, mut order, entity, plane, ray, mineable, side, selectable, drill, commands, controls, occupation, parent, boarder, hangar, tag, camera, screen_dims, pos, nebulae, salvage, station, definitions): Self::SystemData) {
         let ordering = (&entities, &selectable, &side, &commands).join()
             .filter(|(_, selectable, side, _)| selectable.selected && **side == Side::Friendly)
             .map(|(entity, _, _, _)| entity);
 
-        if let Some((entity, _)) = entity.0 {
-            let interaction = if side.get(entity) == Some(&Side::Enemy) {
+        order.facing = None;
+
+        // Move to where the drag started, facing towards the mouse
+        if let (true, Some(start)) = (controls.held(Action::SetFacing), controls.right_drag_start()) {
+            order.to_move = ordering.collect();
+
+            let start_ray = camera.ray(start, screen_dims.0.into());
+            let target = movement_plane_point(&start_ray, plane.0);
+
+            order.facing = target
+                .and_then(|target| movement_plane_point(&ray.0, plane.0).map(|point| (target, point)))
+                .filter(|&(target, point)| target.distance(point) > CLOSE_ENOUGH_DISTANCE)
+                .map(|(target, point)| formation_facing(target, point));
+
+            order.command = target.map(Command::MoveTo);
+            return;
+        }
+
+        let nebulae = nebula_clouds(&pos, &nebulae);
+
+        let fleet: Vec<Vector3<f32>> = (&pos, &side).join()
+            .filter(|(_, side)| **side == Side::Friendly)
+            .map(|(pos, _)| pos.0)
+            .collect();
+
+        // Enemies hidden in a nebula can't be ordered around until the fleet gets close enough to spot them
+        let hidden = |entity: Entity| side.get(entity) == Some(&Side::Enemy) && pos.get(entity)
+            .map(|pos| !spotted(pos.0, std::f32::MAX, fleet.iter().cloned(), &nebulae))
+            .unwrap_or(false);
+
+        if let Some((entity, _)) = entity.0.filter(|(entity, _)| !hidden(*entity)) {
+            if side.get(entity) == Some(&Side::Friendly) && controls.held(Action::Guard) {
+                order.to_move = ordering.filter(|ordered| *ordered != entity).collect();
+                order.command = Some(Command::Guard(entity));
+                return;
+            }
+
+            let interaction = if side.get(entity) == Some(&Side::Enemy) && controls.held(Action::Board) {
+                let carrying_marines: HashSet<Entity> = (&occupation, &parent, !&boarder).join()
+                    .filter(|(occupation, _, _)| **occupation == Occupation::Marine)
+                    .map(|(_, parent, _)| parent.0)
+                    .collect();
+
+                order.to_move = ordering.filter(|entity| carrying_marines.contains(entity)).collect();
+
+                Interaction::Board
+            } else if side.get(entity) == Some(&Side::Enemy) {
                 order.to_move = ordering.collect();
 
                 Interaction::Attack
@@ -306,6 +578,29 @@ impl<'a> System<'a> for RightClickInteractionSystem {
                 order.to_move = ordering.filter(|entity| drill.get(*entity).is_some()).collect();
 
                 Interaction::Mine
+            } else if salvage.get(entity).filter(|salvage| !salvage.is_empty()).is_some() {
+                order.to_move = ordering.collect();
+
+                Interaction::Salvage
+            } else if station.get(entity).is_some() {
+                order.to_move = ordering.collect();
+
+                if controls.held(Action::Trade) {
+                    Interaction::Trade
+                } else {
+                    Interaction::Repair
+                }
+            } else if hangar.get(entity).is_some() {
+                let dockable: Vec<Entity> = ordering
+                    .filter(|ordered| *ordered != entity && tag.get(*ordered).and_then(|tag| definitions.get(tag)).map(|definition| definition.can_dock).unwrap_or(false))
+                    .collect();
+
+                if dockable.is_empty() {
+                    Interaction::Follow
+                } else {
+                    order.to_move = dockable;
+                    Interaction::Dock
+                }
             } else {
                 Interaction::Follow
             };
@@ -315,12 +610,7 @@ impl<'a> System<'a> for RightClickInteractionSystem {
             order.to_move = ordering.collect();
 
             order.command = if !order.to_move.is_empty() {
-                let iso = make_iso(Vector3::new(0.0, plane.0, 0.0), Quaternion::zero());
-
-                Plane::new(Unit::new_normalize(vector_to_na_vector(UP)))
-                    .toi_with_ray(&iso, &ray.0, BACKGROUND_DISTANCE, true)
-                    .map(|toi| ray.0.origin + ray.0.dir * toi)
-                    .map(|point| Command::MoveTo(Vector3::new(point.x, point.y, point.z)))
+                movement_plane_point(&ray.0, plane.0).map(Command::MoveTo)
             } else {
                 None
             };
@@ -328,6 +618,16 @@ impl<'a> System<'a> for RightClickInteractionSystem {
     }
 }
 
+// Where a ray hits the plane of movement
+fn movement_plane_point(ray: &Ray<f32>, plane: f32) -> Option<Vector3<f32>> {
+    let iso = make_iso(Vector3::new(0.0, plane, 0.0), Quaternion::zero());
+
+    Plane::new(Unit::new_normalize(vector_to_na_vector(UP)))
+        .toi_with_ray(&iso, ray, BACKGROUND_DISTANCE, true)
+        .map(|toi| ray.origin + ray.dir * toi)
+        .map(|point| Vector3::new(point.x, point.y, point.z))
+}
+
 // todo:recyc
 
 pub struct MiddleClickSystem;
@@ -371,7 +671,7 @@ impl<'a> System<'a> for TickTimedEntities {
     }
 }
 
-fn delete_entity(entity: Entity, entities: &Entities, parents: &ReadStorage<Parent>) {
+fn delete_entity<D: Deref<Target = MaskedStorage<Parent>>>(entity: Entity, entities: &Entities, parents: &Storage<Parent, D>) {
     entities.delete(entity).unwrap();
 
     (entities, parents).join()
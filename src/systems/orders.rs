@@ -0,0 +1,110 @@
+use super::*;
+
+// How far away from the guarded ship guards stay
+pub const GUARD_DISTANCE: f32 = 10.0;
+// How long the wait command lasts
+const WAIT_TIME: f32 = 5.0;
+
+// Edit the command queues of the selected ships with the keyboard
+pub struct QueueEditSystem;
+
+impl<'a> System<'a> for QueueEditSystem {
+    type SystemData = (
+        Read<'a, Controls>,
+        ReadStorage<'a, Selectable>,
+        ReadStorage<'a, Side>,
+        WriteStorage<'a, Commands>
+    );
+
+    fn run(&mut self, (controls, selectable, side, mut commands): Self::SystemData) {
+        let selected = (&selectable, &side, &mut commands).join()
+            .filter(|(selectable, side, _)| selectable.selected && **side == Side::Friendly)
+            .map(|(_, _, commands)| commands);
+
+        for commands in selected {
+            if controls.triggered(Action::HoldPosition) {
+                commands.order(controls.shift(), Command::HoldPosition(None));
+            }
+
+            if controls.triggered(Action::Wait) {
+                commands.order(controls.shift(), Command::Wait(WAIT_TIME, WAIT_TIME));
+            }
+
+            // Only one repeat makes sense in a queue
+            if controls.triggered(Action::Repeat) && !commands.iter().any(Command::is_repeat) {
+                commands.push(Command::Repeat);
+            }
+
+            if controls.triggered(Action::RemoveLast) {
+                let last = commands.len().saturating_sub(1);
+                commands.remove_command(last);
+            }
+
+            // Move the current command to the back of the queue, staying inside the loop if there's a `Repeat` at the end
+            if controls.triggered(Action::Cycle) {
+                let mut last = commands.len().saturating_sub(1);
+
+                if commands.last().map(Command::is_repeat).unwrap_or(false) {
+                    last = last.saturating_sub(1);
+                }
+
+                commands.move_command(0, last);
+            }
+        }
+    }
+}
+
+// Ships that are guarding something or holding their position attack the closest enemy in range
+pub struct GuardSystem;
+
+impl<'a> System<'a> for GuardSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Paused>,
+        ReadStorage<'a, Commands>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, CanAttack>,
+        ReadStorage<'a, Nebula>,
+        ReadStorage<'a, Docked>,
+        WriteStorage<'a, AttackTarget>
+    );
+
+    fn run(&mut self, (entities, paused, commands, pos, side, tag, attack, nebulae, docked, mut attack_target): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let nebulae = nebula_clouds(&pos, &nebulae);
+
+        let ships: Vec<(Entity, Vector3<f32>, &Side)> = (&entities, &pos, &side, &tag, !&docked).join()
+            .map(|(entity, pos, side, _, _)| (entity, pos.0, side))
+            .collect();
+
+        for (entity, commands, entity_side, attack, _) in (&entities, &commands, &side, &attack, !&docked).join() {
+            let centre = match commands.first() {
+                Some(Command::Guard(target)) => pos.get(*target).map(|pos| pos.0),
+                Some(Command::HoldPosition(point)) => point.or_else(|| pos.get(entity).map(|pos| pos.0)),
+                _ => continue
+            };
+
+            let centre = match centre {
+                Some(centre) => centre,
+                None => continue
+            };
+
+            // Enemies hidden in a nebula have to be up close to be spotted, and derelicts aren't worth shooting at
+            let closest = ships.iter()
+                .filter(|(_, _, side)| *side != entity_side && **side != Side::Neutral)
+                .map(|(ship, ship_pos, _)| (*ship, centre.distance(*ship_pos), sensor_range(*ship_pos, attack.range, &nebulae)))
+                .filter(|(_, distance, range)| distance < range)
+                .map(|(ship, distance, _)| (ship, distance))
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+
+            if let Some((enemy, _)) = closest {
+                attack_target.insert(entity, AttackTarget {entity: enemy, kamikaze: false}).unwrap();
+            }
+        }
+    }
+}
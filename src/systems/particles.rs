@@ -0,0 +1,94 @@
+use super::*;
+use crate::particles::*;
+use cgmath::InnerSpace;
+
+pub struct EmitterSystem;
+
+impl<'a> System<'a> for EmitterSystem {
+    type SystemData = (
+        Read<'a, Secs>,
+        Read<'a, Paused>,
+        Read<'a, Effects>,
+        Write<'a, Particles>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Emitter>
+    );
+
+    fn run(&mut self, (secs, paused, effects, mut particles, pos, emitters): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let mut rng = rand::thread_rng();
+
+        for (pos, emitter) in (&pos, &emitters).join() {
+            particles.emit(&effects, emitter.0, pos.0, secs.0, &mut rng);
+        }
+    }
+}
+
+// Sparks fly off asteroids while they're being mined
+pub struct MiningSparksSystem;
+
+impl<'a> System<'a> for MiningSparksSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Secs>,
+        Read<'a, Paused>,
+        Read<'a, Effects>,
+        Write<'a, Particles>,
+        ReadStorage<'a, Commands>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Size>,
+        ReadStorage<'a, MineableMaterials>,
+        ReadStorage<'a, CrewEfficiency>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (entities, secs, paused, effects, mut particles, commands, pos, size, mineable, efficiency, docked): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let mut rng = rand::thread_rng();
+
+        for (entity, commands, pos, size, _) in (&entities, &commands, &pos, &size, !&docked).join() {
+            let target = match commands.first() {
+                Some(Command::GoToAnd(target, Interaction::Mine)) => *target,
+                _ => continue
+            };
+
+            if crew_efficiency(&efficiency, entity) == 0.0 || mineable.get(target).map(|materials| materials.amount()).unwrap_or(0.0) <= 0.0 {
+                continue;
+            }
+
+            if let (Some(target_pos), Some(target_size)) = (pos.get(target), size.get(target)) {
+                let offset = pos.0 - target_pos.0;
+
+                if offset.magnitude() - CLOSE_ENOUGH_DISTANCE < size.0 + target_size.0 {
+                    let surface = target_pos.0 + offset.normalize() * target_size.0;
+                    particles.emit(&effects, Effect::MiningSparks, surface, secs.0, &mut rng);
+                }
+            }
+        }
+    }
+}
+
+pub struct StepParticles;
+
+impl<'a> System<'a> for StepParticles {
+    type SystemData = (
+        Read<'a, Secs>,
+        Read<'a, Paused>,
+        Read<'a, Effects>,
+        Write<'a, Particles>
+    );
+
+    fn run(&mut self, (secs, paused, effects, mut particles): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        particles.step(&effects, secs.0);
+    }
+}
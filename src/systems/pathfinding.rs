@@ -0,0 +1,120 @@
+use super::*;
+use crate::pathfinding::*;
+use std::collections::HashMap;
+use cgmath::InnerSpace;
+
+// How close ships need to get to a waypoint before heading to the next one, on top of their size
+const WAYPOINT_DISTANCE: f32 = 2.0;
+
+// Turn move orders into chains of waypoints that go around things that don't move, like asteroids.
+// Ships sent to the same place share one path, and formations are pathed as a whole,
+// with enough room around the centre to fit every member's slot
+pub struct PathfindingSystem;
+
+impl<'a> System<'a> for PathfindingSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Paused>,
+        WriteStorage<'a, Path>,
+        WriteStorage<'a, SeekPosition>,
+        ReadStorage<'a, Commands>,
+        ReadStorage<'a, FormationGroup>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Size>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, NoCollide>,
+        ReadStorage<'a, Docked>,
+        ReadStorage<'a, Orbit>
+    );
+
+    fn run(&mut self, (entities, paused, mut paths, mut seek, commands, groups, pos, size, vel, nocollide, docked, orbits): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        // Moons move, so paths around them would go out of date
+        let obstacles: Vec<Obstacle> = (&pos, &size, !&vel, !&nocollide, !&orbits).join()
+            .map(|(pos, size, ..)| Obstacle::new(pos.0, size.0))
+            .collect();
+
+        // Leave room for the biggest ship going to each place
+        let mut clearances: HashMap<[u32; 3], f32> = HashMap::new();
+
+        for (commands, size, _) in (&commands, &size, !&docked).join() {
+            if let Some(Command::MoveTo(destination)) = commands.first() {
+                let clearance = clearances.entry(key(*destination)).or_insert(0.0);
+                *clearance = clearance.max(size.0);
+            }
+        }
+
+        let mut shared: HashMap<[u32; 3], Vec<Vector3<f32>>> = HashMap::new();
+
+        for (entity, commands, pos, size, _) in (&entities, &commands, &pos, &size, !&docked).join() {
+            let destination = match commands.first() {
+                Some(Command::MoveTo(destination)) => *destination,
+                _ => {
+                    paths.remove(entity);
+                    continue;
+                }
+            };
+
+            let path = plan(&mut paths, entity, destination, || {
+                shared.entry(key(destination))
+                    .or_insert_with(|| find_path(pos.0, destination, &obstacles, clearances[&key(destination)]))
+                    .clone()
+            });
+
+            while path.waypoints.len() > 1 && pos.0.distance(path.waypoints[0]) < size.0 + WAYPOINT_DISTANCE {
+                path.waypoints.remove(0);
+            }
+
+            // The last waypoint is the destination, which `handle_command` already seeks to and stops at
+            if path.waypoints.len() > 1 {
+                seek.insert(entity, SeekPosition::to_point(path.waypoints[0], false)).unwrap();
+            }
+        }
+
+        let mut radii: HashMap<Entity, f32> = HashMap::new();
+
+        for (commands, size) in (&commands, &size).join() {
+            for command in commands.iter() {
+                if let Command::InFormation(group, offset) = command {
+                    let radius = radii.entry(*group).or_insert(0.0);
+                    *radius = radius.max(offset.magnitude() + size.0);
+                }
+            }
+        }
+
+        // `FormationSystem` moves the centre of the formation along the path
+        for (entity, group, pos) in (&entities, &groups, &pos).join() {
+            if !group.started {
+                continue;
+            }
+
+            let radius = radii.get(&entity).cloned().unwrap_or(0.0);
+            let path = plan(&mut paths, entity, group.target, || find_path(pos.0, group.target, &obstacles, radius));
+
+            while path.waypoints.len() > 1 && pos.0.distance(path.waypoints[0]) < WAYPOINT_DISTANCE {
+                path.waypoints.remove(0);
+            }
+        }
+    }
+}
+
+// Find a new path if the destination has changed
+fn plan<'a>(paths: &'a mut WriteStorage<Path>, entity: Entity, destination: Vector3<f32>, find: impl FnOnce() -> Vec<Vector3<f32>>) -> &'a mut Path {
+    let replan = paths.get(entity)
+        .map(|path| path.destination != destination)
+        .unwrap_or(true);
+
+    if replan {
+        paths.insert(entity, Path {destination, waypoints: find()}).unwrap();
+    }
+
+    paths.get_mut(entity).unwrap()
+}
+
+// Floats can't be hashed, but their bits can
+fn key(point: Vector3<f32>) -> [u32; 3] {
+    [point.x.to_bits(), point.y.to_bits(), point.z.to_bits()]
+}
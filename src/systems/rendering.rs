@@ -3,26 +3,63 @@ use specs::*;
 use crate::components;
 use crate::context::*;
 use super::*;
-use cgmath::Matrix4;
+use cgmath::{Matrix4, Vector2, InnerSpace};
+use crate::particles::{Effects, Particles};
+use crate::ui::Hud;
+use pedot::{Gui, Primitive};
+
+// Ships further from the camera than this many times their size are drawn as icons instead
+const ICON_DISTANCE: f32 = 150.0;
+// Icons are scaled by their distance from the camera so that they stay the same size on screen
+const ICON_SCALE: f32 = 0.02;
+// The radius of the circles drawn for commands that don't move the ship
+const COMMAND_MARKER_SIZE: f32 = 1.0;
+
+fn drawn_as_icon(camera_pos: Vector3<f32>, pos: Vector3<f32>, size: f32) -> bool {
+    (pos - camera_pos).magnitude() > size * ICON_DISTANCE
+}
+
+fn frustum(camera: &Camera, screen_dims: &ScreenDimensions) -> Frustum {
+    camera.frustum(screen_dims.0.y / screen_dims.0.x)
+}
 
 pub struct ObjectRenderer;
 
 impl<'a> System<'a> for ObjectRenderer {
     type SystemData = (
         Write<'a, ModelBuffers>,
+        Read<'a, Camera>,
+        Read<'a, ScreenDimensions>,
+        Read<'a, Meshes>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, components::Rotation>,
         ReadStorage<'a, Size>,
         ReadStorage<'a, Model>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Docked>
     );
 
-    fn run(&mut self, (mut buffers, pos, rot, size, model): Self::SystemData) {
-        for (pos, rot, size, model) in (&pos, &rot, &size, &model).join() {
+    fn run(&mut self, (mut buffers, camera, screen_dims, meshes, pos, rot, size, model, tag, docked): Self::SystemData) {
+        let camera_pos = camera.position();
+        let frustum = frustum(&camera, &screen_dims);
+
+        for (pos, rot, size, model, tag, _) in (&pos, &rot, &size, &model, tag.maybe(), !&docked).join() {
+            // Distant ships are drawn by `RenderBillboards`
+            if tag.is_some() && drawn_as_icon(camera_pos, pos.0, size.0) {
+                continue;
+            }
+
+            let radius = meshes.get_radius(model).unwrap_or(1.0) * size.0;
+
+            if !frustum.contains_sphere(pos.0, radius) {
+                continue;
+            }
+
             let scale = Matrix4::from_scale(size.0);
             let rotation: Matrix4<f32> = rot.0.into();
             let position = Matrix4::from_translation(pos.0) * rotation * scale;
             let instance = InstanceVertex::new(position);
-            buffers.push_model(*model, instance);
+            buffers.push_model(model, instance);
         }
     }
 }
@@ -33,16 +70,17 @@ impl<'a> System<'a> for RenderSystem {
     type SystemData = (
         Read<'a, Camera>,
         Read<'a, StarSystem>,
+        Read<'a, TextureAtlas>,
         Write<'a, BillboardBuffer>,
     );
 
-    fn run(&mut self, (camera, system, mut buffer): Self::SystemData) {
+    fn run(&mut self, (camera, system, atlas, mut buffer): Self::SystemData) {
         let offset = system.light * BACKGROUND_DISTANCE;
 
         let rotation: Matrix4<f32> = look_at(offset).into();
         let matrix = Matrix4::from_translation(camera.position() + offset) * rotation * Matrix4::from_scale(BACKGROUND_DISTANCE / 10.0);
 
-        buffer.push_billboard(matrix, Image::Star);
+        buffer.push_billboard(matrix, atlas.image(Image::Star));
     }
 }
 
@@ -80,26 +118,81 @@ impl<'a> System<'a> for RenderCommandPaths {
         Read<'a, ScreenDimensions>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Selectable>,
-        ReadStorage<'a, Commands>
+        ReadStorage<'a, Commands>,
+        ReadStorage<'a, Path>
     );
 
-    fn run(&mut self, (mut buffers, camera, screen_dims, positions, selectable, commands): Self::SystemData) {
-        (&positions, &selectable, &commands).join()
-            .filter(|(_, selectable, _)| selectable.selected)
-            .for_each(|(pos, _, commands)| {
-                let points = iter_owned([pos.0])
-                    .chain(commands.iter().filter_map(|command| command.point(&positions)));
-
-                buffers.push_3d_lines(points, WHITE, screen_dims.0, &camera);
+    fn run(&mut self, (mut buffers, camera, screen_dims, positions, selectable, commands, paths): Self::SystemData) {
+        (&positions, &selectable, &commands, paths.maybe()).join()
+            .filter(|(_, selectable, _, _)| selectable.selected)
+            .for_each(|(pos, _, commands, path)| {
+                let mut last = pos.0;
+                let first = commands.first().and_then(|command| command.point(&positions));
+
+                // Show the way around obstacles for the current move order
+                if let Some(path) = path {
+                    for waypoint in &path.waypoints[.. path.waypoints.len().saturating_sub(1)] {
+                        buffers.push_3d_line(last, *waypoint, WHITE, screen_dims.0, &camera);
+                        last = *waypoint;
+                    }
+                }
+
+                for command in commands.iter() {
+                    let colour = command_colour(command);
+
+                    match command {
+                        Command::Patrol(waypoints, _) => {
+                            let looped = waypoints.iter().chain(waypoints.first()).cloned();
+                            buffers.push_3d_lines(looped, colour, screen_dims.0, &camera);
+                        },
+                        // Show where the queue loops back to
+                        Command::Repeat => if let Some(first) = first {
+                            buffers.push_3d_line(last, first, colour, screen_dims.0, &camera);
+                        },
+                        _ => {}
+                    }
+
+                    match command.point(&positions) {
+                        Some(point) => {
+                            buffers.push_3d_line(last, point, colour, screen_dims.0, &camera);
+                            last = point;
+                        },
+                        // Commands that are done on the spot are marked where the ship will be
+                        None if !command.is_repeat() => buffers.push_circle(last, COMMAND_MARKER_SIZE, colour, screen_dims.0, &camera),
+                        None => {}
+                    }
+                }
             });
     }
 }
 
+fn command_colour(command: &Command) -> [f32; 3] {
+    match command {
+        Command::MoveTo(_) => WHITE,
+        Command::GoToAnd(_, interaction) => match interaction {
+            Interaction::Follow => [0.6, 0.6, 0.6],
+            Interaction::Attack => [1.0, 0.25, 0.25],
+            Interaction::Board => [1.0, 0.25, 0.75],
+            Interaction::Mine => [1.0, 0.75, 0.25],
+            Interaction::Salvage => [0.75, 0.5, 0.25],
+            Interaction::Dock => [0.25, 0.75, 1.0],
+            Interaction::Trade => [1.0, 1.0, 0.25],
+            Interaction::Repair => [0.25, 1.0, 0.25]
+        },
+        Command::Launch => [0.5, 1.0, 1.0],
+        Command::Patrol(_, _) => [0.25, 1.0, 0.5],
+        Command::Guard(_) => [0.25, 0.5, 1.0],
+        Command::HoldPosition(_) => [1.0, 0.5, 0.25],
+        Command::Wait(_, _) => [0.75, 0.75, 0.5],
+        Command::Repeat => [0.75, 0.5, 1.0],
+        Command::InFormation(_, _) => [0.75, 0.75, 1.0]
+    }
+}
+
 pub struct RenderUI;
 
 impl<'a> System<'a> for RenderUI {
     type SystemData = (
-        Entities<'a>,
         Write<'a, TextBuffer>,
         Read<'a, Time>,
         Read<'a, Formation>,
@@ -107,15 +200,12 @@ impl<'a> System<'a> for RenderUI {
         Read<'a, Help>,
         Read<'a, Dpi>,
         ReadStorage<'a, ShipType>,
-        ReadStorage<'a, Selectable>,
-        ReadStorage<'a, Occupation>,
-        ReadStorage<'a, Parent>,
-        ReadStorage<'a, Materials>,
-        ReadStorage<'a, MineableMaterials>,
-        ReadStorage<'a, Health>
+        Read<'a, InputMap>,
+        Read<'a, EncounterState>
     );
 
-    fn run(&mut self, (entities, mut text_buffer, time, formation, paused, help, dpi, tag, selectable, occupation, parent, materials, mineable, health): Self::SystemData) {
+    // The fleet's resources and the selected ships are shown by the HUD
+    fn run(&mut self, (mut text_buffer, time, formation, paused, help, dpi, tag, input_map, encounters): Self::SystemData) {
         let y = &mut 10.0;
 
         let mut render_text = |text: &str|  {
@@ -125,28 +215,38 @@ impl<'a> System<'a> for RenderUI {
 
         if help.0 {
             render_text("Controls:");
-            render_text("WASD to move camera");
             render_text("Drag the right mouse button to rotate the camera");
             render_text("Scroll with the mouse wheel to move the camera closer or further away");
             render_text("Click or drag with the left mouse button to select ships");
-            render_text("Hold shift while clicking/dragging to add to the selection");
-            render_text("Press C or click the middle mouse button to center the camera on the selected ships");
+            render_text("Double click a ship to select all the ships of that type on the screen");
+            render_text("Click the middle mouse button to center the camera on the selected ships");
             render_text("Right click the mouse to order the ships to do something");
-            render_text("Holding shift while right clicking will queue orders");
-            render_text("Holding shift while moving the mouse up and down will move the plane of movement vertically");
-            render_text("Press , and . to rotate through the formation list");
-            render_text("Press P to pause/unpause");
-            render_text("Press / for the debug view");
-            render_text("Press H to toggle this text");
+            render_text("Right click a carrier to dock fighters and miners in its hangar");
+
+            for line in input_map.help_text() {
+                render_text(&line);
+            }
+
+            for line in gamepad_help_text() {
+                render_text(&line);
+            }
+
             render_text("---------------------------");
         }
 
-        if paused.0 {
+        if let Some(encounter) = &encounters.active {
+            render_text(&encounter.text);
+
+            for (i, choice) in encounter.choices.iter().enumerate() {
+                render_text(&format!("{}: {}", input_map.key_names(Action::Choice(i as u8)), choice.text));
+            }
+
+            render_text("---------------------------");
+        } else if paused.0 {
             render_text("PAUSED");
         }
 
         render_text(&format!("Time: {:.1}", time.0));
-        render_text(&format!("Population: {}", occupation.join().count()));
         render_text(&format!("Formation: {:?}", *formation));
 
         let (ships, ships_total) = summarize(tag.join());
@@ -154,45 +254,49 @@ impl<'a> System<'a> for RenderUI {
         render_text(&format!("Ship count: {}", ships_total));
 
         for (tag, num) in ships {
-            render_text(&format!("{:?}: {}", tag, num));
+            render_text(&format!("{}: {}", tag, num));
         }
+    }
+}
 
-        let entity = (&entities, &selectable).join()
-            .filter(|(_, selectable)| selectable.selected)
-            .map(|(entity, _)| entity)
-            .next();
 
-        if let Some(entity) = entity {
-            render_text("---------------------");
+pub struct RenderHud;
 
-            if let Some(health) = health.get(entity) {
-                render_text(&format!("Health: {}", health.0));
-            }
+impl<'a> System<'a> for RenderHud {
+    type SystemData = (
+        Read<'a, Hud>,
+        Read<'a, Gui>,
+        Read<'a, Dpi>,
+        Read<'a, ScreenDimensions>,
+        Write<'a, LineBuffers>,
+        Write<'a, TextBuffer>
+    );
 
-            if let Some(materials) = materials.get(entity) {
-                render_text(&format!("Materials: {}", materials.0));
-            }
+    fn run(&mut self, (hud, gui, dpi, screen_dims, mut lines, mut text): Self::SystemData) {
+        let mut primitives = Vec::new();
 
-            if let Some(mineable) = mineable.get(entity) {
-                render_text(&format!("Mineable Materials: {}", mineable.0));
-            }
+        for panel in hud.panels().iter() {
+            panel.draw(&gui, &mut primitives);
+        }
 
-            let people = (&occupation, &parent).join()
-                .filter(|(_, parent)| parent.0 == entity)
-                .map(|(occupation, _)| occupation);
+        for panel in hud.panels().iter() {
+            panel.draw_tooltip(&gui, &mut primitives);
+        }
 
-            let (people, total) = summarize(people);
+        push_primitives(primitives, &mut lines, &mut text, dpi.0, screen_dims.0);
+    }
+}
 
-            render_text(&format!("Population: {}", total));
-            
-            for (tag, num) in people {
-                render_text(&format!("{:?}: {}", tag, num));
-            }
+pub fn push_primitives(primitives: Vec<Primitive>, lines: &mut LineBuffers, text: &mut TextBuffer, dpi: f32, screen_dims: Vector2<f32>) {
+    // Shapes at the same depth don't draw over each other, so the ones on top have to go first
+    for primitive in primitives.into_iter().rev() {
+        match primitive {
+            Primitive::Rect(rect, colour) => lines.push_filled_rect((rect.x, rect.y), (rect.width, rect.height), colour, screen_dims),
+            Primitive::Text(string, x, y) => text.push_text(&string, x, y, dpi)
         }
     }
 }
 
-
 pub struct RenderMouse;
 
 impl<'a> System<'a> for RenderMouse {
@@ -201,13 +305,20 @@ impl<'a> System<'a> for RenderMouse {
         Read<'a, RightClickOrder>,
         Read<'a, Controls>,
         Read<'a, ScreenDimensions>,
+        Read<'a, TextureAtlas>,
     );
 
-    fn run(&mut self, (mut buffers, order, controls, screen_dims): Self::SystemData) {
+    fn run(&mut self, (mut buffers, order, controls, screen_dims, atlas): Self::SystemData) {
         let (x, y) = controls.mouse();
 
-        if let Some(Command::GoToAnd(_, interaction)) = order.command {
-            buffers.push_image(interaction.image(), x + 32.0, y + 32.0, 64.0, 64.0, [0.0; 4], screen_dims.0);
+        let image = match order.command {
+            Some(Command::GoToAnd(_, interaction)) => Some(interaction.image()),
+            Some(Command::Guard(_)) => Some(Image::Move),
+            _ => None
+        };
+
+        if let Some(image) = image {
+            buffers.push_image(atlas.image(image), x + 32.0, y + 32.0, 64.0, 64.0, [0.0; 4], screen_dims.0);
         }
     }
 }
@@ -230,10 +341,11 @@ impl<'a> System<'a> for RenderDebug {
         ReadStorage<'a, Velocity>,
         ReadStorage<'a, SeekForce>,
         ReadStorage<'a, AvoidanceForce>,
-        ReadStorage<'a, FrictionForce>
+        ReadStorage<'a, FrictionForce>,
+        ReadStorage<'a, FlockingForce>
     );
 
-    fn run(&mut self, (entities, mut buffers, camera, entity, debug, meshes, screen_dims, pos, rot, size, model, vel, seek, avoid, friction): Self::SystemData) {
+    fn run(&mut self, (entities, mut buffers, camera, entity, debug, meshes, screen_dims, pos, rot, size, model, vel, seek, avoid, friction, flocking): Self::SystemData) {
         if !debug.0 {
             return;
         }
@@ -263,11 +375,19 @@ impl<'a> System<'a> for RenderDebug {
                 position += step;
             }
 
+            if let Some(flocking) = flocking.get(entity) {
+                buffers.push_3d_line(position, position + flocking.0 * scale, [1.0, 0.0, 1.0], screen_dims.0, &camera);
+                position += step;
+            }
+
             buffers.push_3d_line(position, position + vel.0 * scale / 10.0, [0.0, 1.0, 1.0], screen_dims.0, &camera);
 
             // render bbox
 
-            let bbox = meshes.get_bbox(*model, pos.0, rot.0, size.0);
+            let bbox = match meshes.get_bbox(model, pos.0, rot.0, size.0) {
+                Some(bbox) => bbox,
+                None => continue
+            };
 
             let min = na_point_to_vector(*bbox.mins());
             let max = na_point_to_vector(*bbox.maxs());
@@ -309,6 +429,10 @@ impl<'a> System<'a> for RenderMovementPlane {
 
     fn run(&mut self, (mut buffers, order, camera, screen_dims): Self::SystemData) {
         if let Some(Command::MoveTo(point)) = order.command {
+            if let Some(facing) = order.facing {
+                buffers.push_3d_line(point, point + facing * 10.0, WHITE, screen_dims.0, &camera);
+            }
+
             let distance = 20.0;
 
             let point = Vector3::new(round_to(point.x, distance), point.y, round_to(point.z, distance));
@@ -336,6 +460,37 @@ impl<'a> System<'a> for RenderMovementPlane {
     }
 }
 
+// Black holes can't be seen, so show their middle and how far their pull reaches
+pub struct RenderGravityWells;
+
+impl<'a> System<'a> for RenderGravityWells {
+    type SystemData = (
+        Write<'a, LineBuffers>,
+        Read<'a, Camera>,
+        Read<'a, ScreenDimensions>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Size>,
+        ReadStorage<'a, GravityWell>
+    );
+
+    fn run(&mut self, (mut buffers, camera, screen_dims, pos, size, wells): Self::SystemData) {
+        for (pos, size, well) in (&pos, &size, &wells).join() {
+            buffers.push_3d_lines(ring(pos.0, size.0), [0.5, 0.0, 1.0], screen_dims.0, &camera);
+            buffers.push_3d_lines(ring(pos.0, well.radius), [0.25, 0.0, 0.5], screen_dims.0, &camera);
+        }
+    }
+}
+
+// A flat circle of points, with the first point repeated at the end to close it
+fn ring(centre: Vector3<f32>, radius: f32) -> impl Iterator<Item=Vector3<f32>> {
+    let points = 64;
+
+    (0 ..= points).map(move |i| {
+        let angle = i as f32 / points as f32 * 2.0 * std::f32::consts::PI;
+        centre + Vector3::new(angle.cos(), 0.0, angle.sin()) * radius
+    })
+}
+
 pub struct RenderLogSystem;
 
 impl<'a> System<'a> for RenderLogSystem  {
@@ -357,26 +512,44 @@ pub struct RenderBillboards;
 impl<'a> System<'a> for RenderBillboards {
     type SystemData = (
         Read<'a, Camera>,
+        Read<'a, ScreenDimensions>,
+        Read<'a, TextureAtlas>,
+        Read<'a, Effects>,
+        Read<'a, Particles>,
         Write<'a, BillboardBuffer>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Size>,
-        ReadStorage<'a, Image>
+        ReadStorage<'a, Image>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Docked>
     );
 
-    fn run(&mut self, (camera, mut buffer, pos, size, image): Self::SystemData) {
+    fn run(&mut self, (camera, screen_dims, atlas, effects, particles, mut buffer, pos, size, image, tag, docked): Self::SystemData) {
         let cam_pos = camera.position();
-        let rotation = look_at(-camera.direction());
+        let rotation: Matrix4<f32> = look_at(-camera.direction()).into();
+        let frustum = frustum(&camera, &screen_dims);
+
+        let billboards = (&pos, &size, &image).join()
+            .map(|(pos, size, image)| (pos.0, size.0, *image));
 
-        let pred = |point: &Position| point.distance2(cam_pos);
+        let icons = (&pos, &size, &tag, !&docked).join()
+            .filter(|(pos, size, _, _)| drawn_as_icon(cam_pos, pos.0, size.0))
+            .map(|(pos, _, _, _)| (pos.0, (pos.0 - cam_pos).magnitude() * ICON_SCALE, Image::ShipIcon));
 
-        let mut billboards: Vec<_> = (&pos, &size, &image).join().collect();
-        billboards.sort_unstable_by(|a, b| cmp_floats(pred(a.0), pred(b.0)));
+        let particles = particles.iter()
+            .map(|particle| (particle.position, particle.size(&effects), particle.image(&effects)));
+
+        // Only sort the billboards that can be seen
+        let mut billboards: Vec<_> = billboards.chain(icons).chain(particles)
+            .filter(|(pos, size, _)| frustum.contains_sphere(*pos, *size))
+            .collect();
+
+        billboards.sort_unstable_by(|a, b| cmp_floats(a.0.distance2(cam_pos), b.0.distance2(cam_pos)));
 
         for (pos, size, image) in billboards {
-            let scale = Matrix4::from_scale(size.0);
-            let rotation: Matrix4<f32> = rotation.into();
-            let position = Matrix4::from_translation(pos.0) * rotation * scale;
-            buffer.push_billboard(position, *image);
+            let scale = Matrix4::from_scale(size);
+            let position = Matrix4::from_translation(pos) * rotation * scale;
+            buffer.push_billboard(position, atlas.image(image));
         }
     }
 }
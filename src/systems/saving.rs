@@ -1,7 +1,9 @@
 use crate::components;
 use specs::{*, saveload::*, error::*, saveload::MarkerAllocator as _};
 use crate::context::{Model, Image};
+use crate::particles::Emitter;
 use std::fs::*;
+use std::path::Path;
 use super::*;
 use crate::{Marker, MarkerAllocator};
 
@@ -32,143 +34,171 @@ type ComponentsB<'a> = (
     WriteStorage<'a, AttackTarget>,
     WriteStorage<'a, NoCollide>,
     WriteStorage<'a, ExplosionSize>,
-    WriteStorage<'a, Explosion>
+    WriteStorage<'a, Emitter>,
+    WriteStorage<'a, MinimumCrew>,
+    WriteStorage<'a, Boarder>,
+    WriteStorage<'a, BoardingAction>,
+    WriteStorage<'a, Hangar>,
+    WriteStorage<'a, Docked>,
+    WriteStorage<'a, ControlGroups>,
+    WriteStorage<'a, FormationGroup>
+);
+
+type ComponentsC<'a> = (
+    WriteStorage<'a, TurnRate>,
+    WriteStorage<'a, AngularVelocity>,
+    WriteStorage<'a, Bank>,
+    WriteStorage<'a, Orbit>,
+    WriteStorage<'a, GravityWell>,
+    WriteStorage<'a, Nebula>,
+    WriteStorage<'a, Salvage>,
+    WriteStorage<'a, Station>,
+    WriteStorage<'a, Food>,
+    WriteStorage<'a, Animated>
 );
 
 type ComponentsASerialized = <ComponentsA<'static> as SerializeComponents<Error, Marker>>::Data;
 type ComponentsBSerialized = <ComponentsB<'static> as SerializeComponents<Error, Marker>>::Data;
+type ComponentsCSerialized = <ComponentsC<'static> as SerializeComponents<Error, Marker>>::Data;
+
+type SaveData<'a> = (
+    Entities<'a>,
+
+    Read<'a, Camera>,
+    Read<'a, StarSystem>,
+    Read<'a, Time>,
+    Read<'a, Paused>,
+    Read<'a, Formation>,
+    Read<'a, Log>,
+    Read<'a, MovementPlane>,
+    Read<'a, Debug>,
+    Read<'a, EncounterState>,
+    Read<'a, SpareComponents>,
+
+    ComponentsA<'a>,
+    ComponentsB<'a>,
+    ComponentsC<'a>,
+
+    ReadStorage<'a, Marker>
+);
 
-pub struct SaveSystem;
-
-impl<'a> System<'a> for SaveSystem {
-    type SystemData = (
-        Entities<'a>,
-        Read<'a, Controls>,
-
-        Read<'a, Camera>,
-        Read<'a, StarSystem>,
-        Read<'a, Time>,
-        Read<'a, Paused>,
-        Read<'a, Formation>,
-        Read<'a, Log>,
-        Read<'a, MovementPlane>,
-        Read<'a, Debug>,
-
-        ComponentsA<'a>,
-        ComponentsB<'a>,
-        
-        ReadStorage<'a, Marker>
-    );
-
-    fn run(&mut self, (
-        entities, controls,
-        cam, sys, time, paused, formation, log, plane, debug,
-        comp_a, comp_b,
+type LoadData<'a> = (
+    Entities<'a>,
+    Write<'a, MarkerAllocator>,
+
+    Write<'a, Camera>,
+    Write<'a, StarSystem>,
+    Write<'a, Time>,
+    Write<'a, Paused>,
+    Write<'a, Formation>,
+    Write<'a, Log>,
+    Write<'a, MovementPlane>,
+    Write<'a, Debug>,
+    Write<'a, EncounterState>,
+    Write<'a, SpareComponents>,
+
+    ComponentsA<'a>,
+    ComponentsB<'a>,
+    ComponentsC<'a>,
+
+    WriteStorage<'a, Marker>
+);
+
+pub fn save_game(world: &World, path: &Path) -> Result<(), failure::Error> {
+    let (
+        entities,
+        cam, sys, time, paused, formation, log, plane, debug, encounters, spares,
+        comp_a, comp_b, comp_c,
         markers
-    ): Self::SystemData) {
-        if !controls.save {
-            return;
-        }
-
-        let ids = |entity| markers.get(entity).cloned();
-
-        let comp_a = (&entities, &markers).join()
-            .map(|(entity, marker)| (marker, comp_a.serialize_entity(entity, ids)))
-            .map(|(marker, result): (&Marker, Result<ComponentsASerialized, Error>)| {
-                EntityData {
-                    marker: *marker,
-                    components: result.unwrap()
-                }
-            })
-            .collect();
-
-        let comp_b = (&entities, &markers).join()
-            .map(|(entity, marker)| (marker, comp_b.serialize_entity(entity, ids)))
-            .map(|(marker, result): (&Marker, Result<ComponentsBSerialized, Error>)| {
-                EntityData {
-                    marker: *marker,
-                    components: result.unwrap()
-                }
-            })
-            .collect();
-
-        let data = GameData {
-            camera: cam.clone(),
-            system: sys.clone(),
-            time: time.clone(),
-            paused: paused.clone(),
-            formation: formation.clone(),
-            log: log.clone(),
-            plane: plane.clone(),
-            debug: debug.clone(),
-
-            comp_a, comp_b
-        };
-
-        let game = File::create("save.sav").unwrap();
-
-        bincode::serialize_into(game, &data).unwrap();
+    ): SaveData = world.system_data();
+
+    let ids = |entity| markers.get(entity).cloned();
+
+    let comp_a = (&entities, &markers).join()
+        .map(|(entity, marker)| {
+            let result: Result<ComponentsASerialized, Error> = comp_a.serialize_entity(entity, ids);
+            result.map(|components| EntityData {marker: *marker, components})
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let comp_b = (&entities, &markers).join()
+        .map(|(entity, marker)| {
+            let result: Result<ComponentsBSerialized, Error> = comp_b.serialize_entity(entity, ids);
+            result.map(|components| EntityData {marker: *marker, components})
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let comp_c = (&entities, &markers).join()
+        .map(|(entity, marker)| {
+            let result: Result<ComponentsCSerialized, Error> = comp_c.serialize_entity(entity, ids);
+            result.map(|components| EntityData {marker: *marker, components})
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let data = GameData {
+        camera: cam.clone(),
+        system: sys.clone(),
+        time: time.clone(),
+        paused: paused.clone(),
+        formation: formation.clone(),
+        log: log.clone(),
+        plane: plane.clone(),
+        debug: debug.clone(),
+        encounters: encounters.clone(),
+        spares: spares.clone(),
+
+        comp_a, comp_b, comp_c
+    };
+
+    if let Some(directory) = path.parent() {
+        create_dir_all(directory)?;
     }
+
+    bincode::serialize_into(File::create(path)?, &data)?;
+
+    Ok(())
 }
 
-pub struct LoadSystem;
-
-impl<'a> System<'a> for LoadSystem {
-    type SystemData = (
-        Entities<'a>,
-        Read<'a, Controls>,
-        Write<'a, MarkerAllocator>,
-
-        Write<'a, Camera>,
-        Write<'a, StarSystem>,
-        Write<'a, Time>,
-        Write<'a, Paused>,
-        Write<'a, Formation>,
-        Write<'a, Log>,
-        Write<'a, MovementPlane>,
-        Write<'a, Debug>,
-
-        ComponentsA<'a>,
-        ComponentsB<'a>,
-
-        WriteStorage<'a, Marker>
-    );
-
-    fn run(&mut self, (
-        entities, controls, mut allocator,
-        mut camera, mut system, mut time, mut paused, mut formation, mut log, mut plane, mut debug,
-        mut comp_a, mut comp_b,
+// Load a game into a world. This should be a fresh one, as the entities that are already in it are kept.
+pub fn load_game(world: &World, path: &Path) -> Result<(), failure::Error> {
+    let data: GameData = bincode::deserialize_from(File::open(path)?)?;
+
+    let (
+        entities, mut allocator,
+        mut camera, mut system, mut time, mut paused, mut formation, mut log, mut plane, mut debug, mut encounters, mut spares,
+        mut comp_a, mut comp_b, mut comp_c,
         mut markers
-    ): Self::SystemData) {
-        if !controls.load {
-            return;
-        }
-
-        let file = File::open("save.sav").unwrap();
-
-        let data: GameData = bincode::deserialize_from(file).unwrap();
-
-        let mut func = |marker| allocator.retrieve_entity(marker, &mut markers, &entities);
-
-        *time = data.time;
-        *camera = data.camera;
-        *system = data.system;
-        *paused = data.paused;
-        *formation = data.formation;
-        *log = data.log;
-        *plane = data.plane;
-        *debug = data.debug;
-
-        data.comp_a.into_iter().for_each(|entity_data| {
-            let result: Result<(), Error> = comp_a.deserialize_entity(func(entity_data.marker), entity_data.components, |e| Some(func(e)));
-            result.unwrap();
-        });
-
-        data.comp_b.into_iter().for_each(|entity_data| {
-            let result: Result<(), Error> = comp_b.deserialize_entity(func(entity_data.marker), entity_data.components, |e| Some(func(e)));
-            result.unwrap();
-        });
+    ): LoadData = world.system_data();
+
+    let mut func = |marker| allocator.retrieve_entity(marker, &mut markers, &entities);
+
+    *time = data.time;
+    *camera = data.camera;
+    *system = data.system;
+    *paused = data.paused;
+    *formation = data.formation;
+    *log = data.log;
+    *plane = data.plane;
+    *debug = data.debug;
+    *encounters = data.encounters;
+    *spares = data.spares;
+
+    for entity_data in data.comp_a {
+        let result: Result<(), Error> = comp_a.deserialize_entity(func(entity_data.marker), entity_data.components, |e| Some(func(e)));
+        result?;
+    }
+
+    for entity_data in data.comp_b {
+        let result: Result<(), Error> = comp_b.deserialize_entity(func(entity_data.marker), entity_data.components, |e| Some(func(e)));
+        result?;
     }
+
+    for entity_data in data.comp_c {
+        let result: Result<(), Error> = comp_c.deserialize_entity(func(entity_data.marker), entity_data.components, |e| Some(func(e)));
+        result?;
+    }
+
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize)]
@@ -181,7 +211,10 @@ struct GameData {
     log: Log,
     plane: MovementPlane,
     debug: Debug,
+    encounters: EncounterState,
+    spares: SpareComponents,
 
     comp_a: Vec<EntityData<Marker, ComponentsASerialized>>,
-    comp_b: Vec<EntityData<Marker, ComponentsBSerialized>>
+    comp_b: Vec<EntityData<Marker, ComponentsBSerialized>>,
+    comp_c: Vec<EntityData<Marker, ComponentsCSerialized>>
 }
@@ -0,0 +1,103 @@
+use super::*;
+
+// Control + number puts the selected ships into a group, number selects the group
+pub struct ControlGroupSystem;
+
+impl<'a> System<'a> for ControlGroupSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Controls>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, Docked>,
+        WriteStorage<'a, Selectable>,
+        WriteStorage<'a, ControlGroups>
+    );
+
+    fn run(&mut self, (entities, controls, side, docked, mut selectable, mut groups): Self::SystemData) {
+        let group = match controls.group() {
+            Some(group) => group,
+            None => return
+        };
+
+        if controls.control() {
+            for (entity, selectable, side) in (&entities, &selectable, &side).join() {
+                let in_group = selectable.selected && *side == Side::Friendly;
+
+                match groups.get_mut(entity) {
+                    Some(entity_groups) if in_group => entity_groups.add(group),
+                    Some(entity_groups) => entity_groups.remove(group),
+                    None if in_group => {
+                        let mut new_groups = ControlGroups::default();
+                        new_groups.add(group);
+                        groups.insert(entity, new_groups).unwrap();
+                    },
+                    None => {}
+                }
+            }
+        } else {
+            for (selectable, _) in (&mut selectable, !&docked).join() {
+                if !controls.shift() {
+                    selectable.selected = false;
+                }
+            }
+
+            for (selectable, groups, _) in (&mut selectable, &groups, !&docked).join() {
+                if groups.contains(group) {
+                    selectable.selected = true;
+                }
+            }
+        }
+    }
+}
+
+// Double clicking a ship selects all the ships of the same type and side on the screen
+pub struct DoubleClickSelectSystem;
+
+impl<'a> System<'a> for DoubleClickSelectSystem {
+    type SystemData = (
+        Read<'a, Controls>,
+        Read<'a, EntityUnderMouse>,
+        Read<'a, Camera>,
+        Read<'a, ScreenDimensions>,
+        Read<'a, crate::ui::Hud>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, Docked>,
+        WriteStorage<'a, Selectable>
+    );
+
+    fn run(&mut self, (controls, entity, camera, screen_dims, hud, pos, tag, side, docked, mut selectable): Self::SystemData) {
+        let first_clicked = match controls.left_double_clicked() {
+            Some(entity) if !hud.mouse_over => entity,
+            _ => return
+        };
+
+        // Both clicks have to be on the same ship
+        let clicked = match entity.0 {
+            Some((entity, _)) if entity == first_clicked => entity,
+            _ => return
+        };
+
+        let (clicked_tag, clicked_side) = match (tag.get(clicked), side.get(clicked)) {
+            (Some(tag), Some(side)) => (tag, side),
+            _ => return
+        };
+
+        let (width, height): (f32, f32) = screen_dims.0.into();
+
+        for (pos, tag, side, selectable, _) in (&pos, &tag, &side, &mut selectable, !&docked).join() {
+            if tag != clicked_tag || side != clicked_side {
+                continue;
+            }
+
+            let on_screen = camera.screen_position(pos.0, (width, height), false)
+                .map(|pos| pos.x >= 0.0 && pos.x <= width && pos.y >= 0.0 && pos.y <= height)
+                .unwrap_or(false);
+
+            if on_screen {
+                selectable.selected = true;
+            }
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use super::*;
 use winit::{
-    event::{WindowEvent, ElementState, MouseButton, VirtualKeyCode, KeyboardInput, MouseScrollDelta},
+    event::{WindowEvent, ElementState, KeyboardInput, MouseScrollDelta},
     dpi::{LogicalPosition, PhysicalPosition}
 };
 use ncollide3d::query::RayCast;
 use ordered_float::OrderedFloat;
+use crate::menus::{GameState, SaveMode};
+use pedot::Gui;
 
 pub struct EventHandlerSystem;
 
@@ -18,65 +20,96 @@ impl<'a> System<'a> for EventHandlerSystem {
         Write<'a, Help>,
         Write<'a, Formation>,
         Write<'a, Debug>,
+        Read<'a, InputMap>,
+        Write<'a, Gamepad>,
+        Write<'a, Gui>,
+        Read<'a, EncounterState>,
         WriteStorage<'a, Selectable>
     );
 
-    fn run(&mut self, (mut events, mut camera, mut plane, mut controls, mut paused, mut help, mut formation, mut debug, mut selectable): Self::SystemData) {
-        events.drain(..).for_each(|event| match event {
-            WindowEvent::CursorMoved {position: PhysicalPosition {x, y}, ..} => {
-                let (x, y) = (x as f32, y as f32);
-                let (mouse_x, mouse_y) = controls.mouse();
-                let (delta_x, delta_y) = (x - mouse_x, y - mouse_y);
-                
-                controls.set_mouse(x, y);
-
-                if controls.right_dragging() {
-                    camera.rotate_longitude(delta_x / 200.0);
-                    camera.rotate_latitude(delta_y / 200.0);
-                } else if controls.shift {
-                    plane.0 -= delta_y / 10.0;
-                }
-            },
-            WindowEvent::MouseWheel {delta, ..} => match delta {
-                MouseScrollDelta::PixelDelta(LogicalPosition {y, ..}) => camera.change_distance(y as f32 / 20.0),
-                MouseScrollDelta::LineDelta(_, y) => camera.change_distance(-y * 2.0)
-            },
-            WindowEvent::MouseInput {state, button, ..} => {
-                let pressed = state == ElementState::Pressed;
-
-                match button {
-                    MouseButton::Left => controls.handle_left(pressed),
-                    MouseButton::Right => controls.handle_right(pressed),
-                    MouseButton::Middle => controls.handle_middle(pressed),
-                    _ => {}
-                }
-            },
-            WindowEvent::KeyboardInput {input: KeyboardInput {state, virtual_keycode: Some(key), ..}, ..} => {
-                let pressed = state == ElementState::Pressed;
-
-                match key {
-                    VirtualKeyCode::C => {
-                        (&mut selectable).join()
-                            .for_each(|selectable| selectable.camera_following = selectable.selected);
+    fn run(&mut self, (mut events, mut camera, mut plane, mut controls, mut paused, mut help, mut formation, mut debug, input_map, mut gamepad, mut gui, encounters, mut selectable): Self::SystemData) {
+        events.drain(..).for_each(|event| {
+            let (action, pressed) = match event {
+                InputEvent::Window(event) => match event {
+                    WindowEvent::CursorMoved {position: PhysicalPosition {x, y}, ..} => {
+                        let (x, y) = (x as f32, y as f32);
+                        let (mouse_x, mouse_y) = controls.mouse();
+                        let (delta_x, delta_y) = (x - mouse_x, y - mouse_y);
+                        
+                        controls.set_mouse(x, y);
+
+                        if controls.right_dragging() && !controls.held(Action::SetFacing) {
+                            camera.rotate_longitude(delta_x / 200.0);
+                            camera.rotate_latitude(delta_y / 200.0);
+                        } else if controls.shift() {
+                            plane.0 -= delta_y / 10.0;
+                        }
+
+                        return;
+                    },
+                    WindowEvent::MouseWheel {delta, ..} => {
+                        match delta {
+                            MouseScrollDelta::PixelDelta(LogicalPosition {y, ..}) => camera.change_distance(y as f32 / 20.0),
+                            MouseScrollDelta::LineDelta(_, y) => camera.change_distance(-y * 2.0)
+                        }
+
+                        return;
+                    },
+                    WindowEvent::MouseInput {state, button, ..} => {
+                        controls.handle_mouse(button, state == ElementState::Pressed);
+                        return;
                     },
-                    VirtualKeyCode::P if pressed => paused.switch(),
-                    VirtualKeyCode::H if pressed => help.switch(),
-                    VirtualKeyCode::Slash if pressed => debug.0 = !debug.0,
-                    VirtualKeyCode::Comma if pressed => formation.rotate_left(),
-                    VirtualKeyCode::Period if pressed => formation.rotate_right(),
-                    
-                    VirtualKeyCode::Left   | VirtualKeyCode::A      => controls.left     = pressed,
-                    VirtualKeyCode::Right  | VirtualKeyCode::D      => controls.right    = pressed,
-                    VirtualKeyCode::Up     | VirtualKeyCode::W      => controls.forwards = pressed,
-                    VirtualKeyCode::Down   | VirtualKeyCode::S      => controls.back     = pressed,
-                    VirtualKeyCode::LShift | VirtualKeyCode::T      => controls.shift    = pressed,
-                    VirtualKeyCode::Back   | VirtualKeyCode::Delete => controls.delete   = pressed,
-                    VirtualKeyCode::Z => controls.save = pressed,
-                    VirtualKeyCode::L => controls.load = pressed,
-                    _ => {}
+                    WindowEvent::KeyboardInput {input: KeyboardInput {state, virtual_keycode: Some(key), ..}, ..} => {
+                        match input_map.action(key) {
+                            Some(action) => (action, state == ElementState::Pressed),
+                            None => return
+                        }
+                    },
+                    _ => return
+                },
+                InputEvent::Gamepad(event) => {
+                    gamepad.handle(event);
+
+                    let (button, pressed) = match event {
+                        GamepadEvent::Button(button, pressed) => (button, pressed),
+                        _ => return
+                    };
+
+                    match gamepad_binding(button) {
+                        Some(GamepadBinding::Action(action)) => (action, pressed),
+                        // The HUD only sees the window's events, so it has to be told about the gamepad's clicks
+                        Some(GamepadBinding::Mouse(button)) => {
+                            controls.handle_mouse(button, pressed);
+                            gui.handle_mouse(button, pressed);
+                            return;
+                        },
+                        None => return
+                    }
                 }
+            };
+
+            // Ignore key repeats
+            let triggered = pressed && !controls.held(action);
+
+            controls.handle_action(action, pressed);
+
+            if !triggered {
+                return;
+            }
+
+            match action {
+                Action::CentreCamera => {
+                    (&mut selectable).join()
+                        .for_each(|selectable| selectable.camera_following = selectable.selected);
+                },
+                // The game stays paused until the player chooses what to do in an encounter
+                Action::Pause if encounters.active.is_none() => paused.switch(),
+                Action::Help => help.switch(),
+                Action::Debug => debug.0 = !debug.0,
+                Action::FormationLeft => formation.rotate_left(),
+                Action::FormationRight => formation.rotate_right(),
+                _ => {}
             }
-            _ => {}
         })
     }
 }
@@ -126,18 +159,19 @@ impl<'a> System<'a> for EntityUnderMouseSystem {
         ReadStorage<'a, Position>,
         ReadStorage<'a, components::Rotation>,
         ReadStorage<'a, Size>,
-        ReadStorage<'a, Model>
+        ReadStorage<'a, Model>,
+        ReadStorage<'a, Docked>
     );
 
-    fn run(&mut self, (entities, ray, meshes, mut entity, pos, rot, size, model): Self::SystemData) {
-        entity.0 = (&entities, &pos, &rot, &size, &model).join()
-            .filter_map(|(entity, pos, rot, size, model)| {
+    fn run(&mut self, (entities, ray, meshes, mut entity, pos, rot, size, model, docked): Self::SystemData) {
+        entity.0 = (&entities, &pos, &rot, &size, &model, !&docked).join()
+            .filter_map(|(entity, pos, rot, size, model, _)| {
                 let mut ray = ray.0;
                 ray.origin /= size.0;
 
                 let iso = make_iso(pos.0 / size.0, rot.0);
 
-                meshes.get_mesh(*model)
+                meshes.get_mesh(model)?
                     .toi_with_ray(&iso, &ray, BACKGROUND_DISTANCE, true)
                     .map(|toi| {
                         let point = ray.origin + ray.dir * toi;
@@ -149,13 +183,40 @@ impl<'a> System<'a> for EntityUnderMouseSystem {
     }
 }
 
+pub struct MenuKeysSystem;
+
+impl<'a> System<'a> for MenuKeysSystem {
+    type SystemData = (
+        Read<'a, Controls>,
+        Write<'a, MenuRequest>
+    );
+
+    fn run(&mut self, (controls, mut request): Self::SystemData) {
+        let state = if controls.triggered(Action::Menu) {
+            GameState::PauseMenu
+        } else if controls.triggered(Action::Save) {
+            GameState::Saves(SaveMode::Save)
+        } else if controls.triggered(Action::Load) {
+            GameState::Saves(SaveMode::Load)
+        } else {
+            return;
+        };
+
+        request.0 = Some(state);
+    }
+}
+
 pub struct UpdateControlsSystem;
 
 impl<'a> System<'a> for UpdateControlsSystem {
-    type SystemData = Write<'a, Controls>;
+    type SystemData = (
+        Read<'a, Secs>,
+        Read<'a, EntityUnderMouse>,
+        Write<'a, Controls>
+    );
 
-    fn run(&mut self, mut controls: Self::SystemData) {
-        controls.update();
+    fn run(&mut self, (secs, entity, mut controls): Self::SystemData) {
+        controls.update(secs.0, entity.0.map(|(entity, _)| entity));
     }
 }
 
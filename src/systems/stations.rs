@@ -0,0 +1,224 @@
+use super::*;
+use std::collections::HashMap;
+
+// Materials per second
+const SALVAGE_RATE: f32 = 5.0;
+// Health per second
+const STATION_REPAIR_RATE: f32 = 5.0;
+// The materials it takes to convince someone on a station to join the fleet
+pub const RECRUIT_COST: f32 = 20.0;
+
+// Whether a ship is close enough to the thing it's interacting with
+fn in_range(entity: Entity, target: Entity, pos: &ReadStorage<Position>, size: &ReadStorage<Size>) -> bool {
+    let distance = size.get(entity).map(|size| size.0).unwrap_or(0.0) + size.get(target).map(|size| size.0).unwrap_or(0.0);
+
+    match (pos.get(entity), pos.get(target)) {
+        (Some(pos), Some(target_pos)) => pos.0.distance(target_pos.0) - CLOSE_ENOUGH_DISTANCE < distance,
+        _ => false
+    }
+}
+
+// Count the people on each ship
+fn people_aboard<D: Deref<Target=MaskedStorage<Parent>>>(occupation: &ReadStorage<Occupation>, parent: &Storage<Parent, D>) -> HashMap<Entity, usize> {
+    let mut aboard = HashMap::new();
+
+    (occupation, parent).join()
+        .for_each(|(_, parent)| *aboard.entry(parent.0).or_insert(0) += 1);
+
+    aboard
+}
+
+// Strip derelicts for parts and bring any survivors aboard
+pub struct SalvageSystem;
+
+impl<'a> System<'a> for SalvageSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Paused>,
+        Read<'a, Secs>,
+        Write<'a, Log>,
+        Read<'a, ShipDefinitions>,
+        Write<'a, SpareComponents>,
+        WriteStorage<'a, Commands>,
+        WriteStorage<'a, Salvage>,
+        WriteStorage<'a, Materials>,
+        WriteStorage<'a, Parent>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Size>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (entities, paused, secs, mut log, definitions, mut spares, mut commands, mut salvage, mut materials, mut parent, occupation, pos, size, tags, docked): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let mut aboard = people_aboard(&occupation, &parent);
+
+        for (entity, commands, tag, _) in (&entities, &mut commands, &tags, !&docked).join() {
+            let derelict = match commands.first() {
+                Some(Command::GoToAnd(derelict, Interaction::Salvage)) => *derelict,
+                _ => continue
+            };
+
+            let definition = match definitions.get(tag) {
+                Some(definition) => definition,
+                None => {
+                    commands.remove(0);
+                    continue;
+                }
+            };
+
+            if !entities.is_alive(derelict) || salvage.get(derelict).is_none() {
+                commands.remove(0);
+                continue;
+            }
+
+            if !in_range(entity, derelict, &pos, &size) {
+                continue;
+            }
+
+            let already_stripped = salvage.get(derelict).map(|salvage| salvage.is_empty()).unwrap_or(true);
+
+            let stripped = transfer_between_different(&mut salvage, &mut materials, derelict, entity, SALVAGE_RATE * secs.0)
+                .unwrap_or(true);
+
+            // The derelict's components can be taken out once everything else has been
+            if stripped && !already_stripped {
+                if let Some(components) = tags.get(derelict).and_then(|tag| definitions.default_components(tag, 0)) {
+                    let components = components.into_inner();
+                    log.append(format!("Salvaged {} components from a derelict", components.len()));
+                    spares.0.extend(components);
+                }
+            }
+
+            let room = definition.crew_capacity.saturating_sub(aboard.get(&entity).cloned().unwrap_or(0));
+
+            let survivors: Vec<Entity> = (&entities, &occupation, &parent).join()
+                .filter(|(_, _, parent)| parent.0 == derelict)
+                .map(|(survivor, _, _)| survivor)
+                .collect();
+
+            let rescued = survivors.len().min(room);
+
+            for survivor in survivors.iter().take(rescued) {
+                parent.insert(*survivor, Parent(entity)).unwrap();
+            }
+
+            if rescued > 0 {
+                *aboard.entry(entity).or_insert(0) += rescued;
+                log.append(format!("Rescued {} survivors from a derelict", rescued));
+            }
+
+            // Finished once no more parts can be taken and there's nobody left who can be taken aboard
+            if stripped && (rescued == survivors.len() || room == rescued) {
+                log.append("Finished salvaging a derelict".into());
+                commands.finish_current();
+            }
+        }
+    }
+}
+
+// Repair ships at stations, or trade materials there for new recruits
+pub struct StationSystem;
+
+impl<'a> System<'a> for StationSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Paused>,
+        Read<'a, Secs>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Log>,
+        Read<'a, ShipDefinitions>,
+        WriteStorage<'a, Commands>,
+        WriteStorage<'a, Station>,
+        WriteStorage<'a, Materials>,
+        WriteStorage<'a, Health>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, Occupation>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Size>,
+        ReadStorage<'a, ShipType>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (entities, paused, secs, lazy, mut log, definitions, mut commands, mut station, mut materials, mut health, parent, occupation, pos, size, tag, docked): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        let aboard = people_aboard(&occupation, &parent);
+
+        for (entity, commands, tag, _) in (&entities, &mut commands, &tag, !&docked).join() {
+            let (target, interaction) = match commands.first() {
+                Some(Command::GoToAnd(target, interaction @ Interaction::Trade)) |
+                Some(Command::GoToAnd(target, interaction @ Interaction::Repair)) => (*target, *interaction),
+                _ => continue
+            };
+
+            let (station, definition) = match (station.get_mut(target), definitions.get(tag)) {
+                (Some(station), Some(definition)) if entities.is_alive(target) => (station, definition),
+                _ => {
+                    commands.remove(0);
+                    continue;
+                }
+            };
+
+            if !in_range(entity, target, &pos, &size) {
+                continue;
+            }
+
+            match interaction {
+                Interaction::Repair => {
+                    let repaired = match health.get_mut(entity) {
+                        Some(health) => {
+                            health.0 = move_towards(health.0, definition.max_health(), STATION_REPAIR_RATE * secs.0);
+                            health.0 >= definition.max_health()
+                        },
+                        None => true
+                    };
+
+                    if repaired {
+                        commands.finish_current();
+                    }
+                },
+                Interaction::Trade => {
+                    let materials = match materials.get_mut(entity) {
+                        Some(materials) => materials,
+                        None => {
+                            commands.remove(0);
+                            continue;
+                        }
+                    };
+
+                    let room = definition.crew_capacity.saturating_sub(aboard.get(&entity).cloned().unwrap_or(0));
+                    let affordable = (materials.amount() / RECRUIT_COST) as usize;
+                    let recruits = room.min(affordable).min(station.recruits);
+
+                    for _ in 0 .. recruits {
+                        lazy.create_entity(&entities)
+                            .with(CreationTime::from_age(25))
+                            .with(Occupation::Worker)
+                            .with(Parent(entity))
+                            .marked::<Marker>()
+                            .build();
+                    }
+
+                    materials.reduce(recruits as f32 * RECRUIT_COST);
+                    station.recruits -= recruits;
+
+                    log.append(if recruits > 0 {
+                        format!("{} people from the station joined the fleet", recruits)
+                    } else {
+                        "Nobody on the station wanted to join the fleet".into()
+                    });
+
+                    commands.finish_current();
+                },
+                _ => {}
+            }
+        }
+    }
+}
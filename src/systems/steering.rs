@@ -1,8 +1,27 @@
 use crate::components;
 use specs::*;
 use crate::util::*;
-use cgmath::{InnerSpace, Vector3, Zero};
+use cgmath::{InnerSpace, Vector3, Zero, Quaternion, Rad, Rotation as _, Rotation3};
 use super::*;
+use ncollide3d::shape::Ball;
+
+// How much of a ship's thrust can push it sideways
+const MANOEUVRING_THRUST: f32 = 0.25;
+// How much of a ship's thrust can slow it down while it's facing forwards
+const BRAKING_THRUST: f32 = 0.5;
+// How quickly ships ease off turning as they line up with where they want to go
+const TURN_DAMPING: f32 = 0.5;
+// How far ships lean over when turning as fast as they can
+const MAX_BANK: f32 = 0.6;
+// How quickly ships roll into and out of turns, in radians per frame
+const BANK_SPEED: f32 = 0.02;
+// How many frames ahead ships look for things they're about to hit
+const LOOKAHEAD: f32 = 60.0;
+// How far away ships in a flock count as neighbours
+const FLOCK_RADIUS: f32 = 30.0;
+const SEPARATION_WEIGHT: f32 = 1.5;
+const COHESION_WEIGHT: f32 = 0.3;
+const ALIGNMENT_WEIGHT: f32 = 0.5;
 
 pub struct ApplyVelocitySystem;
 
@@ -29,11 +48,13 @@ pub struct SetRotationSystem;
 impl<'a> System<'a> for SetRotationSystem {
     type SystemData = (
         WriteStorage<'a, components::Rotation>,
-        ReadStorage<'a, Velocity>
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, TurnRate>
     );
 
-    fn run(&mut self, (mut rotation, velocity): Self::SystemData) {
-        for (rotation, velocity) in (&mut rotation, &velocity).join() {
+    fn run(&mut self, (mut rotation, velocity, turn_rate): Self::SystemData) {
+        // Ships turn gradually in the turn system, but things like missiles just point where they're going
+        for (rotation, velocity, _) in (&mut rotation, &velocity, !&turn_rate).join() {
             if velocity.magnitude() > 0.0 {
                 rotation.0 = look_at(velocity.0);
             }
@@ -41,6 +62,72 @@ impl<'a> System<'a> for SetRotationSystem {
     }
 }
 
+// Turn ships towards where they are going at a limited speed, banking into the turn
+pub struct TurnSystem;
+
+impl<'a> System<'a> for TurnSystem {
+    type SystemData = (
+        Read<'a, Paused>,
+        WriteStorage<'a, components::Rotation>,
+        WriteStorage<'a, AngularVelocity>,
+        WriteStorage<'a, Bank>,
+        ReadStorage<'a, TurnRate>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, SeekPosition>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (paused, mut rotation, mut angular, mut bank, turn_rate, vel, pos, seek, docked): Self::SystemData) {
+        if paused.0 {
+            return;
+        }
+
+        for (rotation, angular, bank, turn_rate, vel, pos, seek, _) in (&mut rotation, &mut angular, &mut bank, &turn_rate, &vel, &pos, seek.maybe(), !&docked).join() {
+            // Face the point being flown to, or the direction of travel when drifting
+            let desired = seek
+                .map(|seek| seek.delta(pos.0))
+                .filter(|delta| delta.magnitude2() > 0.0)
+                .unwrap_or(vel.0);
+
+            let heading = valid_rotation(rotation.0) * Quaternion::from_angle_z(Rad(-bank.0));
+            let facing = forwards(heading);
+
+            let angle = if desired.magnitude2() > 0.0 {
+                facing.angle(desired).0
+            } else {
+                0.0
+            };
+
+            // Slow down the turn when nearly lined up so as to not overshoot
+            let target_speed = (angle * TURN_DAMPING).min(turn_rate.0);
+            angular.0 = move_towards(angular.0, target_speed, turn_rate.acceleration());
+
+            let heading = if angle > 0.0 {
+                let target = look_at(desired);
+                // Go the short way round
+                let target = if heading.dot(target) < 0.0 { -target } else { target };
+
+                heading.slerp(target, angular.0.min(angle) / angle).normalize()
+            } else {
+                heading
+            };
+
+            // Lean into the turn, further the faster the ship is turning
+            let turn = facing.cross(desired).y;
+
+            let target_bank = if turn != 0.0 {
+                -turn.signum() * MAX_BANK * angular.0 / turn_rate.0
+            } else {
+                0.0
+            };
+
+            bank.0 = move_towards(bank.0, target_bank, BANK_SPEED);
+            rotation.0 = heading * Quaternion::from_angle_z(Rad(bank.0));
+        }
+    }
+}
+
 // technically steering _and_ arrival
 pub struct SeekSystem;
 
@@ -51,12 +138,17 @@ impl<'a> System<'a> for SeekSystem {
         ReadStorage<'a, Velocity>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, SeekPosition>,
-        ReadStorage<'a, MaxSpeed>
+        ReadStorage<'a, MaxSpeed>,
+        ReadStorage<'a, TurnRate>,
+        ReadStorage<'a, Docked>
     );
 
-    fn run(&mut self, (entities, mut seek, vel, pos, seek_pos, speed): Self::SystemData) {
-        for (entity, vel, pos, seek_pos, speed) in (&entities, &vel, &pos, &seek_pos, &speed).join() {
-            let force = seek_and_arrival_force(pos.0, vel.0, seek_pos, speed.0, 0.01);
+    fn run(&mut self, (entities, mut seek, vel, pos, seek_pos, speed, turn_rate, docked): Self::SystemData) {
+        for (entity, vel, pos, seek_pos, speed, turn_rate, _) in (&entities, &vel, &pos, &seek_pos, &speed, turn_rate.maybe(), !&docked).join() {
+            // Ships that have to turn around to slow down fully need to start braking earlier
+            let braking = if turn_rate.is_some() { 0.01 * BRAKING_THRUST } else { 0.01 };
+
+            let force = seek_and_arrival_force(pos.0, vel.0, seek_pos, speed.0, 0.01, braking);
             seek.insert(entity, SeekForce(force)).unwrap();
         }
     }
@@ -94,18 +186,20 @@ impl<'a> System<'a> for AvoidanceSystem {
         ReadStorage<'a, Position>,
         ReadStorage<'a, MaxSpeed>,
         ReadStorage<'a, Size>,
-        ReadStorage<'a, NoCollide>
+        ReadStorage<'a, NoCollide>,
+        ReadStorage<'a, Docked>
     );
 
-    fn run(&mut self, (entities, mut avoidance, vel, positions, speed, sizes, nocollide): Self::SystemData) {
+    fn run(&mut self, (entities, mut avoidance, vel, positions, speed, sizes, nocollide, docked): Self::SystemData) {
         // collect the entity positions into a vec to avoid having to deref the ecs storage (which can be slow)
         // Also dont collect entities with an image because having images push entities about seems kinda wierd
-        let entity_positions: Vec<_> = (&positions, &sizes, !&nocollide).join().collect();
+        let entity_positions: Vec<_> = (&positions, &sizes, !&nocollide, !&docked).join().collect();
 
-        let iterator = entity_positions.iter().map(|(pos, size, _)| (pos.0, size.0));
+        let iterator = entity_positions.iter().map(|(pos, size, _, _)| (pos.0, size.0));
 
-        for (entity, vel, pos, speed, size) in (&entities, &vel, &positions, &speed, &sizes).join() {
-            let force = avoidance_force(pos.0, vel.0, size.0, iterator.clone(), speed.0, 0.01);
+        for (entity, vel, pos, speed, size, _) in (&entities, &vel, &positions, &speed, &sizes, !&docked).join() {
+            let force = avoidance_force(pos.0, vel.0, size.0, iterator.clone(), speed.0, 0.01) +
+                predictive_avoidance_force(pos.0, vel.0, size.0, iterator.clone(), speed.0, 0.01);
 
             avoidance.insert(entity, AvoidanceForce(force)).unwrap();
         }
@@ -135,6 +229,56 @@ impl<'a> System<'a> for FrictionSystem {
     }
 }
 
+// Ships moving together spread out a little, keep close to each other and fly in the same direction.
+// They keep away from any nearby ships on their side, but only stay with the ones with the same order or group
+pub struct FlockingSystem;
+
+impl<'a> System<'a> for FlockingSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, FlockingForce>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, MaxSpeed>,
+        ReadStorage<'a, Size>,
+        ReadStorage<'a, Side>,
+        ReadStorage<'a, SeekPosition>,
+        ReadStorage<'a, Commands>,
+        ReadStorage<'a, ControlGroups>,
+        ReadStorage<'a, Docked>
+    );
+
+    fn run(&mut self, (entities, mut flocking, vel, pos, speed, size, side, seek, commands, groups, docked): Self::SystemData) {
+        // Ships in a formation already keep their places, so only loose ships that are on the move flock
+        let flocking_ships = (&entities, &vel, &pos, &speed, &size, &side, &seek, &commands, groups.maybe(), !&docked).join()
+            .filter(|(.., commands, _, _)| match commands.first() {
+                Some(Command::InFormation(..)) => false,
+                _ => true
+            })
+            .map(|(entity, vel, pos, speed, size, side, _, commands, groups, _)| {
+                (entity, vel.0, pos.0, speed.0, size.0, side, commands.first(), groups.map(|groups| groups.0).unwrap_or(0))
+            });
+
+        let ships: Vec<_> = flocking_ships.collect();
+
+        flocking.clear();
+
+        for &(entity, vel, pos, speed, size, side, command, groups) in &ships {
+            let neighbours = ships.iter()
+                .filter(|(other, _, other_pos, ..)| *other != entity && pos.distance(*other_pos) < FLOCK_RADIUS)
+                .filter(|(.., other_side, _, _)| *other_side == side)
+                .map(|(_, other_vel, other_pos, _, _, _, other_command, other_groups)| {
+                    let flockmate = *other_command == command || *other_groups & groups != 0;
+                    (*other_pos, *other_vel, flockmate)
+                });
+
+            let force = flocking_force(pos, vel, size, neighbours, speed, 0.01);
+
+            flocking.insert(entity, FlockingForce(force)).unwrap();
+        }
+    }
+}
+
 pub struct MergeForceSystem;
 
 impl<'a> System<'a> for MergeForceSystem {
@@ -145,20 +289,34 @@ impl<'a> System<'a> for MergeForceSystem {
         ReadStorage<'a, SeekForce>,
         ReadStorage<'a, AvoidanceForce>,
         ReadStorage<'a, FrictionForce>,
-        ReadStorage<'a, MaxSpeed>
+        ReadStorage<'a, FlockingForce>,
+        ReadStorage<'a, MaxSpeed>,
+        ReadStorage<'a, CrewEfficiency>,
+        ReadStorage<'a, components::Rotation>,
+        ReadStorage<'a, TurnRate>,
+        ReadStorage<'a, Docked>
     );
 
-    fn run(&mut self, (entities, paused, mut vel, seek, avoid, friction, speed): Self::SystemData) {
+    fn run(&mut self, (entities, paused, mut vel, seek, avoid, friction, flocking, speed, efficiency, rotation, turn_rate, docked): Self::SystemData) {
         if paused.0 {
             return;
         }
 
-        for (entity, vel, avoid, friction, speed) in (&entities, &mut vel, &avoid, &friction, &speed).join() {
+        for (entity, vel, avoid, friction, speed, _) in (&entities, &mut vel, &avoid, &friction, &speed, !&docked).join() {
             let seek = seek.get(entity).map(|seek| seek.0).unwrap_or_else(Vector3::zero);
 
-            let combined = seek + avoid.0 * 10.0 + friction.0;
+            let flocking = flocking.get(entity).map(|flocking| flocking.0).unwrap_or_else(Vector3::zero);
+
+            let thrust = seek + avoid.0 * 10.0 + flocking;
+
+            let thrust = match (rotation.get(entity), turn_rate.get(entity)) {
+                (Some(rotation), Some(_)) => directional_thrust(thrust, forwards(valid_rotation(rotation.0))),
+                _ => thrust
+            };
+
+            let combined = thrust + friction.0;
             let combined = limit_vector(combined, 0.01);
-            vel.0 = limit_vector(vel.0 + combined, speed.0);
+            vel.0 = limit_vector(vel.0 + combined, speed.0 * crew_efficiency(&efficiency, entity));
         }
     }
 }
@@ -168,8 +326,21 @@ fn calc_force(vel: Vector3<f32>, desired: Vector3<f32>, max_force: f32) -> Vecto
     limit_vector(steering, max_force)
 }
 
-fn seek_and_arrival_force(pos: Vector3<f32>, vel: Vector3<f32>, seek_pos: &SeekPosition, max_speed: f32, max_force: f32) -> Vector3<f32> {
-    let braking_distance = vel.magnitude2() / (2.0 * max_force);
+// The direction a rotation points the front of a model in
+pub fn forwards(rotation: Quaternion<f32>) -> Vector3<f32> {
+    rotation.rotate_vector(Vector3::unit_z())
+}
+
+// Full thrust forwards, but only the weaker manoeuvring thrusters sideways and backwards
+pub fn directional_thrust(thrust: Vector3<f32>, facing: Vector3<f32>) -> Vector3<f32> {
+    let forwards = thrust.dot(facing);
+    let sideways = thrust - facing * forwards;
+
+    facing * forwards.max(0.0) + facing * forwards.min(0.0) * BRAKING_THRUST + sideways * MANOEUVRING_THRUST
+}
+
+fn seek_and_arrival_force(pos: Vector3<f32>, vel: Vector3<f32>, seek_pos: &SeekPosition, max_speed: f32, max_force: f32, braking_force: f32) -> Vector3<f32> {
+    let braking_distance = vel.magnitude2() / (2.0 * braking_force);
 
     let delta = seek_pos.delta(pos);
 
@@ -195,3 +366,85 @@ fn avoidance_force<I: Iterator<Item=(Vector3<f32>, f32)>>(pos: Vector3<f32>, vel
         Vector3::zero()
     }
 }
+
+// Cast a ray ahead along the velocity and steer sideways around the closest thing that it hits
+pub fn predictive_avoidance_force<I: Iterator<Item=(Vector3<f32>, f32)>>(pos: Vector3<f32>, vel: Vector3<f32>, size: f32, iterator: I, max_speed: f32, max_force: f32) -> Vector3<f32> {
+    if vel.magnitude2() == 0.0 {
+        return Vector3::zero();
+    }
+
+    let direction = vel.normalize();
+    let lookahead = vel.magnitude() * LOOKAHEAD + size;
+
+    let ray = Ray::new(
+        point_to_na_point(vector_to_point(pos)),
+        vector_to_na_vector(direction)
+    );
+
+    let closest = iterator
+        // Things that are already overlapping are pushed apart by the normal avoidance
+        .filter(|(p, s)| {
+            let distance = pos.distance(*p);
+            distance > size + s && distance < lookahead + size + s
+        })
+        .filter_map(|(p, s)| {
+            Ball::new(size + s)
+                .toi_with_ray(&make_iso(p, Quaternion::zero()), &ray, lookahead, true)
+                .map(|toi| (p, toi))
+        })
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+
+    let (obstacle, toi) = match closest {
+        Some(closest) => closest,
+        None => return Vector3::zero()
+    };
+
+    // Steer away from the centre of the obstacle, perpendicular to the direction of travel
+    let offset = pos - obstacle;
+    let mut away = offset - direction * offset.dot(direction);
+
+    // Heading straight for the middle, so just pick a side
+    if away.magnitude2() == 0.0 {
+        away = direction.cross(UP);
+
+        if away.magnitude2() == 0.0 {
+            away = Vector3::unit_x();
+        }
+    }
+
+    // Steer harder the closer the collision is
+    let urgency = 1.0 - toi / lookahead;
+    let desired = away.normalize_to(max_speed);
+
+    calc_force(vel, desired, max_force) * urgency
+}
+
+// Separation, cohesion and alignment with neighbouring ships, given as (position, velocity)
+// Neighbours are (position, velocity, whether to stay with them)
+pub fn flocking_force<I: Iterator<Item=(Vector3<f32>, Vector3<f32>, bool)>>(pos: Vector3<f32>, vel: Vector3<f32>, size: f32, neighbours: I, max_speed: f32, max_force: f32) -> Vector3<f32> {
+    let neighbours: Vec<_> = neighbours.collect();
+
+    if neighbours.is_empty() {
+        return Vector3::zero();
+    }
+
+    let steer = |direction: Vector3<f32>| if direction.magnitude2() > 0.0 {
+        calc_force(vel, direction.normalize_to(max_speed), max_force)
+    } else {
+        Vector3::zero()
+    };
+
+    // Keep a few ship lengths away from the others
+    let separation = avg(neighbours.iter()
+        .map(|(p, ..)| (*p, pos.distance(*p)))
+        .filter(|(_, distance)| *distance > 0.0 && *distance < size * 4.0)
+        .map(|(p, distance)| (pos - p).normalize_to(1.0 / distance))
+    ).unwrap_or_else(Vector3::zero);
+
+    let flockmates = || neighbours.iter().filter(|(.., flockmate)| *flockmate);
+
+    let cohesion = avg(flockmates().map(|(p, ..)| *p)).map(|centre| steer(centre - pos)).unwrap_or_else(Vector3::zero);
+    let alignment = avg(flockmates().map(|(_, v, _)| *v)).map(steer).unwrap_or_else(Vector3::zero);
+
+    steer(separation) * SEPARATION_WEIGHT + cohesion * COHESION_WEIGHT + alignment * ALIGNMENT_WEIGHT
+}
@@ -22,6 +22,26 @@ impl<'a> StorageGetter for WriteStorage<'a, MineableMaterials> {
     }
 }
 
+impl<'a> StorageGetter for WriteStorage<'a, Food> {
+    fn get(&self, entity: Entity) -> Option<&StoredResource> {
+        self.get(entity).map(|storage| &storage.0)
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut StoredResource> {
+        self.get_mut(entity).map(|storage| &mut storage.0)
+    }
+}
+
+impl<'a> StorageGetter for WriteStorage<'a, Salvage> {
+    fn get(&self, entity: Entity) -> Option<&StoredResource> {
+        self.get(entity).map(|storage| &storage.0)
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut StoredResource> {
+        self.get_mut(entity).map(|storage| &mut storage.0)
+    }
+}
+
 pub trait StorageGetter {
     fn get(&self, entity: Entity) -> Option<&StoredResource>;
     fn get_mut(&mut self, entity: Entity) -> Option<&mut StoredResource>;
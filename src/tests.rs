@@ -3,15 +3,35 @@
 use specs::*;
 use components::*;
 use systems::*;
+use ships::*;
+use input::Action;
 use *;
 use cgmath::*;
 use specs::saveload::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
 fn get_data<C: Component + Clone>(world: &World, entity: Entity) -> Option<C> {
     let data: ReadStorage<C> = world.system_data();
     data.get(entity).cloned()
 }
 
+// A ship facing forwards, which is how most tests need them
+fn add_ship(world: &mut World, tag: ShipType, position: Vector3<f32>, side: Side) -> Entity {
+    create_ship(world, tag, position, Quaternion::one(), side).unwrap()
+}
+
+fn order(world: &World, entity: Entity, command: Command) {
+    world.write_storage::<Commands>().get_mut(entity).unwrap().push(command);
+}
+
+fn select(world: &World, entity: Entity, selected: bool) {
+    world.write_storage::<Selectable>().get_mut(entity).unwrap().selected = selected;
+}
+
+fn selected(world: &World, entity: Entity) -> bool {
+    world.read_storage::<Selectable>().get(entity).unwrap().selected
+}
+
 #[test]
 fn test_velocity() {
     let mut world = create_world();
@@ -40,18 +60,1093 @@ fn test_saveload() {
         .marked::<Marker>()
         .build();
 
-    let mut controls = Controls::default();
-    controls.save = true;
-    controls.load = true;
-
-    *world_a.write_resource() = controls.clone();
-    *world_b.write_resource() = controls;
+    let path = std::env::temp_dir().join("fleet_commander_test.sav");
 
-    SaveSystem.run_now(&world_a.res);
-    LoadSystem.run_now(&world_b.res);
+    save_game(&world_a, &path).unwrap();
+    load_game(&world_b, &path).unwrap();
 
     assert_eq!(
         get_data(&world_b, entity),
         Some(Velocity(Vector3::new(1.0, 2.0, 3.0)))
     );
 }
+
+#[test]
+fn test_crew_requirements() {
+    let mut world = create_world();
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    let minimum = world.read_storage::<MinimumCrew>().get(carrier).unwrap().0;
+
+    // Marines don't count as crew
+    create_person(carrier, &mut world, Occupation::Marine);
+    CrewSystem.run_now(&world);
+    assert_eq!(get_data(&world, carrier), Some(CrewEfficiency(0.0)));
+
+    // Uncrewed ships can't reload
+    *world.write_resource() = Secs(1.0);
+    world.write_storage::<CanAttack>().get_mut(carrier).unwrap().time = 1.0;
+    ReduceAttackTime.run_now(&world);
+    assert_eq!(world.read_storage::<CanAttack>().get(carrier).unwrap().time, 1.0);
+
+    for _ in 0 .. minimum / 2 {
+        create_person(carrier, &mut world, Occupation::Worker);
+    }
+
+    CrewSystem.run_now(&world);
+    assert_eq!(get_data(&world, carrier), Some(CrewEfficiency((minimum / 2) as f32 / minimum as f32)));
+}
+
+#[test]
+fn test_crew_evacuation() {
+    let mut world = create_world();
+    world.insert(GameRng(StdRng::seed_from_u64(1)));
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+
+    for _ in 0 .. 20 {
+        create_person(carrier, &mut world, Occupation::Worker);
+    }
+
+    world.write_storage::<Health>().get_mut(carrier).unwrap().0 = 0.0;
+    DestroyShips.run_now(&world);
+    world.maintain();
+
+    assert!(!world.is_alive(carrier));
+
+    // Each crew member gets one roll to make it out
+    let mut rng = StdRng::seed_from_u64(1);
+    let evacuated = (0 .. 20).filter(|_| rng.gen_bool(EVACUATION_CHANCE)).count();
+    let pod_capacity = ShipDefinitions::built_in().get(&ShipType::escape_pod()).unwrap().crew_capacity;
+    assert!(evacuated > 0);
+
+    let (entities, occupation, parent, tag, side): (Entities, ReadStorage<Occupation>, ReadStorage<Parent>, ReadStorage<ShipType>, ReadStorage<Side>) = world.system_data();
+
+    let pods: Vec<Entity> = (&entities, &tag).join()
+        .filter(|(_, tag)| **tag == ShipType::escape_pod())
+        .map(|(entity, _)| entity)
+        .collect();
+
+    assert_eq!(pods.len(), (evacuated + pod_capacity - 1) / pod_capacity);
+    assert_eq!((&occupation, &parent).join().filter(|(_, parent)| pods.contains(&parent.0)).count(), evacuated);
+
+    // The pods are on the same side as the ship
+    for pod in &pods {
+        assert_eq!(side.get(*pod), Some(&Side::Friendly));
+    }
+}
+
+#[test]
+fn test_boarding_resolution() {
+    let resolve = |seed| resolve_boarding(20, 15, &mut StdRng::seed_from_u64(seed));
+
+    let (attackers, defenders) = resolve(7);
+
+    // the same seed gives the same fight
+    assert_eq!(resolve(7), (attackers, defenders));
+
+    assert!(attackers <= 20 && defenders <= 15);
+
+    // A lone defender only gets one shot in before it's overwhelmed
+    assert_eq!(resolve_boarding(100, 1, &mut StdRng::seed_from_u64(7)), (99, 0));
+}
+
+#[test]
+fn test_boarding_capture() {
+    let mut world = create_world();
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    let target = add_ship(&mut world, ShipType::carrier(), Vector3::new(10.0, 0.0, 0.0), Side::Enemy);
+    let fighter = add_ship(&mut world, ShipType::fighter(), Vector3::new(10.0, 0.0, 0.0), Side::Enemy);
+
+    world.write_storage::<Docked>().insert(fighter, Docked).unwrap();
+    world.write_storage::<Parent>().insert(fighter, Parent(target)).unwrap();
+    world.write_storage::<Materials>().insert(target, Materials(StoredResource::full(100.0))).unwrap();
+
+    // The target has no marines to defend it
+    create_person(carrier, &mut world, Occupation::Marine);
+    order(&world, carrier, Command::GoToAnd(target, Interaction::Board));
+
+    LaunchBoardingSystem.run_now(&world);
+    BoardingSystem.run_now(&world);
+
+    assert_eq!(get_data(&world, target), Some(Side::Friendly));
+    assert_eq!(get_data(&world, fighter), Some(Side::Friendly));
+    assert_eq!(world.read_storage::<Materials>().get(carrier).unwrap().amount(), 100.0);
+    assert_eq!(world.read_storage::<Materials>().get(target).unwrap().amount(), 0.0);
+    assert!(world.read_storage::<BoardingAction>().get(target).is_none());
+}
+
+#[test]
+fn test_seeded_boarding() {
+    let mut world = create_world();
+    world.insert(GameRng(StdRng::seed_from_u64(3)));
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    let target = add_ship(&mut world, ShipType::carrier(), Vector3::new(10.0, 0.0, 0.0), Side::Enemy);
+
+    for _ in 0 .. 10 {
+        create_person(carrier, &mut world, Occupation::Marine);
+    }
+
+    for _ in 0 .. 8 {
+        create_person(target, &mut world, Occupation::Marine);
+    }
+
+    order(&world, carrier, Command::GoToAnd(target, Interaction::Board));
+    LaunchBoardingSystem.run_now(&world);
+
+    *world.write_resource() = Secs(BOARDING_ROUND_TIME);
+    BoardingSystem.run_now(&world);
+    world.maintain();
+
+    // The fight in the game is decided by the game's seed
+    let casualties = boarding_round(10, 8, &mut StdRng::seed_from_u64(3));
+    assert_eq!(world.read_storage::<Occupation>().join().count(), 18 - casualties.attackers - casualties.defenders);
+}
+
+#[test]
+fn test_hangar() {
+    let mut world = create_world();
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    let fighter = add_ship(&mut world, ShipType::fighter(), Vector3::new(1.0, 0.0, 0.0), Side::Friendly);
+    let enemy = add_ship(&mut world, ShipType::fighter(), Vector3::new(5.0, 0.0, 0.0), Side::Enemy);
+
+    world.write_storage::<CanAttack>().insert(fighter, CanAttack {delay: 1.0, time: 0.0, range: 50.0}).unwrap();
+    world.write_storage::<AttackTarget>().insert(fighter, AttackTarget {entity: enemy, kamikaze: false}).unwrap();
+    order(&world, fighter, Command::GoToAnd(carrier, Interaction::Dock));
+
+    HangarSystem.run_now(&world);
+
+    assert_eq!(world.read_storage::<Parent>().get(fighter).map(|parent| parent.0), Some(carrier));
+    assert!(world.read_storage::<Docked>().get(fighter).is_some());
+    assert!(world.read_storage::<AttackTarget>().get(fighter).is_none());
+
+    // Docked ships don't fire, even with a target
+    world.write_storage::<AttackTarget>().insert(fighter, AttackTarget {entity: enemy, kamikaze: false}).unwrap();
+    ShootStuffSystem.run_now(&world);
+    assert_eq!(world.read_storage::<CanAttack>().get(fighter).unwrap().time, 0.0);
+
+    // They get repaired while docked
+    *world.write_resource() = Secs(0.25);
+    world.write_storage::<Health>().get_mut(fighter).unwrap().0 = 0.5;
+    DockedSystem.run_now(&world);
+    assert_eq!(world.read_storage::<Health>().get(fighter).unwrap().0, 1.0);
+
+    order(&world, carrier, Command::Launch);
+    HangarSystem.run_now(&world);
+
+    assert!(world.read_storage::<Docked>().get(fighter).is_none());
+    assert!(world.read_storage::<Parent>().get(fighter).is_none());
+    assert!(world.read_storage::<Commands>().get(carrier).unwrap().is_empty());
+}
+
+#[test]
+fn test_command_repeat() {
+    let point = Vector3::new(1.0, 0.0, 0.0);
+
+    let mut commands = Commands(vec![Command::MoveTo(point), Command::Wait(0.0, 5.0), Command::Repeat]);
+
+    commands.finish_current();
+    commands.finish_current();
+
+    // Both commands are added back in before the repeat, with the wait reset
+    match commands.as_slice() {
+        [Command::MoveTo(moving_to), Command::Wait(left, _), Command::Repeat] => {
+            assert_eq!(*moving_to, point);
+            assert_eq!(*left, 5.0);
+        },
+        _ => panic!("unexpected queue: {:?}", commands.0)
+    }
+
+    assert!(commands.move_command(0, 1));
+    assert!(!commands.move_command(0, 3));
+
+    match commands.as_slice() {
+        [Command::Wait(_, _), Command::MoveTo(_), Command::Repeat] => {},
+        _ => panic!("unexpected queue: {:?}", commands.0)
+    }
+}
+
+#[test]
+fn test_queue_editing() {
+    let mut world = create_world();
+    let ship = add_ship(&mut world, ShipType::fighter(), Vector3::zero(), Side::Friendly);
+    select(&world, ship, true);
+    order(&world, ship, Command::MoveTo(Vector3::unit_x()));
+    order(&world, ship, Command::Wait(5.0, 5.0));
+    order(&world, ship, Command::Repeat);
+
+    // Cycling keeps the current command inside the repeated loop
+    world.write_resource::<Controls>().trigger(Action::Cycle);
+    QueueEditSystem.run_now(&world);
+
+    match world.read_storage::<Commands>().get(ship).unwrap().as_slice() {
+        [Command::Wait(_, _), Command::MoveTo(_), Command::Repeat] => {},
+        commands => panic!("unexpected queue: {:?}", commands)
+    }
+
+    world.write_resource::<Controls>().update(0.0, None);
+    world.write_resource::<Controls>().trigger(Action::HoldPosition);
+    QueueEditSystem.run_now(&world);
+    ShipMovementSystem.run_now(&world);
+
+    // Ships that get pushed away head back to where they were told to hold
+    world.write_storage::<Position>().get_mut(ship).unwrap().0 = Vector3::new(5.0, 0.0, 0.0);
+    ShipMovementSystem.run_now(&world);
+
+    assert_eq!(world.read_storage::<Commands>().get(ship).unwrap().as_slice(), &[Command::HoldPosition(Some(Vector3::zero()))]);
+}
+
+#[test]
+fn test_control_groups() {
+    let mut world = create_world();
+    let a = add_ship(&mut world, ShipType::fighter(), Vector3::zero(), Side::Friendly);
+    let b = add_ship(&mut world, ShipType::fighter(), Vector3::zero(), Side::Friendly);
+
+    select(&world, a, true);
+
+    // Control + 1 puts the selected ship into group 1
+    world.write_resource::<Controls>().handle_action(Action::Control, true);
+    world.write_resource::<Controls>().handle_action(Action::Group(1), true);
+    ControlGroupSystem.run_now(&world);
+    world.write_resource::<Controls>().release_all();
+
+    select(&world, a, false);
+    select(&world, b, true);
+
+    // 1 selects the group instead
+    world.write_resource::<Controls>().handle_action(Action::Group(1), true);
+    ControlGroupSystem.run_now(&world);
+
+    assert!(selected(&world, a));
+    assert!(!selected(&world, b));
+}
+
+#[test]
+fn test_double_click_select() {
+    let mut world = create_world();
+    *world.write_resource() = ScreenDimensions(Vector2::new(800.0, 600.0));
+
+    let a = add_ship(&mut world, ShipType::fighter(), Vector3::zero(), Side::Friendly);
+    let b = add_ship(&mut world, ShipType::fighter(), Vector3::zero(), Side::Friendly);
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+
+    let wait = |world: &mut World, secs: f32| {
+        *world.write_resource() = Secs(secs);
+        UpdateControlsSystem.run_now(world);
+    };
+
+    let click = |world: &mut World, entity: Entity| {
+        *world.write_resource() = EntityUnderMouse(Some((entity, Vector3::zero())));
+
+        let mut controls = world.write_resource::<Controls>();
+        controls.handle_left(true);
+        controls.handle_left(false);
+        drop(controls);
+
+        DoubleClickSelectSystem.run_now(world);
+        wait(world, 0.0);
+    };
+
+    // Clicks on different ships don't count
+    click(&mut world, carrier);
+    click(&mut world, a);
+    assert!(!selected(&world, b));
+
+    // Neither do clicks that are too far apart
+    click(&mut world, a);
+    wait(&mut world, 1.0);
+    click(&mut world, a);
+    assert!(!selected(&world, b));
+
+    wait(&mut world, 0.1);
+    click(&mut world, a);
+    assert!(selected(&world, a));
+    assert!(selected(&world, b));
+    assert!(!selected(&world, carrier));
+}
+
+#[test]
+fn test_input_rebinding() {
+    use winit::event::VirtualKeyCode;
+
+    let mut input_map = input::InputMap::default();
+    assert_eq!(input_map.action(VirtualKeyCode::W), Some(Action::CameraForwards));
+
+    input_map.bind(Action::Pause, vec![VirtualKeyCode::W]);
+
+    assert_eq!(input_map.action(VirtualKeyCode::W), Some(Action::Pause));
+    assert_eq!(input_map.action(VirtualKeyCode::P), None);
+    assert_eq!(input_map.action(VirtualKeyCode::Up), Some(Action::CameraForwards));
+    assert!(input_map.help_text().iter().any(|line| line == "W: pause/unpause"));
+
+    let input_map = input::InputMap::parse("(bindings: {Pause: [W]})").unwrap();
+    assert_eq!(input_map.action(VirtualKeyCode::W), Some(Action::Pause));
+
+    // Groups past the last bit of `ControlGroups` would overflow it
+    let error = input::InputMap::parse("(bindings: {Group(16): [G]})").err().unwrap();
+    assert_eq!(error.downcast::<input::InputError>().unwrap(), input::InputError::InvalidGroup(16, 16));
+}
+
+#[test]
+fn test_gamepad_events() {
+    use input::*;
+
+    let world = create_world();
+
+    *world.write_resource() = ScreenDimensions(Vector2::new(1000.0, 1000.0));
+    *world.write_resource() = Secs(0.1);
+    world.write_resource::<Controls>().set_mouse(500.0, 500.0);
+
+    {
+        let mut events = world.write_resource::<Events>();
+        events.push(InputEvent::Gamepad(GamepadEvent::Axis(GamepadAxis::LeftStickX, 1.0)));
+        events.push(InputEvent::Gamepad(GamepadEvent::Axis(GamepadAxis::LeftStickY, 0.05)));
+        events.push(InputEvent::Gamepad(GamepadEvent::Button(GamepadButton::South, true)));
+        events.push(InputEvent::Gamepad(GamepadEvent::Button(GamepadButton::South, false)));
+    }
+
+    EventHandlerSystem.run_now(&world);
+    GamepadSystem.run_now(&world);
+
+    let controls = world.read_resource::<Controls>();
+
+    // The small y movement is inside the dead zone
+    assert_eq!(controls.mouse(), (580.0, 500.0));
+    assert!(controls.left_clicked());
+    drop(controls);
+
+    world.write_resource::<Events>().push(InputEvent::Gamepad(GamepadEvent::Button(GamepadButton::Start, true)));
+    EventHandlerSystem.run_now(&world);
+    assert!(world.read_resource::<Paused>().0);
+
+    // The HUD gets the cursor and the clicks as well
+    {
+        let gui = world.read_resource::<pedot::Gui>();
+        assert_eq!(gui.mouse(), (580.0, 500.0));
+        assert!(gui.mouse_pressed());
+    }
+
+    // So do the menus, which are updated without the game's systems
+    world.write_resource::<pedot::Gui>().clear();
+    world.write_resource::<Events>().push(InputEvent::Gamepad(GamepadEvent::Button(GamepadButton::South, true)));
+    MenuGamepadSystem.run_now(&world);
+
+    {
+        let gui = world.read_resource::<pedot::Gui>();
+        assert_eq!(gui.mouse(), (660.0, 500.0));
+        assert!(gui.mouse_pressed());
+    }
+
+    let help = gamepad_help_text();
+    assert!(help.contains(&"South: left click".to_string()));
+    assert!(help.contains(&format!("Start: {}", Action::Pause.description())));
+}
+
+#[test]
+fn test_formation_offsets() {
+    let mut formation = Formation::Screen;
+
+    loop {
+        let offsets = formation.offsets(7, 4.0);
+        assert_eq!(offsets.len(), 7);
+
+        // Every formation apart from going straight to the target gives each ship its own slot
+        if formation.keeps_shape() {
+            for (i, &a) in offsets.iter().enumerate() {
+                assert!(offsets[i + 1 ..].iter().all(|b| a.distance(*b) > 1.0), "{:?}", formation);
+            }
+        }
+
+        formation.rotate_right();
+
+        if formation == Formation::Screen {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_formation_stragglers() {
+    let mut world = create_world();
+
+    let group = FormationGroup {target: Vector3::new(0.0, 0.0, 100.0), facing: Some(Vector3::unit_z()), started: true};
+    let offsets = [Vector3::new(-4.0, 0.0, 0.0), Vector3::zero(), Vector3::new(4.0, 0.0, 0.0)];
+    let slots: Vec<Vector3<f32>> = offsets.iter().map(|offset| group.slot(Vector3::zero(), *offset)).collect();
+
+    let group = world.create_entity()
+        .with(group)
+        .with(Position(Vector3::zero()))
+        .build();
+
+    let fighters: Vec<Entity> = offsets.iter().zip(slots)
+        .map(|(offset, slot)| {
+            let fighter = add_ship(&mut world, ShipType::fighter(), slot, Side::Friendly);
+            order(&world, fighter, Command::InFormation(group, *offset));
+            fighter
+        })
+        .collect();
+
+    // One fighter has no crew to fly it and another has been told to do something else first
+    world.write_storage::<CrewEfficiency>().insert(fighters[1], CrewEfficiency(0.0)).unwrap();
+    world.write_storage::<Commands>().get_mut(fighters[2]).unwrap().insert_command(0, Command::HoldPosition(None));
+
+    FormationSystem.run_now(&world);
+
+    // The first fighter carries on without them
+    assert!(world.read_storage::<Position>().get(group).unwrap().0.z > 0.0);
+}
+
+#[test]
+fn test_formation_slots() {
+    // A carrier in the middle of a group of fighters gets the middle of the wall
+    let slots = Formation::Wall.slots(&[1.0, 1.0, 4.0, 1.0, 1.0]);
+
+    assert_eq!(slots[2], Vector3::new(0.0, 0.0, 0.0));
+
+    // The slots are spaced to fit the ships on either side of them
+    let mut xs: Vec<f32> = slots.iter().map(|slot| slot.x).collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(xs, vec![-11.0, -7.0, 0.0, 7.0, 11.0]);
+
+    // The biggest ship still fits in the middle of a sphere
+    let slots = Formation::Sphere.slots(&[1.0, 1.0, 4.0, 1.0, 1.0]);
+
+    for (i, &a) in slots.iter().enumerate() {
+        assert!(slots[i + 1 ..].iter().all(|b| a.distance(*b) >= 4.0));
+    }
+}
+
+#[test]
+fn test_directional_thrust() {
+    let facing = Vector3::unit_z();
+
+    // Full thrust forwards
+    assert_eq!(directional_thrust(Vector3::new(0.0, 0.0, 1.0), facing), Vector3::new(0.0, 0.0, 1.0));
+
+    // Much less sideways and backwards
+    assert!(directional_thrust(Vector3::new(1.0, 0.0, 0.0), facing).magnitude() < 0.5);
+    assert!(directional_thrust(Vector3::new(0.0, 0.0, -1.0), facing).magnitude() < 1.0);
+
+    // Heavy ships turn slower than light ones
+    assert!(TurnRate::new(1.0, 2.0).0 > TurnRate::new(200.0, 2000.0).0);
+}
+
+#[test]
+fn test_turning_from_zero_rotation() {
+    let mut world = create_world();
+    let fighter = create_ship(&mut world, ShipType::fighter(), Vector3::zero(), Quaternion::zero(), Side::Friendly).unwrap();
+    world.write_storage::<Velocity>().insert(fighter, Velocity(Vector3::new(1.0, 0.0, 0.0))).unwrap();
+
+    for _ in 0 .. 10 {
+        TurnSystem.run_now(&world);
+    }
+
+    let rotation = world.read_storage::<components::Rotation>().get(fighter).unwrap().0;
+    assert!((rotation.magnitude() - 1.0).abs() < 0.001, "{:?}", rotation);
+}
+
+#[test]
+fn test_predictive_avoidance() {
+    let asteroid = (Vector3::new(1.0, 0.0, 20.0), 5.0);
+    let velocity = Vector3::new(0.0, 0.0, 0.5);
+
+    // Steer to the side of the asteroid that's furthest from its centre
+    let force = predictive_avoidance_force(Vector3::zero(), velocity, 1.0, vec![asteroid].into_iter(), 0.5, 0.01);
+    assert!(force.x < 0.0);
+
+    // Don't worry about things behind the ship
+    let force = predictive_avoidance_force(Vector3::zero(), -velocity, 1.0, vec![asteroid].into_iter(), 0.5, 0.01);
+    assert_eq!(force, Vector3::zero());
+}
+
+#[test]
+fn test_flocking_neighbours() {
+    let neighbour = |flockmate| vec![(Vector3::new(10.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0), flockmate)].into_iter();
+
+    // Ships with a different order don't pull each other along
+    assert_eq!(flocking_force(Vector3::zero(), Vector3::zero(), 1.0, neighbour(false), 1.0, 0.01), Vector3::zero());
+
+    let force = flocking_force(Vector3::zero(), Vector3::zero(), 1.0, neighbour(true), 1.0, 0.01);
+    assert!(force.x > 0.0 && force.z > 0.0);
+}
+
+#[test]
+fn test_pathfinding() {
+    use pathfinding::*;
+
+    let start = Vector3::new(0.0, 0.0, -100.0);
+    let end = Vector3::new(0.0, 0.0, 100.0);
+    let asteroids = [Obstacle::new(Vector3::zero(), 30.0)];
+
+    // Nothing in the way
+    assert_eq!(find_path(start, end, &[], 1.0), vec![end]);
+
+    let path = find_path(start, end, &asteroids, 1.0);
+
+    assert!(path.len() > 1);
+    assert_eq!(path.last(), Some(&end));
+
+    // None of the legs go through the asteroid
+    let mut last = start;
+
+    for waypoint in path {
+        assert!(!asteroids[0].blocks(last, waypoint, 1.0));
+        last = waypoint;
+    }
+}
+
+#[test]
+fn test_formation_pathfinding() {
+    use pathfinding::Obstacle;
+
+    let mut world = create_world();
+    let start = Vector3::new(0.0, 0.0, -100.0);
+    let target = Vector3::new(0.0, 0.0, 100.0);
+
+    // An asteroid in the way
+    world.create_entity()
+        .with(Position(Vector3::zero()))
+        .with(Size(30.0))
+        .build();
+
+    let group = FormationGroup {target, facing: Some(Vector3::unit_z()), started: true};
+    let offsets = [Vector3::new(-4.0, 0.0, 0.0), Vector3::new(4.0, 0.0, 0.0)];
+    let slots: Vec<Vector3<f32>> = offsets.iter().map(|offset| group.slot(start, *offset)).collect();
+
+    let group = world.create_entity()
+        .with(group)
+        .with(Position(start))
+        .build();
+
+    for (offset, slot) in offsets.iter().zip(slots) {
+        let fighter = add_ship(&mut world, ShipType::fighter(), slot, Side::Friendly);
+        order(&world, fighter, Command::InFormation(group, *offset));
+    }
+
+    PathfindingSystem.run_now(&world);
+
+    let waypoints = world.read_storage::<Path>().get(group).unwrap().waypoints.clone();
+    assert!(waypoints.len() > 1);
+    assert_eq!(waypoints.last(), Some(&target));
+
+    // The whole formation fits past the asteroid
+    let mut last = start;
+
+    for waypoint in &waypoints {
+        assert!(!Obstacle::new(Vector3::zero(), 30.0).blocks(last, *waypoint, 5.0));
+        last = *waypoint;
+    }
+
+    // The centre heads for the first waypoint instead of straight through the asteroid
+    FormationSystem.run_now(&world);
+
+    let centre = world.read_storage::<Position>().get(group).unwrap().0;
+    let heading = (centre - start).normalize();
+    assert!(heading.dot((waypoints[0] - start).normalize()) > 0.999);
+}
+
+#[test]
+fn test_shared_paths() {
+    let mut world = create_world();
+    let destination = Vector3::new(0.0, 0.0, 100.0);
+
+    // An asteroid in the way
+    world.create_entity()
+        .with(Position(Vector3::zero()))
+        .with(Size(30.0))
+        .build();
+
+    let fighters: Vec<Entity> = [-5.0, 5.0].iter()
+        .map(|x| {
+            let fighter = add_ship(&mut world, ShipType::fighter(), Vector3::new(*x, 0.0, -100.0), Side::Friendly);
+            order(&world, fighter, Command::MoveTo(destination));
+            fighter
+        })
+        .collect();
+
+    // A moon in the way of another ship, which would have moved on by the time it got there
+    let moon = Orbit {centre: Vector3::new(100.0, 0.0, 0.0), radius: 100.0, angle: 0.0, speed: 0.1};
+
+    world.create_entity()
+        .with(Position(moon.position()))
+        .with(Size(10.0))
+        .with(moon)
+        .build();
+
+    let past_moon = Vector3::new(200.0, 0.0, 100.0);
+    let fighter = add_ship(&mut world, ShipType::fighter(), Vector3::new(200.0, 0.0, -100.0), Side::Friendly);
+    order(&world, fighter, Command::MoveTo(past_moon));
+
+    PathfindingSystem.run_now(&world);
+
+    // Both fighters got the same order, so they take the same way round
+    let paths = world.read_storage::<Path>();
+    let waypoints = &paths.get(fighters[0]).unwrap().waypoints;
+
+    assert!(waypoints.len() > 1);
+    assert_eq!(&paths.get(fighters[1]).unwrap().waypoints, waypoints);
+
+    let mut last = Vector3::new(-5.0, 0.0, -100.0);
+
+    for waypoint in waypoints {
+        assert!(!pathfinding::Obstacle::new(Vector3::zero(), 30.0).blocks(last, *waypoint, 1.0));
+        last = *waypoint;
+    }
+
+    assert_eq!(paths.get(fighter).unwrap().waypoints, vec![past_moon]);
+}
+
+#[test]
+fn test_system_generation() {
+    use star_system::*;
+
+    let types = [SystemType::Asteroids, SystemType::Planetoid, SystemType::Nebula, SystemType::BlackHole];
+
+    for system_type in &types {
+        let definitions = ShipDefinitions::built_in();
+        let features = generate_features(*system_type, &definitions, &mut StdRng::seed_from_u64(0));
+
+        // The same seed always gives the same system
+        assert_eq!(features, generate_features(*system_type, &definitions, &mut StdRng::seed_from_u64(0)));
+
+        let count = |matches: fn(&SystemFeature) -> bool| features.iter().filter(|feature| matches(feature)).count();
+
+        match system_type {
+            SystemType::Asteroids => assert!(count(|f| match f { SystemFeature::Asteroid {..} => true, _ => false }) >= 40),
+            SystemType::Planetoid => {
+                assert_eq!(count(|f| match f { SystemFeature::Planetoid {..} => true, _ => false }), 1);
+                assert!(count(|f| match f { SystemFeature::Moon {..} => true, _ => false }) >= 2);
+            },
+            SystemType::Nebula => assert!(count(|f| match f { SystemFeature::NebulaCloud {..} => true, _ => false }) >= 4),
+            SystemType::BlackHole => assert_eq!(count(|f| match f { SystemFeature::BlackHole {..} => true, _ => false }), 1)
+        }
+
+        // Scattered things are kept away from the middle, where the fleet starts
+        if *system_type != SystemType::Asteroids {
+            for feature in &features {
+                if let SystemFeature::Derelict {position, ..} | SystemFeature::Asteroid {position, ..} = feature {
+                    let distance = Vector2::new(position.x, position.z).magnitude();
+                    assert!(distance > 499.9 && distance < 1000.1);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_sensor_range() {
+    let mut world = create_world();
+
+    world.create_entity()
+        .with(Position(Vector3::zero()))
+        .with(Nebula {radius: 50.0})
+        .build();
+
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::new(0.0, 0.0, -40.0), Side::Friendly);
+    let enemy = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Enemy);
+
+    world.write_storage::<CanAttack>().insert(carrier, CanAttack {delay: 1.0, time: 0.0, range: 50.0}).unwrap();
+    order(&world, carrier, Command::GoToAnd(enemy, Interaction::Attack));
+
+    // The enemy is in range, but hidden in the nebula
+    ShipMovementSystem.run_now(&world);
+    assert!(world.read_storage::<AttackTarget>().get(carrier).is_none());
+
+    world.write_storage::<Position>().insert(carrier, Position(Vector3::new(0.0, 0.0, -10.0))).unwrap();
+    ShipMovementSystem.run_now(&world);
+    assert_eq!(world.read_storage::<AttackTarget>().get(carrier).map(|target| target.entity), Some(enemy));
+}
+
+#[test]
+fn test_salvage() {
+    let mut world = create_world();
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    let derelict = add_ship(&mut world, ShipType::tanker(), Vector3::new(5.0, 0.0, 0.0), Side::Neutral);
+
+    world.write_storage::<Salvage>().insert(derelict, Salvage(StoredResource::full(10.0))).unwrap();
+    for _ in 0 .. 3 {
+        create_person(derelict, &mut world, Occupation::Worker);
+    }
+
+    let survivors: Vec<Entity> = (&world.entities(), &world.read_storage::<Occupation>()).join()
+        .map(|(survivor, _)| survivor)
+        .collect();
+
+    order(&world, carrier, Command::GoToAnd(derelict, Interaction::Salvage));
+    *world.write_resource() = Secs(1.0);
+
+    SalvageSystem.run_now(&world);
+
+    // The survivors are brought aboard straight away, but stripping the parts takes time
+    for survivor in &survivors {
+        assert_eq!(world.read_storage::<Parent>().get(*survivor).map(|parent| parent.0), Some(carrier));
+    }
+
+    assert_eq!(world.read_storage::<Materials>().get(carrier).unwrap().amount(), 5.0);
+    assert!(!world.read_storage::<Commands>().get(carrier).unwrap().is_empty());
+    assert!(world.read_resource::<SpareComponents>().0.is_empty());
+
+    SalvageSystem.run_now(&world);
+    SalvageSystem.run_now(&world);
+
+    assert_eq!(world.read_storage::<Materials>().get(carrier).unwrap().amount(), 10.0);
+    assert!(world.read_storage::<Salvage>().get(derelict).unwrap().is_empty());
+    assert!(world.read_storage::<Commands>().get(carrier).unwrap().is_empty());
+
+    // The tanker's components come out once it's been stripped
+    let loadout = ShipDefinitions::built_in().default_components(&ShipType::tanker(), 0).unwrap().into_inner();
+
+    assert!(!loadout.is_empty());
+    assert_eq!(world.read_resource::<SpareComponents>().0.len(), loadout.len());
+}
+
+#[test]
+fn test_stations() {
+    let mut world = create_world();
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+
+    let station = world.create_entity()
+        .with(Position(Vector3::new(5.0, 0.0, 0.0)))
+        .with(Size(5.0))
+        .with(Station {recruits: 2})
+        .build();
+
+    let max_health = ShipDefinitions::built_in().get(&ShipType::carrier()).unwrap().max_health();
+    world.write_storage::<Health>().get_mut(carrier).unwrap().0 = max_health - 5.0;
+    order(&world, carrier, Command::GoToAnd(station, Interaction::Repair));
+    *world.write_resource() = Secs(0.5);
+
+    StationSystem.run_now(&world);
+    assert_eq!(world.read_storage::<Health>().get(carrier).unwrap().0, max_health - 2.5);
+    assert!(!world.read_storage::<Commands>().get(carrier).unwrap().is_empty());
+
+    StationSystem.run_now(&world);
+    assert_eq!(world.read_storage::<Health>().get(carrier).unwrap().0, max_health);
+    assert!(world.read_storage::<Commands>().get(carrier).unwrap().is_empty());
+
+    // Only as many people join as the station has, even with materials for more
+    world.write_storage::<Materials>().get_mut(carrier).unwrap().increase(100.0);
+    order(&world, carrier, Command::GoToAnd(station, Interaction::Trade));
+
+    StationSystem.run_now(&world);
+    world.maintain();
+
+    let (occupation, parent): (ReadStorage<Occupation>, ReadStorage<Parent>) = world.system_data();
+    assert_eq!((&occupation, &parent).join().filter(|(_, parent)| parent.0 == carrier).count(), 2);
+    assert_eq!(world.read_storage::<Materials>().get(carrier).unwrap().amount(), 100.0 - 2.0 * systems::RECRUIT_COST);
+    assert_eq!(world.read_storage::<Station>().get(station).unwrap().recruits, 0);
+}
+
+#[test]
+fn test_encounter_triggers() {
+    use encounters::*;
+
+    // The built in encounters have to parse
+    assert!(!Encounters::built_in().0.is_empty());
+
+    let rationing = Encounter {
+        name: "Rationing".into(),
+        text: String::new(),
+        trigger: Trigger::LowFood(0.2),
+        conditions: vec![Condition::MinPopulation(10)],
+        chance: 1.0,
+        repeatable: false,
+        choices: Vec::new()
+    };
+
+    let mut status = FleetStatus {
+        population: 20,
+        food: 50.0,
+        food_capacity: 100.0,
+        ..FleetStatus::default()
+    };
+
+    assert!(!rationing.triggered(&status));
+
+    status.food = 10.0;
+    assert!(rationing.triggered(&status));
+
+    status.population = 5;
+    assert!(!rationing.triggered(&status));
+}
+
+#[test]
+fn test_encounters_on_arrival() {
+    use encounters::*;
+    use input::{GamepadEvent, GamepadButton};
+
+    let mut world = create_world();
+    populate_world(&mut world, 0, Difficulty::Normal);
+
+    let arrival = Encounter {
+        name: "Arrival".into(),
+        text: String::new(),
+        trigger: Trigger::Arrival,
+        conditions: Vec::new(),
+        chance: 1.0,
+        repeatable: true,
+        choices: Vec::new()
+    };
+
+    world.insert(Encounters(vec![arrival]));
+
+    // Starting the game isn't arriving in a system
+    EncounterSystem.run_now(&world);
+    assert!(world.read_resource::<EncounterState>().active.is_none());
+
+    world.write_resource::<EncounterState>().system = None;
+    EncounterSystem.run_now(&world);
+    assert!(world.read_resource::<EncounterState>().active.is_some());
+    assert!(world.read_resource::<Paused>().0);
+
+    // Encounters can't be unpaused without making a choice
+    world.write_resource::<Events>().push(InputEvent::Gamepad(GamepadEvent::Button(GamepadButton::Start, true)));
+    EventHandlerSystem.run_now(&world);
+    assert!(world.read_resource::<Paused>().0);
+}
+
+#[test]
+fn test_ship_definitions() {
+    let definitions = ShipDefinitions::built_in();
+    let carrier = definitions.get(&ShipType::carrier()).unwrap();
+
+    assert_eq!(carrier.hangar_capacity, Some(60));
+    assert_eq!(definitions.default_components(&ShipType::carrier(), 0).unwrap().thrust(), 200.0);
+    assert!(definitions.get(&ShipType::new("Corvette")).is_none());
+    assert!(definitions.default_components(&ShipType::new("Corvette"), 0).is_none());
+
+    let mut world = create_world();
+    assert!(create_ship(&mut world, ShipType::new("Corvette"), Vector3::zero(), Quaternion::one(), Side::Friendly).is_none());
+
+    let unknown_component = r#"(
+        components: [],
+        ships: [(name: "Corvette", model: "Fighter", mass: 10.0, size: 1.5, crew_capacity: 5, minimum_crew: 2, slots: 1, loadout: ["Warp Core"])],
+    )"#;
+
+    let error = ShipDefinitions::parse(unknown_component).err().unwrap();
+    assert_eq!(error.downcast::<DefinitionError>().unwrap(), DefinitionError::UnknownComponent("Corvette".into(), "Warp Core".into()));
+}
+
+#[test]
+fn test_model_manifest() {
+    let assets = context::Assets::embedded();
+    let definitions = ShipDefinitions::built_in();
+
+    for ship in definitions.iter() {
+        assert!(assets.models.contains_key(&ship.model), "'{}' has no model", ship.name);
+    }
+
+    let missing_file = r#"[(name: "Cruiser", obj: "cruiser.obj", diffuse: "Carrier")]"#;
+    let error = context::parse_manifest(missing_file, &std::collections::HashMap::new()).err().unwrap();
+    assert_eq!(error.downcast::<context::AssetError>().unwrap(), context::AssetError::MissingFile("Cruiser".into(), "cruiser.obj".into()));
+
+    // Textures are looked up by name, so a typo is only found when the model is loaded
+    let cruiser = context::ModelDefinition {
+        name: "Cruiser".into(),
+        obj: "cruiser.obj".into(),
+        diffuse: "Carrier".into(),
+        specular: Some("CruiserSpecular".into())
+    };
+
+    let files = vec![("cruiser.obj".to_string(), Vec::new())].into_iter().collect();
+    let error = context::load_model(&cruiser, &context::TextureAtlas::embedded(), &files).err().unwrap();
+    assert_eq!(error, context::AssetError::UnknownTexture("Cruiser".into(), "CruiserSpecular".into()));
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn test_asset_loading() {
+    let assets = context::Assets::load(context::ASSET_DIR).unwrap();
+    let embedded = context::Assets::embedded();
+
+    for model in embedded.models.keys() {
+        assert!(assets.models.contains_key(model), "'{:?}' wasn't loaded", model);
+    }
+
+    assert!(context::Assets::load(std::env::temp_dir().join("fleet_commander_missing_assets")).is_err());
+
+    // A resources directory without any textures or models in it
+    let broken = std::env::temp_dir().join("fleet_commander_broken_assets");
+    std::fs::create_dir_all(broken.join("models")).unwrap();
+    std::fs::write(broken.join("models").join("models.ron"), "[]").unwrap();
+
+    assert!(context::Assets::load(&broken).is_err());
+    std::fs::remove_dir_all(&broken).unwrap();
+}
+
+#[test]
+fn test_ship_stats_reload() {
+    let mut world = create_world();
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    let speed = world.read_storage::<MaxSpeed>().get(carrier).unwrap().0;
+
+    let heavier = include_str!("../ships.ron").replace("mass: 2000.0", "mass: 4000.0");
+    world.insert(ShipDefinitions::parse(&heavier).unwrap());
+    update_ship_stats(&mut world);
+
+    assert_eq!(world.read_storage::<MaxSpeed>().get(carrier).unwrap().0, speed / 2.0);
+}
+
+#[test]
+fn test_frustum_culling() {
+    let camera = camera::Camera::default();
+    let frustum = camera.frustum(9.0 / 16.0);
+
+    // The camera looks at the origin
+    assert!(frustum.contains_sphere(Vector3::zero(), 1.0));
+    // Behind the camera
+    assert!(!frustum.contains_sphere(camera.position() + camera.direction() * 10.0, 1.0));
+    // Off to the side, unless it's big enough to reach into view
+    assert!(!frustum.contains_sphere(Vector3::new(1000.0, 0.0, 0.0), 1.0));
+    assert!(frustum.contains_sphere(Vector3::new(1000.0, 0.0, 0.0), 1000.0));
+}
+
+#[test]
+fn test_particles() {
+    use particles::*;
+
+    let effects = Effects::built_in();
+    let mut particles = Particles::default();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    particles.burst(&effects, Effect::WeaponImpact, Vector3::zero(), 1.0, &mut rng);
+    assert_eq!(particles.iter().count(), effects.get(Effect::WeaponImpact).count);
+
+    particles.burst(&effects, Effect::Explosion, Vector3::zero(), 10.0, &mut rng);
+    assert_eq!(particles.iter().last().unwrap().size(&effects), 10.0);
+
+    // Explosions last longer than impacts, so they're the only particles left
+    particles.step(&effects, effects.get(Effect::WeaponImpact).lifetime);
+    assert_eq!(particles.iter().count(), 1);
+
+    particles.step(&effects, effects.get(Effect::Explosion).lifetime);
+    assert_eq!(particles.iter().count(), 0);
+}
+
+#[test]
+fn test_animations() {
+    use context::{Animation, Image};
+
+    assert_eq!(Animation::Explosion.frames(), &[Image::Explosion1, Image::Explosion2, Image::Explosion3, Image::Explosion4, Image::Explosion5, Image::Explosion6]);
+    assert!(!Animation::Explosion.looping());
+
+    let mut world = create_world();
+
+    let entity = world.create_entity()
+        .with(Animated {animation: Animation::Explosion, time: 0.0})
+        .build();
+
+    *world.write_resource() = Secs(Animation::Explosion.frame_duration() * 2.5);
+    AnimationSystem.run_now(&world);
+    assert_eq!(get_data::<Image>(&world, entity), Some(Image::Explosion3));
+
+    // It stops on the last frame
+    *world.write_resource() = Secs(100.0);
+    AnimationSystem.run_now(&world);
+    assert_eq!(get_data::<Image>(&world, entity), Some(Image::Explosion6));
+}
+
+// Move the mouse to a point on the screen and press the left button there
+#[allow(deprecated)]
+fn click_gui(world: &World, x: f32, y: f32) {
+    use winit::event::*;
+    use winit::dpi::PhysicalPosition;
+
+    let mut gui = world.write_resource::<pedot::Gui>();
+    let device_id = unsafe { DeviceId::dummy() };
+    gui.update(&WindowEvent::CursorMoved {device_id, position: PhysicalPosition::new(x as f64, y as f64), modifiers: ModifiersState::empty()});
+    gui.update(&WindowEvent::MouseInput {device_id, state: ElementState::Pressed, button: MouseButton::Left, modifiers: ModifiersState::empty()});
+}
+
+#[test]
+fn test_hud_select_type() {
+    let mut world = create_world();
+    world.insert(pedot::Gui::new(800.0, 600.0));
+
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    let fighter = add_ship(&mut world, ShipType::fighter(), Vector3::zero(), Side::Friendly);
+
+    for entity in &[carrier, fighter] {
+        select(&world, *entity, true);
+    }
+
+    HudSystem.run_now(&world);
+    assert_eq!(world.read_resource::<ui::Hud>().selected_types, vec![ShipType::carrier(), ShipType::fighter()]);
+
+    // Click on the fighters, which are the last entry in the list
+    let rect = world.read_resource::<ui::Hud>().selection.rect();
+    click_gui(&world, rect.x + 20.0, rect.y + rect.height - pedot::ROW_HEIGHT);
+    HudSystem.run_now(&world);
+
+    assert!(world.read_resource::<ui::Hud>().mouse_over);
+    assert!(!selected(&world, carrier));
+    assert!(selected(&world, fighter));
+}
+
+#[test]
+fn test_hud_command_buttons() {
+    let mut world = create_world();
+    world.insert(pedot::Gui::new(800.0, 600.0));
+
+    let carrier = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Friendly);
+    select(&world, carrier, true);
+
+    HudSystem.run_now(&world);
+    assert!(!world.read_resource::<ui::Hud>().commands.is_empty());
+
+    // Click on the first button, which launches ships
+    let rect = world.read_resource::<ui::Hud>().commands.rect();
+    click_gui(&world, rect.x + 20.0, rect.y + pedot::ROW_HEIGHT);
+    HudSystem.run_now(&world);
+
+    assert!(world.read_resource::<Controls>().triggered(Action::Launch));
+
+    // Enemy ships can't be given orders
+    select(&world, carrier, false);
+    let enemy = add_ship(&mut world, ShipType::carrier(), Vector3::zero(), Side::Enemy);
+    select(&world, enemy, true);
+
+    HudSystem.run_now(&world);
+    assert!(world.read_resource::<ui::Hud>().commands.is_empty());
+}
+
+#[test]
+fn test_new_game_seeds() {
+    use menus::*;
+
+    let positions = |seed| {
+        let mut world = create_world();
+        populate_world(&mut world, seed, Difficulty::Hard);
+        let positions: Vec<Position> = world.read_storage::<Position>().join().cloned().collect();
+        positions
+    };
+
+    assert_eq!(positions(3), positions(3));
+    assert_ne!(positions(3), positions(4));
+
+    // Numbers are used as they are, and other text is hashed
+    let options = NewGameOptions {seed: "asteroids".into(), difficulty: Difficulty::Easy};
+    assert_eq!(options.seed(), options.seed());
+    assert_eq!(options.seed(), 0xa77c_eb98_89aa_b119);
+    assert_eq!(NewGameOptions {seed: "42".into(), ..options}.seed(), 42);
+}
+
+#[test]
+fn test_save_browser() {
+    use menus::*;
+    use winit::event::WindowEvent;
+
+    let mut gui = pedot::Gui::new(800.0, 600.0);
+    let mut menus = Menus::default();
+    menus.open(GameState::Playing);
+    menus.open(GameState::Saves(SaveMode::Save));
+
+    for character in "my save!".chars() {
+        gui.update(&WindowEvent::ReceivedCharacter(character));
+        assert_eq!(menus.update(&gui), None);
+        gui.clear();
+    }
+
+    // Slot names are file names, so they can't have punctuation in them
+    assert_eq!(menus.slot_name, "my save");
+    assert_eq!(slot_path(&menus.slot_name), std::path::Path::new("saves/my save.sav"));
+    assert!(!menus.in_game());
+}
@@ -1,45 +1,69 @@
-use context::*;
+// The HUD, made out of pedot panels. They're filled in every frame by `HudSystem` and drawn by `RenderHud`.
+
 use pedot::*;
-use odds::vec::*;
+use crate::input::{Action, InputMap};
+use crate::ships::ShipType;
 
-pub struct Button {
-    x: HorizontalAlign,
-    y: VerticalAlign,
-    image: Image
-}
+pub const MATERIALS_COLOUR: [f32; 3] = [0.5, 0.4, 0.2];
+pub const FOOD_COLOUR: [f32; 3] = [0.2, 0.5, 0.2];
+pub const HEALTH_COLOUR: [f32; 3] = [0.6, 0.15, 0.15];
+pub const CREW_COLOUR: [f32; 3] = [0.2, 0.3, 0.6];
 
-impl Button {
-    pub fn new(x: HorizontalAlign, y: VerticalAlign, image: Image) -> Self {
-        Self {
-            x, y, image
-        }
-    }
+// The buttons on the command card, which do the same thing as pressing the action's key
+const COMMANDS: [(&str, Action); 6] = [
+    ("Launch", Action::Launch),
+    ("Hold position", Action::HoldPosition),
+    ("Wait", Action::Wait),
+    ("Repeat orders", Action::Repeat),
+    ("Remove last order", Action::RemoveLast),
+    ("Cycle orders", Action::Cycle)
+];
 
-    fn location(&self, context: &Context) -> (f32, f32, f32, f32) {
-        let (width, height) = context.resources.image_dimensions(self.image);
-        let (width, height) = (width as f32 * 2.0, height as f32 * 2.0);
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HudAction {
+    Trigger(Action),
+    // Narrow the selection down to one of the types in the selection panel's list
+    SelectType(usize)
+}
 
-        let x = context.gui.x_absolute(self.x * width + width / 2.0);
-        let y = context.gui.y_absolute(self.y * height + height / 2.0);
+pub struct Hud {
+    pub resources: Panel<HudAction>,
+    pub selection: Panel<HudAction>,
+    pub commands: Panel<HudAction>,
+    // The ship types in the selection panel's list, in order
+    pub selected_types: Vec<ShipType>,
+    // Clicks on the HUD shouldn't also select ships or give orders
+    pub mouse_over: bool
+}
 
-        (x, y, width, height)
+impl Hud {
+    pub fn panels(&self) -> [&Panel<HudAction>; 3] {
+        [&self.resources, &self.selection, &self.commands]
     }
 
-    fn state(&self, context: &Context) -> ButtonState {
-        let (x, y, width, height) = self.location(context);
-        context.gui.button(x, y, width, height)
+    pub fn panels_mut(&mut self) -> [&mut Panel<HudAction>; 3] {
+        [&mut self.resources, &mut self.selection, &mut self.commands]
     }
 
-    pub fn render(&self, context: &mut Context) {
-        let (x, y, width, height) = self.location(context);
-
-        let colour = if self.state(context).is_hovering() {
-            [0.0, 0.0, 0.0, 0.25]
-        } else {
-            [0.0; 4]
-        };
+    // The tooltips show the keys for the actions, which can be rebound
+    pub fn command_buttons(input_map: &InputMap) -> Vec<Widget<HudAction>> {
+        COMMANDS.iter()
+            .map(|(text, action)| {
+                let tooltip = format!("{}: {}", input_map.key_names(*action), action.description());
+                Widget::button(*text, HudAction::Trigger(*action)).with_tooltip(tooltip)
+            })
+            .collect()
+    }
+}
 
-        context.render_image(Image::Button, x, y, width, height, colour);
-        context.render_image(self.image, x, y, width, height, colour);
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            resources: Panel::new(HorizontalAlign::Right(10.0), VerticalAlign::Top(10.0), 250.0),
+            selection: Panel::new(HorizontalAlign::Left(10.0), VerticalAlign::Bottom(10.0), 250.0),
+            commands: Panel::new(HorizontalAlign::Right(10.0), VerticalAlign::Bottom(10.0), 200.0),
+            selected_types: Vec::new(),
+            mouse_over: false
+        }
     }
-}
\ No newline at end of file
+}
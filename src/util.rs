@@ -2,7 +2,6 @@ use cgmath::*;
 use std::f32::consts::*;
 use std::ops::*;
 use std::collections::*;
-use rand::rngs::*;
 use rand::Rng;
 use std::cmp::*;
 
@@ -102,7 +101,7 @@ pub fn summarize<T: Ord, I: Iterator<Item=T>>(iterator: I) -> (BTreeMap<T, u64>,
     )
 }
 
-pub fn uniform_sphere_distribution(rng: &mut ThreadRng) -> Vector3<f32> {
+pub fn uniform_sphere_distribution<R: Rng>(rng: &mut R) -> Vector3<f32> {
     uniform_sphere_distribution_from_coords(rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0))
 }
 
@@ -166,12 +165,18 @@ pub fn point_to_na_point(point: Point3<f32>) -> nalgebra::Point3<f32> {
     nalgebra::Point3::new(point.x, point.y, point.z)
 }
 
-pub fn make_iso(pos: Vector3<f32>, rot: Quaternion<f32>) -> nalgebra::Isometry3<f32> {
-    let quat = if rot == Quaternion::zero() {
-        nalgebra::Quaternion::identity()
+// Ships start out with a zeroed rotation, which is treated as no rotation at all
+pub fn valid_rotation(rot: Quaternion<f32>) -> Quaternion<f32> {
+    if rot == Quaternion::zero() {
+        Quaternion::one()
     } else {
-        nalgebra::Quaternion::new(rot.s, rot.v.x, rot.v.y, rot.v.z)
-    };
+        rot
+    }
+}
+
+pub fn make_iso(pos: Vector3<f32>, rot: Quaternion<f32>) -> nalgebra::Isometry3<f32> {
+    let rot = valid_rotation(rot);
+    let quat = nalgebra::Quaternion::new(rot.s, rot.v.x, rot.v.y, rot.v.z);
 
     nalgebra::Isometry3::from_parts(
         nalgebra::Translation3::new(pos.x, pos.y, pos.z),
@@ -0,0 +1,76 @@
+// Polls files for changes, so that assets and data files can be reloaded without restarting the game
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct FileWatcher {
+    // Files, or directories whose files are watched (but not their subdirectories)
+    paths: Vec<PathBuf>,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant
+}
+
+impl FileWatcher {
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> Self {
+        let mut watcher = Self {
+            paths: paths.iter().map(|path| path.as_ref().to_path_buf()).collect(),
+            modified: HashMap::new(),
+            last_poll: Instant::now()
+        };
+
+        watcher.modified = watcher.scan();
+        watcher
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut modified = HashMap::new();
+
+        for path in &self.paths {
+            if path.is_dir() {
+                let files = std::fs::read_dir(path).into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file());
+
+                for file in files {
+                    insert_modified(&mut modified, file);
+                }
+            } else {
+                insert_modified(&mut modified, path.clone());
+            }
+        }
+
+        modified
+    }
+
+    // The files that have been changed, added or removed since the last poll
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+
+        self.last_poll = Instant::now();
+
+        let modified = self.scan();
+
+        let mut changed: Vec<PathBuf> = modified.iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        changed.extend(self.modified.keys().filter(|path| !modified.contains_key(*path)).cloned());
+
+        self.modified = modified;
+        changed
+    }
+}
+
+fn insert_modified(modified: &mut HashMap<PathBuf, SystemTime>, path: PathBuf) {
+    if let Ok(time) = std::fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+        modified.insert(path, time);
+    }
+}